@@ -9,10 +9,10 @@ let s:pyfile = has('python3') ? 'py3file' : 'pyfile'
 let s:plugin_root_dir = fnamemodify(g:clap#autoload_dir, ':h')
 
 if has('win32')
-  let s:LIB = '\pythonx\clap\fuzzymatch_rs.pyd'
+  let s:LIB = '\pythonx\clap\fulf_py.pyd'
   let s:SETUP_PY = '\setup_python.py'
 else
-  let s:LIB = '/pythonx/clap/fuzzymatch_rs.so'
+  let s:LIB = '/pythonx/clap/fulf_py.so'
   let s:SETUP_PY = '/setup_python.py'
 endif
 
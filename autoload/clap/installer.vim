@@ -42,9 +42,9 @@ function! s:run_term(cmd, cwd, success_info) abort
 endfunction
 
 if has('win32')
-  let s:from = '.\fuzzymatch-rs\target\release\fuzzymatch_rs.dll'
-  let s:to = 'fuzzymatch_rs.pyd'
-  let s:rust_ext_cmd = printf('pushd fuzzymatch-rs && cargo build --release && popd && copy %s %s', s:from, s:to)
+  let s:from = '.\fulf-py\target\release\fulf_py.dll'
+  let s:to = 'fulf_py.pyd'
+  let s:rust_ext_cmd = printf('pushd fulf-py && cargo build --release && popd && copy %s %s', s:from, s:to)
   let s:rust_ext_cwd = s:plugin_root_dir.'\pythonx\clap'
   let s:prebuilt_maple_binary = s:plugin_root_dir.'\bin\maple.exe'
   let s:maple_cargo_toml = s:plugin_root_dir.'\Cargo.toml'
@@ -0,0 +1,249 @@
+//! C ABI of the searcher, for editors and tools, that can't use Rust directly.
+//!
+//! The header is generated with `cbindgen --config cbindgen.toml --output fulf.h`.
+//! The library is `libfulf_ffi` (`fulf_ffi.dll` on Windows), link it with `-lfulf_ffi`:
+//! it's not named `fulf`, so it doesn't collide with the searcher crate itself.
+//!
+//! The search runs on its own threads:
+//!
+//! ```c
+//! FulfSearch *search = fulf_search("/path/to/project", "needle", 100);
+//! /* fulf_cancel(search) could be called from any thread. */
+//! size_t len = fulf_wait(search);
+//! for (size_t i = 0; i < len; ++i) {
+//!     size_t positions_len;
+//!     const size_t *positions = fulf_result_positions(search, i, &positions_len);
+//!     printf("%d %s\n", fulf_result_score(search, i), fulf_result_line(search, i));
+//! }
+//! fulf_free(search);
+//! ```
+
+use {
+    fulf::{
+        filepath_cache::{serialize, NotUtf8},
+        fzy_search,
+        merger::{ByScore, Merger},
+        CancelToken, Rules,
+    },
+    std::{
+        ffi::{CStr, CString},
+        os::raw::c_char,
+        path::Path,
+        ptr,
+        sync::Arc,
+        thread::{self, JoinHandle},
+    },
+};
+
+type FfiResult = (CString, i32, Box<[usize]>);
+
+/// The running or finished search.
+pub struct FulfSearch {
+    cancel: CancelToken,
+    thread: Option<JoinHandle<Vec<FfiResult>>>,
+    results: Vec<FfiResult>,
+}
+
+/// Starts the search of the `needle` in the files of the `root` folder,
+/// returns right away.
+///
+/// Only the best `number` results are kept.
+///
+/// Returns null if any of the strings is null or not UTF-8,
+/// if the needle is empty or longer than the default `max_line_len` of the rules,
+/// or if the `number` is 0.
+///
+/// # Safety
+///
+/// `root` and `needle` should be null or valid nul-terminated strings.
+/// The returned search should be freed with the `fulf_free`.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_search(
+    root: *const c_char,
+    needle: *const c_char,
+    number: usize,
+) -> *mut FulfSearch {
+    let (root, needle) = match (to_str(root), to_str(needle)) {
+        (Some(root), Some(needle)) => (root.to_owned(), needle.to_owned()),
+        _ => return ptr::null_mut(),
+    };
+
+    let cancel = CancelToken::new();
+    let r = match Rules::builder()
+        .cancel(cancel.clone())
+        .results_cap(number)
+        .build()
+    {
+        Ok(r) if r.check_needle(&needle).is_ok() => r,
+        _ => return ptr::null_mut(),
+    };
+
+    let thread = thread::spawn(move || {
+        let builder = match r.walk_builder(&root) {
+            Ok(builder) => builder,
+            Err(_) => return Vec::new(),
+        };
+        let cache = match serialize(&root, builder, NotUtf8::IgnorePath, |_| ()) {
+            Ok(cache) => Arc::new(cache),
+            Err(_) => return Vec::new(),
+        };
+
+        let mut merger = Merger::new(number, ByScore);
+        let _any_result = fzy_search(Path::new(&root).into(), needle.into(), cache, r, |batch| {
+            merger.merge(batch)
+        });
+
+        merger
+            .into_items()
+            .into_iter()
+            // C strings can't have nul chars inside.
+            .filter_map(|(line, score, positions)| {
+                Some((CString::new(line).ok()?, score, positions))
+            })
+            .collect()
+    });
+
+    Box::into_raw(Box::new(FulfSearch {
+        cancel,
+        thread: Some(thread),
+        results: Vec::new(),
+    }))
+}
+
+/// Asks the search to stop; the results found so far are kept.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_cancel(search: *const FulfSearch) {
+    if let Some(search) = search.as_ref() {
+        search.cancel.cancel()
+    }
+}
+
+/// Waits for the search to finish, returns the number of results.
+///
+/// Could be called many times, the results are not changed after the first call.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_wait(search: *mut FulfSearch) -> usize {
+    let search = match search.as_mut() {
+        Some(search) => search,
+        None => return 0,
+    };
+
+    if let Some(thread) = search.thread.take() {
+        // A panicked search has no results.
+        search.results = thread.join().unwrap_or_default();
+    }
+    search.results.len()
+}
+
+/// The formatted line of the result, `path:row:col:line`.
+///
+/// Returns null if the index is out of bounds.
+/// The string lives as long as the search.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_result_line(search: *const FulfSearch, idx: usize) -> *const c_char {
+    result(search, idx).map_or(ptr::null(), |(line, _, _)| line.as_ptr())
+}
+
+/// The score of the result, or 0 if the index is out of bounds.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_result_score(search: *const FulfSearch, idx: usize) -> i32 {
+    result(search, idx).map_or(0, |(_, score, _)| *score)
+}
+
+/// Positions of the matched chars of the line, in chars.
+///
+/// Writes the number of positions into the `len`,
+/// returns null if the index is out of bounds.
+/// The array lives as long as the search.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+/// `len` should be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_result_positions(
+    search: *const FulfSearch,
+    idx: usize,
+    len: *mut usize,
+) -> *const usize {
+    let positions = result(search, idx).map_or(&[][..], |(_, _, positions)| positions);
+    if let Some(len) = len.as_mut() {
+        *len = positions.len();
+    }
+    if positions.is_empty() {
+        ptr::null()
+    } else {
+        positions.as_ptr()
+    }
+}
+
+/// Cancels the search, waits for it and frees it.
+///
+/// # Safety
+///
+/// `search` should be null or returned by the `fulf_search` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn fulf_free(search: *mut FulfSearch) {
+    if !search.is_null() {
+        let mut search = Box::from_raw(search);
+        search.cancel.cancel();
+        if let Some(thread) = search.thread.take() {
+            let _any_result = thread.join();
+        }
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+unsafe fn result<'a>(search: *const FulfSearch, idx: usize) -> Option<&'a FfiResult> {
+    search.as_ref()?.results.get(idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_this_crate() {
+        let root = CString::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+        let needle = CString::new("fulf_search").unwrap();
+
+        unsafe {
+            let search = fulf_search(root.as_ptr(), needle.as_ptr(), 10);
+            assert!(!search.is_null());
+
+            let len = fulf_wait(search);
+            assert_ne!(len, 0);
+            assert!(!fulf_result_line(search, 0).is_null());
+            assert!(fulf_result_line(search, len).is_null());
+
+            let mut positions_len = 0;
+            assert!(!fulf_result_positions(search, 0, &mut positions_len).is_null());
+            assert_eq!(positions_len, "fulf_search".len());
+
+            fulf_free(search);
+        }
+    }
+}
@@ -0,0 +1,511 @@
+//! `fulf NEEDLE [DIR]`: fuzzy-searches the lines of all the files in the directory.
+//!
+//! Mostly for benchmarking, but could be used right from the shell too.
+
+use {
+    fulf::{
+        config::{Algo, Config},
+        filepath_cache::{serialize_relative, IndexedCache, NotUtf8},
+        fzy_algo::{ascii, explain::explain, utf8},
+        matcher,
+        merger::ByScore,
+        DefaultFormatter, FileOrder, GrepFormatter, HandleResults, ItemFormatter, LineWriter,
+        PositionUnit, QuickfixFormatter, ReplaceFormatter, Rules, RulesBuilder, SearchMode,
+        SearchStats, SpecializedAscii,
+    },
+    std::{
+        io,
+        path::{Path, PathBuf},
+        process,
+        sync::Arc,
+        time::Duration,
+    },
+    structopt::StructOpt,
+};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "fulf", about = "Fuzzy line searcher.")]
+struct Opt {
+    /// The string to fuzzy-search.
+    #[structopt(index = 1, required_unless = "rpc")]
+    needle: Option<String>,
+
+    /// The directory to search in, respecting ignore files.
+    #[structopt(index = 2, default_value = ".", parse(from_os_str))]
+    dir: PathBuf,
+
+    /// Number of the searching threads, the main one included.
+    #[structopt(short = "j", long)]
+    threads: Option<usize>,
+
+    /// Search only the files of the git index, instead of the walk;
+    /// the walk options are ignored then.
+    #[structopt(long)]
+    git_tracked: bool,
+
+    /// Search only the files, added or modified since the `HEAD`,
+    /// and the untracked ones; the walk options are ignored then.
+    #[structopt(long)]
+    git_changed: bool,
+
+    /// Search only the files, that match this glob, like `src/**/*.rs`.
+    #[structopt(short = "g", long = "include", number_of_values = 1)]
+    include_globs: Vec<String>,
+
+    /// Skip the files and folders, that match this glob, like `**/testdata/**`.
+    #[structopt(short = "x", long = "exclude", number_of_values = 1)]
+    exclude_globs: Vec<String>,
+
+    /// Descend at most this many folders, 1 means only the files right in the `DIR`.
+    #[structopt(long)]
+    max_depth: Option<usize>,
+
+    /// Follow the symlinks.
+    #[structopt(short = "L", long = "follow")]
+    follow_links: bool,
+
+    /// Don't cross the file system boundaries.
+    #[structopt(long = "one-file-system")]
+    same_file_system: bool,
+
+    /// Search the hidden files and folders too.
+    #[structopt(long)]
+    hidden: bool,
+
+    /// Don't respect the ignore files.
+    #[structopt(long)]
+    no_ignore: bool,
+
+    /// Don't respect the `.gitignore` and other git ignore files.
+    #[structopt(long)]
+    no_ignore_vcs: bool,
+
+    /// Don't respect the ignore files of the folders above the `DIR`.
+    #[structopt(long)]
+    no_ignore_parent: bool,
+
+    /// Respect the ignore files with this name too, like `.clapignore`.
+    #[structopt(long = "ignore-file-name", number_of_values = 1)]
+    custom_ignore_filenames: Vec<String>,
+
+    /// The order of the searched files: `modified` searches
+    /// the most recently modified files first, `size` the smallest ones first.
+    #[structopt(long, possible_values = &["walk", "modified", "size"])]
+    order: Option<String>,
+
+    /// Print the absolute paths of the files, instead of the relative to the `DIR` ones.
+    #[structopt(long)]
+    absolute_path: bool,
+
+    /// Print the paths with the `/` separators on Windows.
+    #[structopt(long)]
+    forward_slashes: bool,
+
+    /// The positions of the `json` output are the byte offsets
+    /// of the matched chars, instead of the char indices.
+    #[structopt(long)]
+    byte_positions: bool,
+
+    /// Search the file, reached through the hardlinks or symlinks, only once.
+    #[structopt(long)]
+    dedupe_links: bool,
+
+    /// Print only this many best results, sorted by the score.
+    ///
+    /// Without the cap, all the results are printed as soon as they are found.
+    #[structopt(short, long)]
+    cap: Option<usize>,
+
+    /// Lines longer than this number of bytes are not searched, 1024 by default.
+    #[structopt(long)]
+    max_line_len: Option<usize>,
+
+    /// Search the start of the longer lines, cut to the `--max-line-len`.
+    #[structopt(long)]
+    truncate_long_lines: bool,
+
+    /// Search the longer lines by the windows of the `--max-line-len`.
+    #[structopt(long)]
+    chunk_long_lines: bool,
+
+    /// Search the files, that look minified or generated, too.
+    #[structopt(long)]
+    search_minified: bool,
+
+    /// The algorithm: `fzy` uses the faster ASCII version for ASCII lines,
+    /// `utf8` uses the UTF-8 version for all lines,
+    /// `substring` searches the whole needle.
+    ///
+    /// `fzy` if neither this option nor the config says otherwise.
+    #[structopt(short, long, possible_values = &["fzy", "utf8", "substring"])]
+    algo: Option<String>,
+
+    /// Output format of the results.
+    ///
+    /// `clap` speaks vim-clap's dynamic provider protocol,
+    /// sending the best `cap` results (100 by default).
+    #[structopt(
+        short,
+        long,
+        default_value = "default",
+        possible_values = &["default", "grep", "quickfix", "json", "clap"]
+    )]
+    format: String,
+
+    /// Preview the replacement of the matched text with this template,
+    /// `$0` is the matched text: prints the Vim dictionaries
+    /// with the `text` and the `replaced` lines, ignoring the `--format`.
+    #[structopt(long, value_name = "template")]
+    replace: Option<String>,
+
+    /// Print only the `path:count` of every file with the matched lines,
+    /// ignoring the `--format`.
+    #[structopt(long)]
+    count: bool,
+
+    /// Print only the paths of the files with the matched lines,
+    /// ignoring the `--format`.
+    #[structopt(short = "l", long)]
+    files_with_matches: bool,
+
+    /// Trim the whitespaces around the needle.
+    #[structopt(long)]
+    trim_needle: bool,
+
+    /// Turn every whitespace run in the needle into one space.
+    #[structopt(long)]
+    collapse_needle_spaces: bool,
+
+    /// Ignore the whitespaces of the lines and of the needle.
+    #[structopt(long)]
+    ignore_whitespace: bool,
+
+    /// Print the lines, that do NOT match the needle.
+    #[structopt(short = "v", long)]
+    invert_match: bool,
+
+    /// Separate the results with `\0` instead of `\n`.
+    #[structopt(short = "0", long)]
+    null: bool,
+
+    /// Stop the search after this number of milliseconds.
+    #[structopt(long)]
+    deadline: Option<u64>,
+
+    /// Print the statistics of the search to the stderr.
+    #[structopt(long)]
+    stats: bool,
+
+    /// Explain the score of this line for the needle, instead of the search.
+    #[structopt(long, value_name = "line")]
+    explain: Option<String>,
+
+    /// Serve the JSON requests from the stdin, see the `fulf::server` docs.
+    #[structopt(long)]
+    rpc: bool,
+
+    /// The config file, `~/.config/fulf/config.toml` by default.
+    ///
+    /// The options override the config.
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+}
+
+type Prealloc = (Vec<i32>, Vec<i32>);
+
+fn main() {
+    let opt = Opt::from_args();
+
+    let config = match &opt.config {
+        Some(path) => Config::load(path),
+        None => Config::load_default(),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("fulf: {}", e);
+            process::exit(2);
+        }
+    };
+
+    if opt.rpc {
+        serve(config.rules);
+    }
+    if let (Some(line), Some(needle)) = (&opt.explain, &opt.needle) {
+        match explain(needle, line) {
+            Some(explanation) => print!("{}", explanation),
+            None => println!("no match"),
+        }
+        return;
+    }
+
+    match run(&opt, config) {
+        Ok(stats) if opt.stats => eprintln!("{:#?}", stats),
+        Ok(_stats) => (),
+        Err(e) => {
+            eprintln!("fulf: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn serve(rules: Rules) -> ! {
+    let stdin = io::stdin();
+    match fulf::server::run_forever_with(stdin.lock(), rules) {
+        Ok(()) => process::exit(0),
+        Err(e) => {
+            eprintln!("fulf: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn serve(_rules: Rules) -> ! {
+    eprintln!("fulf: the server needs the `json` feature");
+    process::exit(2);
+}
+
+fn run(opt: &Opt, config: Config) -> Result<SearchStats, String> {
+    let mut builder = RulesBuilder::from(config.rules);
+    if let Some(threads) = opt.threads {
+        builder = builder.threads(threads);
+    }
+    if let Some(cap) = opt.cap {
+        builder = builder.results_cap(cap);
+    }
+    if let Some(max_line_len) = opt.max_line_len {
+        builder = builder.max_line_len(max_line_len);
+    }
+    if opt.truncate_long_lines {
+        builder = builder.truncate_long_lines(true);
+    }
+    if opt.chunk_long_lines {
+        builder = builder.chunk_long_lines(true);
+    }
+    if opt.search_minified {
+        builder = builder.skip_minified(false);
+    }
+    if let Some(deadline) = opt.deadline {
+        builder = builder.deadline(Duration::from_millis(deadline));
+    }
+    if let Some(depth) = opt.max_depth {
+        builder = builder.max_depth(depth);
+    }
+    if opt.follow_links {
+        builder = builder.follow_links(true);
+    }
+    if opt.same_file_system {
+        builder = builder.same_file_system(true);
+    }
+    if opt.hidden {
+        builder = builder.search_hidden(true);
+    }
+    if opt.no_ignore {
+        builder = builder.no_ignore();
+    }
+    if opt.no_ignore_vcs {
+        builder = builder.git_ignore(false);
+    }
+    if opt.no_ignore_parent {
+        builder = builder.parent_ignores(false);
+    }
+    match opt.order.as_deref() {
+        Some("modified") => builder = builder.file_order(FileOrder::Modified),
+        Some("size") => builder = builder.file_order(FileOrder::Size),
+        Some(_) => builder = builder.file_order(FileOrder::Walk),
+        None => (),
+    }
+    if opt.absolute_path {
+        builder = builder.absolute_paths(true);
+    }
+    if opt.forward_slashes {
+        builder = builder.forward_slashes(true);
+    }
+    if opt.byte_positions {
+        builder = builder.position_unit(PositionUnit::Bytes);
+    }
+    if opt.dedupe_links {
+        builder = builder.dedupe_links(true);
+    }
+    if opt.count {
+        builder = builder.search_mode(SearchMode::Count);
+    } else if opt.files_with_matches {
+        builder = builder.search_mode(SearchMode::Files);
+    }
+    if opt.trim_needle {
+        builder = builder.trim_needle(true);
+    }
+    if opt.collapse_needle_spaces {
+        builder = builder.collapse_needle_spaces(true);
+    }
+    if opt.ignore_whitespace {
+        builder = builder.ignore_whitespace(true);
+    }
+    if opt.invert_match {
+        builder = builder.invert_match(true);
+    }
+    for name in &opt.custom_ignore_filenames {
+        builder = builder.custom_ignore_filename(name.as_str());
+    }
+    for glob in &opt.include_globs {
+        builder = builder.include_glob(glob.as_str());
+    }
+    for glob in &opt.exclude_globs {
+        builder = builder.exclude_glob(glob.as_str());
+    }
+    let r = builder
+        .build()
+        .map_err(|e| format!("invalid option: {}", e))?;
+
+    let needle = opt.needle.as_deref().unwrap_or_default();
+    r.check_needle(needle).map_err(|e| e.to_string())?;
+
+    let cache = if opt.git_tracked || opt.git_changed {
+        git_files(&opt.dir, opt.git_changed)?
+    } else {
+        walk(&opt.dir, &r)?
+    };
+
+    let utf8_algo = |line: &str, needle: &str, prealloc: &mut Prealloc| {
+        utf8::match_and_score_with_positions(needle, line, prealloc)
+    };
+    let ascii_algo = |line: &str, needle: &str, prealloc: &mut Prealloc| {
+        ascii::match_and_score_with_positions(needle.as_bytes(), line.as_bytes(), prealloc)
+    };
+
+    let is_ascii = needle.is_ascii();
+    let (root_folder, needle) = (opt.dir.as_path().into(), needle.into());
+    let cache = Arc::new(cache);
+    let algo = match opt.algo.as_deref() {
+        Some("utf8") => Algo::Utf8,
+        Some("substring") => Algo::Substring,
+        Some(_) => Algo::Fzy,
+        None => config.algo,
+    };
+    if algo == Algo::Substring {
+        let searcher = matcher::searcher(root_folder, needle, Arc::new(matcher::Substring));
+        with_format(searcher, cache, r, opt)
+    } else if algo == Algo::Fzy && is_ascii {
+        let spec = SpecializedAscii::new(root_folder, needle, ascii_algo, utf8_algo);
+        with_format(spec, cache, r, opt)
+    } else {
+        let unspec = SpecializedAscii::new(root_folder, needle, utf8_algo, utf8_algo);
+        with_format(unspec, cache, r, opt)
+    }
+}
+
+fn walk(dir: &Path, r: &Rules) -> Result<IndexedCache, String> {
+    let builder = r
+        .walk_builder(dir)
+        .map_err(|e| format!("invalid glob: {}", e))?;
+    serialize_relative(dir, builder, r.cache_options(), NotUtf8::IgnorePath, |e| {
+        eprintln!("fulf: {}", e)
+    })
+    .map_err(|e| format!("can't walk the directory: {:?}", e))
+}
+
+#[cfg(feature = "git")]
+fn git_files(dir: &Path, changed_only: bool) -> Result<IndexedCache, String> {
+    let files = if changed_only {
+        fulf::git::changed_files(dir)
+    } else {
+        fulf::git::tracked_files(dir)
+    };
+    files.map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "git"))]
+fn git_files(_dir: &Path, _changed_only: bool) -> Result<IndexedCache, String> {
+    Err("`--git-tracked` and `--git-changed` need the `git` feature".into())
+}
+
+fn with_format<A, U>(
+    spec: SpecializedAscii<A, U>,
+    cache: Arc<IndexedCache>,
+    r: Rules,
+    opt: &Opt,
+) -> Result<SearchStats, String>
+where
+    A: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+    U: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+{
+    if let Some(template) = &opt.replace {
+        return search(
+            spec.with_formatter(ReplaceFormatter::new(template.as_str())),
+            cache,
+            r,
+            opt,
+        );
+    }
+    match opt.format.as_str() {
+        "grep" => search(spec.with_formatter(GrepFormatter), cache, r, opt),
+        "quickfix" => search(spec.with_formatter(QuickfixFormatter), cache, r, opt),
+        #[cfg(feature = "json")]
+        "json" => search(spec.with_formatter(fulf::JsonFormatter), cache, r, opt),
+        #[cfg(feature = "json")]
+        "clap" => provide(spec, cache, r, opt),
+        #[cfg(not(feature = "json"))]
+        "json" | "clap" => Err("JSON output needs the `json` feature".into()),
+        _ => search(spec.with_formatter(DefaultFormatter), cache, r, opt),
+    }
+}
+
+fn search<A, U, F>(
+    spec: SpecializedAscii<A, U, F>,
+    cache: Arc<IndexedCache>,
+    r: Rules,
+    opt: &Opt,
+) -> Result<SearchStats, String>
+where
+    A: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+    U: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+    F: ItemFormatter + Clone + Send + 'static,
+{
+    let stdout = io::stdout();
+    let mut writer = LineWriter::new(stdout.lock());
+    if opt.null {
+        writer = writer.nul_delimited();
+    }
+
+    let stats = if opt.cap.is_some() {
+        let (results, stats) = spec
+            .sorted_spawner(cache, r, ByScore, |_delta| ())
+            .map_err(|e| e.to_string())?;
+        writer.handle_results(results);
+        stats
+    } else {
+        spec.spawner(cache, r, |batch| writer.handle_results(batch))
+            .map_err(|e| e.to_string())?
+    };
+
+    let _stdout = writer
+        .finish()
+        .map_err(|e| format!("can't write the results: {}", e))?;
+    Ok(stats)
+}
+
+#[cfg(feature = "json")]
+fn provide<A, U>(
+    spec: SpecializedAscii<A, U>,
+    cache: Arc<IndexedCache>,
+    r: Rules,
+    opt: &Opt,
+) -> Result<SearchStats, String>
+where
+    A: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+    U: Fn(&str, &str, &mut Prealloc) -> Option<(i32, Vec<usize>)> + Clone + Send + 'static,
+{
+    let stdout = io::stdout();
+    let mut provider = fulf::provider::DynProvider::new(stdout.lock(), opt.cap.unwrap_or(100));
+
+    let stats = spec
+        .spawner(cache, r, |batch| provider.handle_results(batch))
+        .map_err(|e| e.to_string())?;
+
+    let _stdout = provider
+        .finish()
+        .map_err(|e| format!("can't write the results: {}", e))?;
+    Ok(stats)
+}
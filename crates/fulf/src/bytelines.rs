@@ -1,6 +1,7 @@
 //! A custom implementation of `lines()` method.
 
 use {
+    crate::fzy_algo::ascii::ascii_from_bytes,
     memchr::{memchr, memrchr},
     std::{
         iter::{DoubleEndedIterator, FusedIterator, Iterator},
@@ -14,6 +15,10 @@ pub enum Line<'a> {
     Ascii(&'a str),
     Utf8(&'a str),
     NotUtf8Line,
+    /// The line is longer than the [`max_len`], its encoding is not checked.
+    ///
+    /// [`max_len`]: struct.ByteLines.html#method.max_len
+    TooLong,
 }
 
 /// Parses raw untrusted bytes into the strings.
@@ -32,23 +37,180 @@ pub enum Line<'a> {
 /// assert_eq!(lines.next(), None);
 /// ```
 //
-//x XXX: poor Windows guys will be left with a '\r' char at the end of a string.
+//x XXX: poor Windows guys will be left with a '\r' char at the end of a string,
+//x unless they ask for the `crlf`.
 //x Nowadays it's a lone `\n` even on Windows (everywhere except Notepad),
 //x so yeah, nobody cares.
 #[derive(Clone)]
 pub struct ByteLines<'a> {
     text: &'a [u8],
+    max_len: usize,
+    delimiter: u8,
+    crlf: bool,
+    truncate: bool,
 }
 impl<'a> ByteLines<'a> {
     #[inline]
     pub fn new(text: &'a [u8]) -> Self {
-        Self { text }
+        Self {
+            text,
+            max_len: usize::MAX,
+            delimiter: NL,
+            crlf: false,
+            truncate: false,
+        }
+    }
+
+    /// Lines longer than `max_len` bytes are returned as the [`TooLong`].
+    ///
+    /// ```
+    /// use fulf::bytelines::{ByteLines, Line::*};
+    ///
+    /// let mut lines = ByteLines::new(b"short\nway too long").max_len(5);
+    /// assert_eq!(lines.next(), Some(Ascii("short")));
+    /// assert_eq!(lines.next(), Some(TooLong));
+    /// ```
+    ///
+    /// [`TooLong`]: enum.Line.html#variant.TooLong
+    #[inline]
+    pub fn max_len(self, max_len: usize) -> Self {
+        Self { max_len, ..self }
+    }
+
+    /// Splits the text on the `delimiter` instead of the newline,
+    /// e.g. the NUL of the `git ls-files -z` or the `find -print0` output.
+    ///
+    /// ```
+    /// use fulf::bytelines::{ByteLines, Line::*};
+    ///
+    /// let mut records = ByteLines::new(b"a b.rs\0new\nline.rs\0").delimiter(b'\0');
+    /// assert_eq!(records.next(), Some(Ascii("a b.rs")));
+    /// assert_eq!(records.next(), Some(Ascii("new\nline.rs")));
+    /// assert_eq!(records.next(), None);
+    /// ```
+    #[inline]
+    pub fn delimiter(self, delimiter: u8) -> Self {
+        Self { delimiter, ..self }
+    }
+
+    /// Trims the `\r` at the end of every line, so the `\r\n` is the line end,
+    /// like in the `str::lines`. The lone `\r` in the middle of the line
+    /// is kept, only the delimiter splits the lines.
+    ///
+    /// ```
+    /// use fulf::bytelines::{ByteLines, Line::*};
+    ///
+    /// let mut lines = ByteLines::new(b"one\r\ntw\ro\r\n").crlf(true);
+    /// assert_eq!(lines.next(), Some(Ascii("one")));
+    /// assert_eq!(lines.next(), Some(Ascii("tw\ro")));
+    /// assert_eq!(lines.next(), None);
+    /// ```
+    #[inline]
+    pub fn crlf(self, crlf: bool) -> Self {
+        Self { crlf, ..self }
+    }
+
+    /// Lines longer than the [`max_len`] are cut to it at the char boundary,
+    /// instead of the [`TooLong`]. Only the kept part of the line is checked,
+    /// so the minified files are searched by their visible start.
+    ///
+    /// ```
+    /// use fulf::bytelines::{ByteLines, Line::*};
+    ///
+    /// let mut lines = ByteLines::new("short\nпотом".as_bytes()).max_len(5).truncate(true);
+    /// assert_eq!(lines.next(), Some(Ascii("short")));
+    /// assert_eq!(lines.next(), Some(Utf8("по")));
+    /// ```
+    ///
+    /// [`max_len`]: #method.max_len
+    /// [`TooLong`]: enum.Line.html#variant.TooLong
+    #[inline]
+    pub fn truncate(self, truncate: bool) -> Self {
+        Self { truncate, ..self }
+    }
+
+    /// The raw lines along with their byte offsets, counted from the start
+    /// of the text left in this iterator.
+    ///
+    /// The lines are not checked, so the [`max_len`] doesn't matter here.
+    ///
+    /// ```
+    /// use fulf::bytelines::ByteLines;
+    ///
+    /// let mut lines = ByteLines::new(b"one\ntwo\n\nfour").with_offsets();
+    /// assert_eq!(lines.next(), Some((0, &b"one"[..])));
+    /// assert_eq!(lines.next(), Some((4, &b"two"[..])));
+    /// assert_eq!(lines.next(), Some((8, &b""[..])));
+    /// assert_eq!(lines.next_back(), Some((9, &b"four"[..])));
+    /// assert_eq!(lines.next(), None);
+    /// ```
+    ///
+    /// [`max_len`]: #method.max_len
+    #[inline]
+    pub fn with_offsets(self) -> ByteLinesOffsets<'a> {
+        ByteLinesOffsets {
+            text: self.text,
+            offset: 0,
+            delimiter: self.delimiter,
+            crlf: self.crlf,
+        }
+    }
+}
+
+impl<'a> ByteLines<'a> {
+    #[inline]
+    fn parse(&self, line: &'a [u8]) -> Line<'a> {
+        let mut line = trim_cr(line, self.crlf);
+        if line.len() > self.max_len {
+            if !self.truncate {
+                return Line::TooLong;
+            }
+            line = &line[..char_start(line, self.max_len)];
+        }
+
+        match ascii_from_bytes(line) {
+            Some(line) => Line::Ascii(line),
+            None => str::from_utf8(line).map_or(Line::NotUtf8Line, Line::Utf8),
+        }
+    }
+}
+
+/// The start of the utf8 char at the `idx`, or of the one before it,
+/// if the `idx` is in the middle of the char.
+#[inline]
+fn char_start(line: &[u8], mut idx: usize) -> usize {
+    // The continuation bytes are `0b10xx_xxxx`.
+    while idx > 0 && line[idx] & 0b1100_0000 == 0b1000_0000 {
+        idx -= 1;
     }
+    idx
 }
 
-/// Newline char.
+/// Iterator over the raw lines and their byte offsets,
+/// see the [`ByteLines::with_offsets`].
+///
+/// [`ByteLines::with_offsets`]: struct.ByteLines.html#method.with_offsets
+#[derive(Clone)]
+pub struct ByteLinesOffsets<'a> {
+    text: &'a [u8],
+    // Offset of the `text` start.
+    offset: usize,
+    delimiter: u8,
+    crlf: bool,
+}
+
+/// Newline char, the default delimiter.
 const NL: u8 = b'\n';
 
+/// The line without its `\r` end, if the `crlf` is set.
+#[inline]
+fn trim_cr(line: &[u8], crlf: bool) -> &[u8] {
+    match line.split_last() {
+        Some((b'\r', rest)) if crlf => rest,
+        _ => line,
+    }
+}
+
 impl<'a> Iterator for ByteLines<'a> {
     type Item = Line<'a>;
 
@@ -56,7 +218,7 @@ impl<'a> Iterator for ByteLines<'a> {
     fn size_hint(&self) -> (usize, Option<usize>) {
         // The maximum of items takes every char to be a newline.
         let high = self.text.len();
-        (0, Some(high))
+        (usize::from(high != 0), Some(high))
     }
 
     #[inline]
@@ -67,7 +229,7 @@ impl<'a> Iterator for ByteLines<'a> {
             return None;
         }
 
-        let line = match memchr(NL, text) {
+        let line = match memchr(self.delimiter, text) {
             Some(newline_idx) => {
                 self.text = &text[newline_idx + 1..];
                 &text[..newline_idx]
@@ -80,13 +242,7 @@ impl<'a> Iterator for ByteLines<'a> {
             }
         };
 
-        Some(if line.is_ascii() {
-            // SAFETY: the whole line is checked and is ASCII,
-            // which is always valid utf8.
-            unsafe { Line::Ascii(str::from_utf8_unchecked(line)) }
-        } else {
-            str::from_utf8(line).map_or(Line::NotUtf8Line, Line::Utf8)
-        })
+        Some(self.parse(line))
     }
 }
 
@@ -99,7 +255,7 @@ impl DoubleEndedIterator for ByteLines<'_> {
             return None;
         }
 
-        let line = match memrchr(NL, text) {
+        let line = match memrchr(self.delimiter, text) {
             Some(newline_idx) => {
                 self.text = &text[..newline_idx];
                 &text[newline_idx + 1..]
@@ -112,14 +268,159 @@ impl DoubleEndedIterator for ByteLines<'_> {
             }
         };
 
-        Some(if line.is_ascii() {
-            // SAFETY: the whole line is checked and is ASCII,
-            // which is always valid utf8.
-            unsafe { Line::Ascii(str::from_utf8_unchecked(line)) }
-        } else {
-            str::from_utf8(line).map_or(Line::NotUtf8Line, Line::Utf8)
-        })
+        Some(self.parse(line))
     }
 }
 
 impl FusedIterator for ByteLines<'_> {}
+
+impl<'a> Iterator for ByteLinesOffsets<'a> {
+    type Item = (usize, &'a [u8]);
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let high = self.text.len();
+        (usize::from(high != 0), Some(high))
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.text;
+
+        if text.is_empty() {
+            return None;
+        }
+
+        let offset = self.offset;
+        let line = match memchr(self.delimiter, text) {
+            Some(newline_idx) => {
+                self.text = &text[newline_idx + 1..];
+                self.offset += newline_idx + 1;
+                &text[..newline_idx]
+            }
+
+            None => {
+                self.text = &[];
+                self.offset += text.len();
+                text
+            }
+        };
+
+        Some((offset, trim_cr(line, self.crlf)))
+    }
+}
+
+impl DoubleEndedIterator for ByteLinesOffsets<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let text = self.text;
+
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(match memrchr(self.delimiter, text) {
+            Some(newline_idx) => {
+                self.text = &text[..newline_idx];
+                let line = &text[newline_idx + 1..];
+                (self.offset + newline_idx + 1, trim_cr(line, self.crlf))
+            }
+
+            None => {
+                self.text = &[];
+                (self.offset, trim_cr(text, self.crlf))
+            }
+        })
+    }
+}
+
+impl FusedIterator for ByteLinesOffsets<'_> {}
+
+impl<'a> ByteLinesOffsets<'a> {
+    /// Groups the raw lines by `size`,
+    /// so the per-line work of the scoring loops could be done once per group.
+    ///
+    /// ```
+    /// use fulf::bytelines::ByteLines;
+    ///
+    /// let mut batches = ByteLines::new(b"a\nb\nc").with_offsets().batches(2);
+    /// assert_eq!(batches.next(), Some((0, vec![&b"a"[..], b"b"])));
+    /// assert_eq!(batches.next(), Some((2, vec![&b"c"[..]])));
+    /// assert_eq!(batches.next(), None);
+    ///
+    /// let all = ByteLines::new(b"a\nb").with_offsets().batches(usize::MAX);
+    /// assert_eq!(all.size_hint(), (1, Some(1)));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `size` is 0, just like the `slice::chunks`.
+    #[inline]
+    pub fn batches(self, size: usize) -> LineBatches<'a> {
+        assert!(size != 0, "the size of the line batches is 0");
+        LineBatches {
+            lines: self,
+            size,
+            line_idx: 0,
+        }
+    }
+}
+
+/// Iterator over the groups of the raw lines, along with the index
+/// of the first line of every group, see the [`ByteLinesOffsets::batches`].
+///
+/// [`ByteLinesOffsets::batches`]: struct.ByteLinesOffsets.html#method.batches
+#[derive(Clone)]
+pub struct LineBatches<'a> {
+    lines: ByteLinesOffsets<'a>,
+    size: usize,
+    // Index of the next line.
+    line_idx: usize,
+}
+
+impl<'a> LineBatches<'a> {
+    /// Like the `next`, but reuses the allocation of the `batch`,
+    /// which is cleared first.
+    ///
+    /// Returns the index of the first line of the batch,
+    /// or `None` if there are no lines left.
+    pub fn next_into(&mut self, batch: &mut Vec<&'a [u8]>) -> Option<usize> {
+        batch.clear();
+        batch.extend(
+            self.lines
+                .by_ref()
+                .take(self.size)
+                .map(|(_offset, line)| line),
+        );
+        if batch.is_empty() {
+            return None;
+        }
+
+        let first_idx = self.line_idx;
+        self.line_idx += batch.len();
+        Some(first_idx)
+    }
+}
+
+impl<'a> Iterator for LineBatches<'a> {
+    type Item = (usize, Vec<&'a [u8]>);
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (low, high) = self.lines.size_hint();
+        // Rounded up, without the overflow of the `high + size - 1`.
+        let batches = |lines: usize| lines / self.size + usize::from(lines % self.size != 0);
+        (batches(low), high.map(batches))
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        // Not the whole `size`, that could be huge, for the few lines left.
+        let (low, _high) = self.lines.size_hint();
+        let mut batch = Vec::with_capacity(self.size.min(low.saturating_add(1)));
+        self.next_into(&mut batch)
+            .map(|first_idx| (first_idx, batch))
+    }
+}
+
+impl FusedIterator for LineBatches<'_> {}
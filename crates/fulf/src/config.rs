@@ -0,0 +1,169 @@
+//! The user config, shared by the `fulf` binary and the server.
+//!
+//! The config is a TOML file, or a JSON one with the `json` feature
+//! and the `.json` extension. Every field is optional:
+//!
+//! ```toml
+//! algo = "utf8"
+//!
+//! [rules]
+//! threads = 8
+//! results_cap = 100
+//! deadline = { secs = 2, nanos = 0 }
+//! ```
+//!
+//! See the [`Rules`] for all the fields of the `rules` table.
+//!
+//! [`Rules`]: ../struct.Rules.html
+
+use {
+    crate::{InvalidRules, Rules},
+    serde::Deserialize,
+    std::{
+        env,
+        error::Error,
+        fmt, fs, io,
+        path::{Path, PathBuf},
+    },
+};
+
+/// The scoring algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algo {
+    /// The faster ASCII version for ASCII lines and needles,
+    /// the UTF-8 version for all others.
+    Fzy,
+    /// The UTF-8 version for all lines.
+    Utf8,
+    /// The first occurrence of the whole needle, see the [`matcher::Substring`].
+    ///
+    /// [`matcher::Substring`]: ../matcher/struct.Substring.html
+    Substring,
+}
+
+impl Default for Algo {
+    #[inline]
+    fn default() -> Self {
+        Algo::Fzy
+    }
+}
+
+/// The parsed config file.
+///
+/// Missing fields have their default values.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub algo: Algo,
+    pub rules: Rules,
+}
+
+impl Config {
+    /// Parses the TOML config and checks the rules.
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(s).map_err(ConfigError::Toml)?;
+        config.validate()
+    }
+
+    /// Parses the JSON config and checks the rules.
+    #[cfg(feature = "json")]
+    pub fn from_json(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = serde_json::from_str(s).map_err(ConfigError::Json)?;
+        config.validate()
+    }
+
+    /// Reads the config file: the JSON one if the extension is `json`,
+    /// the TOML one otherwise.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let s = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        match path.extension() {
+            #[cfg(feature = "json")]
+            Some(ext) if ext == "json" => Self::from_json(&s),
+            #[cfg(not(feature = "json"))]
+            Some(ext) if ext == "json" => Err(ConfigError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "JSON config needs the `json` feature",
+            ))),
+            _ => Self::from_toml(&s),
+        }
+    }
+
+    /// Reads the config at the [`default_path`],
+    /// if there's no file, returns the default config.
+    ///
+    /// [`default_path`]: fn.default_path.html
+    pub fn load_default() -> Result<Self, ConfigError> {
+        match default_path() {
+            Some(path) if path.is_file() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    fn validate(self) -> Result<Self, ConfigError> {
+        self.rules.validate().map_err(ConfigError::Invalid)?;
+        Ok(self)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/fulf/config.toml`, falling back to `~/.config/fulf/config.toml`,
+/// or `%APPDATA%\fulf\config.toml` on Windows.
+///
+/// `None` if none of the variables is set.
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("fulf").join("config.toml"))
+}
+
+/// The error of the config loading.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// The config was parsed, but the rules make no sense.
+    Invalid(InvalidRules),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "can't read the config: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid config: {}", e),
+            #[cfg(feature = "json")]
+            ConfigError::Json(e) => write!(f, "invalid config: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid rules in the config: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_are_default() {
+        let config = Config::from_toml("[rules]\nthreads = 3\n").unwrap();
+        assert_eq!(config.algo, Algo::Fzy);
+        assert_eq!(config.rules.threads.get(), 3);
+        assert_eq!(config.rules.results_cap, Rules::new().results_cap);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        match Config::from_toml("algo = \"utf8\"\n[rules]\nresults_cap = 0\n") {
+            Err(ConfigError::Invalid(InvalidRules::ZeroResultsCap)) => (),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Live searching: a new search on every keystroke, but not too often.
+
+use {
+    crate::CancelToken,
+    std::{
+        sync::Arc,
+        thread::{self, JoinHandle},
+        time::Duration,
+    },
+};
+
+/// Coalesces rapid needle updates and runs only the last one.
+///
+/// The search is launched only after the needle stayed the same
+/// for the whole quiet period. If a new needle arrives while
+/// the previous search is still running, that search is cancelled
+/// with its [`CancelToken`] right away, and joined before the next one
+/// is launched, so there's at most one search running at any time.
+///
+/// Dropping the searcher cancels the running search and waits for it.
+///
+/// # Examples
+///
+/// ```
+/// use fulf::debounce::DebouncedSearcher;
+/// use std::time::Duration;
+///
+/// let searcher = DebouncedSearcher::new(Duration::from_millis(50), |needle, cancel| {
+///     let mut rules = fulf::Rules::new();
+///     rules.cancel = cancel;
+///     /* Search the `needle` with those rules. */
+/// #   let _ = (needle, rules);
+/// });
+///
+/// // Only "hello" will be searched.
+/// searcher.update("h");
+/// searcher.update("hel");
+/// searcher.update("hello");
+/// ```
+///
+/// [`CancelToken`]: ../struct.CancelToken.html
+pub struct DebouncedSearcher {
+    // Both are `Option` only to be taken in the `Drop`.
+    sender: Option<flume::Sender<String>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DebouncedSearcher {
+    /// Spawns the thread, that waits for the needle updates.
+    ///
+    /// `search` is called on its own thread with the needle and the token,
+    /// that is cancelled as soon as the search is superseded.
+    pub fn new<S>(quiet_period: Duration, search: S) -> Self
+    where
+        S: Fn(String, CancelToken) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = flume::unbounded::<String>();
+        let search = Arc::new(search);
+
+        let thread = thread::spawn(move || {
+            let mut in_flight: Option<(CancelToken, JoinHandle<()>)> = None;
+
+            // Waits for the first needle of the burst.
+            'burst: while let Ok(mut needle) = receiver.recv() {
+                // The running search is outdated already, it stops while it's quiet.
+                if let Some((cancel, _t)) = &in_flight {
+                    cancel.cancel();
+                }
+
+                // Takes the newest needle until it's quiet.
+                loop {
+                    match receiver.recv_timeout(quiet_period) {
+                        Ok(newer) => needle = newer,
+                        Err(flume::RecvTimeoutError::Timeout) => break,
+                        Err(flume::RecvTimeoutError::Disconnected) => break 'burst,
+                    }
+                }
+
+                stop(in_flight.take());
+
+                let cancel = CancelToken::new();
+                let search = Arc::clone(&search);
+                let token = cancel.clone();
+                let t = thread::spawn(move || search(needle, token));
+                in_flight = Some((cancel, t));
+            }
+
+            stop(in_flight);
+        });
+
+        Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Sets the new needle.
+    ///
+    /// The search with this needle will start after the quiet period,
+    /// unless the needle is updated again.
+    pub fn update(&self, needle: impl Into<String>) {
+        if let Some(sender) = &self.sender {
+            let _any_result = sender.send(needle.into());
+        }
+    }
+}
+
+/// Cancels the search and waits for its thread.
+pub(crate) fn stop(in_flight: Option<(CancelToken, JoinHandle<()>)>) {
+    if let Some((cancel, t)) = in_flight {
+        cancel.cancel();
+        // A panicked search is just a finished search there.
+        let _any_result = t.join();
+    }
+}
+
+impl Drop for DebouncedSearcher {
+    fn drop(&mut self) {
+        // Disconnects the channel, which stops the waiting thread.
+        drop(self.sender.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _any_result = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn only_last_needle_is_searched() {
+        let searched = Arc::new(Mutex::new(Vec::new()));
+
+        let searcher = {
+            let searched = Arc::clone(&searched);
+            DebouncedSearcher::new(Duration::from_millis(50), move |needle, _cancel| {
+                searched.lock().unwrap().push(needle)
+            })
+        };
+
+        searcher.update("a");
+        searcher.update("ab");
+        searcher.update("abc");
+        thread::sleep(Duration::from_millis(200));
+        drop(searcher);
+
+        assert_eq!(*searched.lock().unwrap(), vec![String::from("abc")]);
+    }
+
+    #[test]
+    fn running_search_is_cancelled_by_the_next_needle() {
+        let (started_sx, started_rx) = flume::unbounded();
+        let (cancelled_sx, cancelled_rx) = flume::unbounded();
+
+        let searcher = DebouncedSearcher::new(Duration::from_millis(500), move |needle, cancel| {
+            let _any_result = started_sx.send(());
+            while !cancel.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let _any_result = cancelled_sx.send(needle);
+        });
+
+        searcher.update("a");
+        started_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        searcher.update("ab");
+        // Long before the quiet period of the new needle is over.
+        let cancelled = cancelled_rx.recv_timeout(Duration::from_millis(250));
+        assert_eq!(cancelled.unwrap(), "a");
+    }
+}
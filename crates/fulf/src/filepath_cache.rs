@@ -101,13 +101,16 @@
 //! it will return an error or just be ignored, based on the option choosen.
 
 use {
+    crate::FileOrder,
     ignore,
     inlinable_string::{InlinableString as InString, StringExt},
     std::{
-        cmp::Ordering as CmpOrd,
+        cmp::Reverse,
+        collections::HashSet,
         mem,
-        path::{self, MAIN_SEPARATOR},
+        path::{self, Path, MAIN_SEPARATOR},
         sync::atomic::AtomicUsize,
+        time::{SystemTime, UNIX_EPOCH},
     },
 };
 
@@ -132,8 +135,6 @@ pub enum NotUtf8 {
 /// Errors, that could occur during serialization.
 #[derive(Debug)]
 pub enum SerializeError {
-    /// The inner error of the iterator.
-    Walk(ignore::Error),
     /// Path that cannot be encoded as UTF-8 was met.
     /// Pretty much self-descriptive.
     NonUtf8Path,
@@ -144,43 +145,137 @@ pub enum SerializeError {
     StdinEntry,
 }
 
-impl From<ignore::Error> for SerializeError {
-    fn from(e: ignore::Error) -> Self {
-        Self::Walk(e)
-    }
-}
-
 /// If `non_utf8_path` is `ReturnError`,
 /// then `ignore::Error::IO(InvalidData)` error will be returned.
 ///
 /// If it is `IgnorePath`,
 /// then those paths are ignored, and the serialization continues.
 ///
-/// If the `ignore::Walk` iterator meets any error, that error is returned.
+/// If the `ignore::Walk` iterator meets any error (unreadable folder,
+/// broken ignore file, symlink loop, etc.), that error is passed to
+/// `on_walk_error` and the serialization continues with the next entry.
+/// Thus a folder full of unreadable files is simply left out of the cache,
+/// instead of ruining the whole search.
 ///
 /// # Symlinks and added paths
 ///
-/// This algorithm does not support both.
+/// The [link jumps] are fine: the paths behind the symlinks still start
+/// with the base folder, and the symlink loops are passed to `on_walk_error`.
 ///
-/// Thus enabling [link jumps] or [adding] other paths to the builder,
-/// will lead to logic bugs, where all files behind the symlink or
+/// [Adding] other paths to the builder is not supported,
+/// and will lead to logic bugs, where all files
 /// in the added path would not be read.
 ///
 /// If you need to read files in more than one base folder,
 /// create a cache for each such folder
 /// and process all needed caches separately, one-by-one.
 ///
-/// [adding]: https://docs.rs/ignore/0.4.15/ignore/struct.WalkBuilder.html#method.add
+/// [Adding]: https://docs.rs/ignore/0.4.15/ignore/struct.WalkBuilder.html#method.add
 /// [link jumps]: https://docs.rs/ignore/0.4.15/ignore/struct.WalkBuilder.html#method.follow_links
 ///
-/// # Overrides
+/// # Order
 ///
-/// This function overrides any preset sort in the `WalkBuilder`
-/// with "files before folders" sort.
+/// The files are cached in the order of the walk, and the files
+/// of one folder are kept in one chunk only while they go one after another.
+/// The walk of the `WalkBuilder::new` is not sorted, so use the
+/// [`Rules::walk_builder`] with the [`sorted_walk`] to get the same cache
+/// on every run.
+///
+/// [`Rules::walk_builder`]: ../struct.Rules.html#method.walk_builder
+/// [`sorted_walk`]: ../struct.Rules.html#structfield.sorted_walk
 pub fn serialize(
     base_folder: impl AsRef<str>,
-    mut builder: ignore::WalkBuilder,
+    builder: ignore::WalkBuilder,
+    not_utf8_path: NotUtf8,
+    on_walk_error: impl FnMut(ignore::Error),
+) -> Result<IndexedCache, SerializeError> {
+    serialize_ordered(
+        base_folder,
+        builder,
+        CacheOptions::default(),
+        not_utf8_path,
+        on_walk_error,
+    )
+}
+
+/// The options of the cache, besides the walk itself.
+///
+/// See the [`Rules::cache_options`].
+///
+/// [`Rules::cache_options`]: ../struct.Rules.html#method.cache_options
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheOptions {
+    /// The order of the files in the cache.
+    ///
+    /// Any order, except the [`FileOrder::Walk`], reads the metadata of every file,
+    /// and puts the files of one folder in one chunk only while they go
+    /// one after another in that order.
+    ///
+    /// [`FileOrder::Walk`]: ../enum.FileOrder.html#variant.Walk
+    pub order: FileOrder,
+    /// Cache the same file, reached through the hardlinks or symlinks,
+    /// only once: the first path of the walk is kept.
+    ///
+    /// Reads the metadata of every file to get its device and inode
+    /// (the volume and the file index on Windows).
+    pub dedupe_links: bool,
+}
+
+/// Just like the [`serialize`], but with the given options,
+/// like the order of the files in the cache, and so in the search.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_ordered(
+    base_folder: impl AsRef<str>,
+    builder: ignore::WalkBuilder,
+    options: CacheOptions,
+    not_utf8_path: NotUtf8,
+    on_walk_error: impl FnMut(ignore::Error),
+) -> Result<IndexedCache, SerializeError> {
+    let base_folder = append_separator(InString::from(base_folder.as_ref()));
+    serialize_from(
+        &base_folder,
+        Path::new(&*base_folder),
+        builder,
+        options,
+        not_utf8_path,
+        on_walk_error,
+    )
+}
+
+/// Just like the [`serialize_ordered`], but the `root` is not saved to the cache,
+/// so it could be any path, even not UTF-8 one; only the paths
+/// inside the `root` have to be UTF-8.
+///
+/// The files of such cache are relative to the `root`,
+/// so the searcher has to be given the same `root` to open them.
+///
+/// [`serialize_ordered`]: fn.serialize_ordered.html
+pub fn serialize_relative(
+    root: impl AsRef<Path>,
+    builder: ignore::WalkBuilder,
+    options: CacheOptions,
     not_utf8_path: NotUtf8,
+    on_walk_error: impl FnMut(ignore::Error),
+) -> Result<IndexedCache, SerializeError> {
+    serialize_from(
+        "",
+        root.as_ref(),
+        builder,
+        options,
+        not_utf8_path,
+        on_walk_error,
+    )
+}
+
+/// Writes the `base_folder` to the cache, and the walked files without the `root`.
+fn serialize_from(
+    base_folder: &str,
+    root: &Path,
+    builder: ignore::WalkBuilder,
+    options: CacheOptions,
+    not_utf8_path: NotUtf8,
+    mut on_walk_error: impl FnMut(ignore::Error),
 ) -> Result<IndexedCache, SerializeError> {
     macro_rules! not_utf8 {
         () => {
@@ -191,61 +286,177 @@ pub fn serialize(
         };
     }
 
-    let base_folder = append_separator(InString::from(base_folder.as_ref()));
-
-    let mut indicies: Vec<usize> = Vec::new();
-    let mut cache: Vec<u8> = Vec::with_capacity(1024);
-    write_base_folder(base_folder.as_ref(), &mut cache);
-
-    let mut current_folder = FolderWithfFiles::new(InString::from(""));
-
-    let iter = builder
-        .sort_by_file_path(|a_path, b_path| {
-            match (a_path.is_file(), b_path.is_file()) {
-                (true, true) | (false, false) => a_path.cmp(b_path),
-                // Files in the current directory should always go before
-                // anything else.
-                (true, false) => CmpOrd::Less,
-                (false, true) => CmpOrd::Greater,
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("walk", root = %root.display()).entered();
+
+    let mut chunks = Chunks::new(base_folder);
+    // Not used with the walk order: files go right into the chunks.
+    let mut ordered_files: Vec<(SortKey, InString)> = Vec::new();
+    // The files, that are already in the cache.
+    let mut file_ids: HashSet<FileId> = HashSet::new();
+
+    for dir_ent in builder.build() {
+        let dir_ent = match dir_ent {
+            Ok(dir_ent) => dir_ent,
+            Err(e) => {
+                on_walk_error(e);
+                continue;
             }
-        })
-        .build();
-
-    for dir_ent in iter {
-        let dir_ent = dir_ent?;
+        };
 
         match dir_ent.file_type() {
-            Some(filetype) => {
-                if filetype.is_dir() {
-                    current_folder.write_chunk_to(&mut cache, &mut indicies);
-
-                    match dir_ent.path().as_os_str().to_str() {
-                        Some(s) => match s.get(base_folder.len()..) {
-                            Some(s) => {
-                                current_folder =
-                                    FolderWithfFiles::new(append_separator(InString::from(s)))
-                            }
-                            // I told them to not use symlinks and additional paths.
-                            // Too bad they didn't read the docs of the function.
-                            None => continue,
-                        },
+            Some(filetype) if filetype.is_file() => {
+                let path = match dir_ent.path().strip_prefix(root) {
+                    Ok(path) => path,
+                    // I told them to not use additional paths.
+                    // Too bad they didn't read the docs of the function.
+                    Err(_) => continue,
+                };
+                let path = match path.to_str() {
+                    Some(path) => path,
+                    None => not_utf8!(),
+                };
 
-                        None => not_utf8!(),
-                    };
-                } else if filetype.is_file() {
-                    match dir_ent.file_name().to_str() {
-                        Some(s) => current_folder.push(s),
-                        None => not_utf8!(),
-                    };
+                if options.dedupe_links {
+                    match file_id(dir_ent.path()) {
+                        Some(id) if !file_ids.insert(id) => continue,
+                        // The file without the id is still searched.
+                        _ => (),
+                    }
+                }
+
+                match options.order {
+                    FileOrder::Walk => chunks.push(path),
+                    FileOrder::Modified => {
+                        // Unknown time puts the file to the end.
+                        let modified = dir_ent
+                            .metadata()
+                            .ok()
+                            .and_then(|metadata| metadata.modified().ok())
+                            .unwrap_or(UNIX_EPOCH);
+                        ordered_files
+                            .push((SortKey::Modified(Reverse(modified)), InString::from(path)));
+                    }
+                    FileOrder::Size => {
+                        // Unknown size puts the file to the end.
+                        let len = dir_ent
+                            .metadata()
+                            .map_or(u64::MAX, |metadata| metadata.len());
+                        ordered_files.push((SortKey::Size(len), InString::from(path)));
+                    }
                 }
             }
+            Some(_folder_or_symlink) => (),
             None => return Err(SerializeError::StdinEntry),
         }
     }
 
-    current_folder.write_chunk_to(&mut cache, &mut indicies);
+    // The sort is stable, so the walk order is kept for the same keys.
+    ordered_files.sort_by_key(|(key, _path)| *key);
+    ordered_files
+        .iter()
+        .for_each(|(_key, path)| chunks.push(path));
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(files = chunks.files_count, "walk finished");
+    Ok(chunks.finish())
+}
+
+/// Makes the relative cache of the given paths, without the walk:
+/// the paths are relative to the root, like the ones of the [`serialize_relative`].
+///
+/// The files of one folder are kept in one chunk only while they go
+/// one after another, so the sorted paths make the smaller cache.
+///
+/// [`serialize_relative`]: fn.serialize_relative.html
+pub fn serialize_paths<'p>(paths: impl IntoIterator<Item = &'p str>) -> IndexedCache {
+    let mut chunks = Chunks::new("");
+    paths.into_iter().for_each(|path| chunks.push(path));
+    chunks.finish()
+}
+
+/// The key of the [`FileOrder`], all files of one cache have the same variant.
+///
+/// [`FileOrder`]: ../enum.FileOrder.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    /// Newest first.
+    Modified(Reverse<SystemTime>),
+    /// Smallest first.
+    Size(u64),
+}
+
+/// The device and the inode of the file, or the volume and the file index on Windows.
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use winapi_util::{file, Handle};
+
+    let handle = Handle::from_path_any(path).ok()?;
+    let info = file::information(&handle).ok()?;
+    Some((info.volume_serial_number(), info.file_index()))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_id(_path: &Path) -> Option<FileId> {
+    None
+}
+
+/// The cache in the making.
+struct Chunks {
+    cache: Vec<u8>,
+    indicies: Vec<usize>,
+    files_count: usize,
+    current_folder: FolderWithfFiles,
+}
+
+impl Chunks {
+    fn new(base_folder: &str) -> Self {
+        let mut cache: Vec<u8> = Vec::with_capacity(1024);
+        write_base_folder(base_folder, &mut cache);
+
+        Self {
+            cache,
+            indicies: Vec::new(),
+            files_count: 0,
+            current_folder: FolderWithfFiles::new(InString::from("")),
+        }
+    }
+
+    /// Adds the file by its path without the base folder.
+    fn push(&mut self, path: &str) {
+        let (folder, file) = match path.rfind(path::is_separator) {
+            Some(idx) => path.split_at(idx + 1),
+            None => ("", path),
+        };
+
+        // Without the sort, the files of one folder could be split
+        // by its subfolders, and such files go to the new chunk.
+        if &*self.current_folder.foldername != folder {
+            self.current_folder
+                .write_chunk_to(&mut self.cache, &mut self.indicies);
+            self.current_folder = FolderWithfFiles::new(InString::from(folder));
+        }
+        self.current_folder.push(file);
+        self.files_count += 1;
+    }
+
+    fn finish(mut self) -> IndexedCache {
+        self.current_folder
+            .write_chunk_to(&mut self.cache, &mut self.indicies);
 
-    Ok(IndexedCache::new(cache, indicies))
+        IndexedCache::new(self.cache, self.indicies, self.files_count)
+    }
 }
 
 /// The base folder is unique: it's the only folder that has no files in it;
@@ -265,9 +476,12 @@ fn write_base_folder(base_folder: &str, cache: &mut Vec<u8>) {
 /// If the string doesn't end with a separator,
 /// the [`MAIN_SEPARATOR`] is pushed to the end of string.
 ///
+/// The empty string is the base folder of the relative cache,
+/// and is left empty.
+///
 /// [`MAIN_SEPARATOR`]: https://doc.rust-lang.org/std/path/constant.MAIN_SEPARATOR.html
 fn append_separator(mut folder_name: InString) -> InString {
-    if folder_name.ends_with(path::is_separator) {
+    if folder_name.is_empty() || folder_name.ends_with(path::is_separator) {
         folder_name
     } else {
         folder_name.push(MAIN_SEPARATOR);
@@ -351,6 +565,7 @@ pub fn deserialize(bytes: Vec<u8>) -> Result<IndexedCache, InvalidCache<Vec<u8>>
     let mut cacheslice: &[u8] = &bytes;
 
     let mut indicies: Vec<usize> = Vec::with_capacity(8);
+    let mut files_count: usize = 0;
     let mut index_of_first_byte_of_folder_length = 1 + USIZE_SIZE;
 
     loop {
@@ -362,6 +577,11 @@ pub fn deserialize(bytes: Vec<u8>) -> Result<IndexedCache, InvalidCache<Vec<u8>>
                 Ok(chunk_len) if chunk_len < 1 + USIZE_SIZE + 1 => invalid!(),
                 Ok(u) => match cacheslice.get(u..) {
                     Some(slice) => {
+                        match count_files(&bytes[index_of_first_byte_of_folder_length..]) {
+                            Ok(count) => files_count += count,
+                            Err(_) => invalid!(),
+                        }
+
                         cacheslice = slice;
                         indicies.push(index_of_first_byte_of_folder_length);
                         index_of_first_byte_of_folder_length += u;
@@ -375,7 +595,26 @@ pub fn deserialize(bytes: Vec<u8>) -> Result<IndexedCache, InvalidCache<Vec<u8>>
         }
     }
 
-    Ok(IndexedCache::new(bytes, indicies))
+    Ok(IndexedCache::new(bytes, indicies, files_count))
+}
+
+/// Counts the files in the chunk, that starts with the folder's length.
+///
+/// Returns error, if the chunk doesn't end with the zero byte.
+fn count_files(chunk: &[u8]) -> Result<usize, InvalidCache<()>> {
+    let (folder_len, chunk) = ByteOrUsize::decode(chunk)?;
+    let mut chunk = chunk.get(folder_len.as_usize()..).ok_or(InvalidCache(()))?;
+
+    let mut count = 0;
+    loop {
+        match ByteOrUsize::decode(chunk)? {
+            (ZeroByte, _) => return Ok(count),
+            (len, rest) => {
+                chunk = rest.get(len.as_usize()..).ok_or(InvalidCache(()))?;
+                count += 1;
+            }
+        }
+    }
 }
 
 /// The error, indicating the invalidness of the cache.
@@ -387,18 +626,41 @@ pub struct InvalidCache<T>(pub T);
 pub struct IndexedCache {
     jumper: AtomicUsize,
     indicies: Vec<usize>,
+    files_count: usize,
     cache: Vec<u8>,
 }
 
 impl IndexedCache {
-    fn new(cache: Vec<u8>, indicies: Vec<usize>) -> Self {
+    fn new(cache: Vec<u8>, indicies: Vec<usize>, files_count: usize) -> Self {
         Self {
             jumper: AtomicUsize::new(0),
             indicies,
+            files_count,
             cache,
         }
     }
 
+    /// The number of files in the cache.
+    pub fn files_count(&self) -> usize {
+        self.files_count
+    }
+
+    /// The bytes of the paths and of their index.
+    pub fn heap_size(&self) -> usize {
+        self.cache.len() + self.indicies.len() * mem::size_of::<usize>()
+    }
+
+    /// The cache was made by the [`serialize_relative`],
+    /// and its paths are relative to the root, given to that function.
+    ///
+    /// [`serialize_relative`]: fn.serialize_relative.html
+    pub fn is_relative(&self) -> bool {
+        match self.cache.get(1 + USIZE_SIZE..).map(ByteOrUsize::decode) {
+            Some(Ok((base_folder_len, _))) => base_folder_len.as_usize() == 0,
+            _ => false,
+        }
+    }
+
     /// Shows the whole cache.
     ///
     /// Use it to write the cache into the file.
@@ -413,6 +675,15 @@ impl IndexedCache {
     }
 }
 
+/// The files of the cache are read only once, by all the workers of one search,
+/// so every next search of the same files needs its own clone,
+/// that starts from the first file again.
+impl Clone for IndexedCache {
+    fn clone(&self) -> Self {
+        Self::new(self.cache.clone(), self.indicies.clone(), self.files_count)
+    }
+}
+
 /// An "easy to do bytetricks and writes" enum.
 #[derive(Debug, Clone, Copy)]
 enum ByteOrUsize {
@@ -701,6 +972,7 @@ mod iter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Rules;
 
     #[test]
     fn test_serialize_deserialize() {
@@ -718,8 +990,9 @@ mod tests {
 
         let cache = serialize(
             current_dir.as_os_str().to_str().unwrap(),
-            ignore::WalkBuilder::new(&current_dir),
+            Rules::new().walk_builder(&current_dir).unwrap(),
             NotUtf8::ReturnError,
+            |e| panic!("{}", e),
         )
         .unwrap();
 
@@ -728,13 +1001,35 @@ mod tests {
 
         println!("\n\n");
 
+        let files_count = cache.files_count();
+        assert!(files_count > 0);
+
         let vec_cache: Vec<u8> = cache.show_cache().to_owned();
         let cache = deserialize(vec_cache).unwrap();
+        assert_eq!(cache.files_count(), files_count);
 
         let q = qwe(cache.stream_iter().unwrap());
         assert!(q.is_ok());
     }
 
+    #[test]
+    fn test_walk_errors_do_not_abort() {
+        let mut missing_dir = std::env::current_dir().unwrap();
+        missing_dir.push("surely there is no such folder");
+
+        let mut walk_errors = Vec::new();
+        let cache = serialize(
+            missing_dir.as_os_str().to_str().unwrap(),
+            Rules::new().walk_builder(&missing_dir).unwrap(),
+            NotUtf8::ReturnError,
+            |e| walk_errors.push(e),
+        )
+        .unwrap();
+
+        assert!(!walk_errors.is_empty());
+        assert_eq!(cache.stream_iter().unwrap().read_next().unwrap(), None);
+    }
+
     #[test]
     fn test_multithread_access() {
         fn asd(mut iter: StreamIter) -> Result<Vec<Box<str>>, InvalidCache<()>> {
@@ -755,8 +1050,9 @@ mod tests {
 
         let cache = serialize(
             current_dir.as_os_str().to_str().unwrap(),
-            ignore::WalkBuilder::new(&current_dir),
+            Rules::new().walk_builder(&current_dir).unwrap(),
             NotUtf8::ReturnError,
+            |e| panic!("{}", e),
         )
         .unwrap();
 
@@ -781,4 +1077,102 @@ mod tests {
         collected.sort_unstable();
         collected.windows(2).for_each(|sl| assert_ne!(sl[0], sl[1]));
     }
+
+    #[test]
+    fn test_recently_modified_first() {
+        use {
+            crate::temp_tree::TempTree,
+            filetime::{set_file_mtime, FileTime},
+        };
+
+        let tree = TempTree::new("modified-first");
+        let root = tree.path();
+        tree.write("a/old", "old").write("b/new", "new");
+        // The walk goes to the old file first, so only the order could put the new one first.
+        set_file_mtime(root.join("a/old"), FileTime::from_unix_time(1_000_000, 0)).unwrap();
+        set_file_mtime(root.join("b/new"), FileTime::from_unix_time(2_000_000, 0)).unwrap();
+
+        let cache = serialize_ordered(
+            root.to_str().unwrap(),
+            Rules::new().walk_builder(root).unwrap(),
+            CacheOptions {
+                order: FileOrder::Modified,
+                ..CacheOptions::default()
+            },
+            NotUtf8::ReturnError,
+            |e| panic!("{}", e),
+        )
+        .unwrap();
+        let mut iter = cache.stream_iter().unwrap();
+        let first = iter.read_next().unwrap().map(String::from);
+
+        assert_eq!(cache.files_count(), 2);
+        assert!(first.unwrap().ends_with("new"));
+    }
+
+    #[test]
+    fn test_small_files_first() {
+        use crate::temp_tree::TempTree;
+
+        let tree = TempTree::new("small-first");
+        let root = tree.path();
+        tree.write("a/big", "big".repeat(100))
+            .write("a/empty", "")
+            .write("small", "small");
+
+        let mut builder = ignore::WalkBuilder::new(root);
+        builder.sort_by_file_path(|a, b| a.cmp(b));
+        let cache = serialize_ordered(
+            root.to_str().unwrap(),
+            builder,
+            CacheOptions {
+                order: FileOrder::Size,
+                ..CacheOptions::default()
+            },
+            NotUtf8::ReturnError,
+            |e| panic!("{}", e),
+        )
+        .unwrap();
+        let mut iter = cache.stream_iter().unwrap();
+        let mut files = Vec::new();
+        while let Some(path) = iter.read_next().unwrap() {
+            files.push(path.rsplit(path::is_separator).next().unwrap().to_owned());
+        }
+
+        assert_eq!(files, ["empty", "small", "big"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_linked_files_are_cached_once() {
+        use {
+            crate::temp_tree::TempTree,
+            std::{fs, os::unix::fs::symlink},
+        };
+
+        let tree = TempTree::new("dedupe-links");
+        let root = tree.path();
+        tree.write("real/file", "fn main() {}");
+        fs::hard_link(root.join("real").join("file"), root.join("hardlink")).unwrap();
+        symlink(root.join("real").join("file"), root.join("symlink")).unwrap();
+
+        let cache = |dedupe_links| {
+            let mut r = Rules::new();
+            r.follow_links = true;
+            let builder = r.walk_builder(root).unwrap();
+            let options = CacheOptions {
+                dedupe_links,
+                ..CacheOptions::default()
+            };
+            serialize_relative(root, builder, options, NotUtf8::ReturnError, |e| {
+                panic!("{}", e)
+            })
+            .unwrap()
+            .files_count()
+        };
+        let (all, deduped) = (cache(false), cache(true));
+
+        assert_eq!(all, 3);
+        assert_eq!(deduped, 1);
+    }
 }
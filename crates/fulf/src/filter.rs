@@ -0,0 +1,378 @@
+//! Filtering of the lines, that are already in memory,
+//! e.g. the lines of the Vim buffer.
+
+use {
+    crate::fzy_algo::{ascii, scoring_utils::MWP, utf8},
+    std::{cmp::Reverse, mem},
+};
+
+/// Scores every line with the fzy algorithm,
+/// returns the matched lines, best scores first.
+///
+/// Unlike the file search, the lines are not trimmed,
+/// so positions point to the chars of the line as it is.
+/// Lines with equal scores keep their order.
+pub fn filter_lines(needle: &str, lines: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<MWP> {
+    filter_by(needle, lines, |line| line.as_ref())
+        .into_iter()
+        .map(|(line, score, positions)| (line.as_ref().into(), score, positions))
+        .collect()
+}
+
+/// The lines, that do NOT match the needle, in their order,
+/// like the `grep -v`: with the zero scores and no positions,
+/// so they are handled just like the results of the [`filter_lines`].
+///
+/// [`filter_lines`]: fn.filter_lines.html
+pub fn filter_out_lines(
+    needle: &str,
+    lines: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Vec<MWP> {
+    let mut prealloc = (Vec::new(), Vec::new());
+    let ascii_needle = needle.is_ascii();
+
+    lines
+        .into_iter()
+        .filter(|line| score_text(needle, ascii_needle, line.as_ref(), &mut prealloc).is_none())
+        .map(|line| (line.as_ref().into(), 0, Box::new([]) as Box<[usize]>))
+        .collect()
+}
+
+/// Like the [`filter_lines`], but only the chosen [`Fields`] of the lines
+/// are matched; the positions still point to the chars of the whole line.
+///
+/// ```
+/// use fulf::filter::{filter_fields, Fields};
+///
+/// let lines = ["src/fb.rs:1:fn main()", "src/main.rs:2:fn bar()"];
+/// let fields = Fields::parse(':', "3..").unwrap();
+/// let matched = filter_fields("fb", lines.iter(), &fields);
+/// assert_eq!(matched.len(), 1);
+/// assert_eq!(&*matched[0].0, "src/main.rs:2:fn bar()");
+/// assert_eq!(&*matched[0].2, [14, 17]);
+/// ```
+///
+/// [`filter_lines`]: fn.filter_lines.html
+/// [`Fields`]: struct.Fields.html
+pub fn filter_fields(
+    needle: &str,
+    lines: impl IntoIterator<Item = impl AsRef<str>>,
+    fields: &Fields,
+) -> Vec<MWP> {
+    let mut prealloc = (Vec::new(), Vec::new());
+    let ascii_needle = needle.is_ascii();
+    let mut chosen = Chosen::default();
+
+    let mut matched: Vec<MWP> = lines
+        .into_iter()
+        .filter_map(|line| {
+            let line = line.as_ref();
+            fields.choose(line, &mut chosen);
+            let (score, mut positions) =
+                score_text(needle, ascii_needle, &chosen.text, &mut prealloc)?;
+            positions.iter_mut().for_each(|p| *p = chosen.line_char(*p));
+
+            Some((line.into(), score, positions.into_boxed_slice()))
+        })
+        .collect();
+
+    matched.sort_by_key(|m| Reverse(m.1));
+    matched
+}
+
+/// Narrows the `path:row:col:text` results of the search, or the
+/// `path:row:text` lines of the grep, by the second fuzzy pass:
+/// only the text is matched, the path and the row are kept as they are.
+///
+/// The positions point to the chars of the whole record;
+/// the records without the row are matched whole.
+///
+/// ```
+/// use fulf::filter::refilter_records;
+///
+/// let records = ["src/fb.rs:1:4:fn main()", "src/main.rs:2:fn bar()"];
+/// let matched = refilter_records("fb", records.iter());
+/// assert_eq!(matched.len(), 1);
+/// assert_eq!(&*matched[0].0, "src/main.rs:2:fn bar()");
+/// assert_eq!(&*matched[0].2, [14, 17]);
+/// ```
+pub fn refilter_records(
+    needle: &str,
+    records: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Vec<MWP> {
+    let mut prealloc = (Vec::new(), Vec::new());
+    let ascii_needle = needle.is_ascii();
+
+    let mut matched: Vec<MWP> = records
+        .into_iter()
+        .filter_map(|record| {
+            let record = record.as_ref();
+            let (score, positions) = score_record(needle, ascii_needle, record, &mut prealloc)?;
+            Some((record.into(), score, positions.into_boxed_slice()))
+        })
+        .collect();
+
+    matched.sort_by_key(|m| Reverse(m.1));
+    matched
+}
+
+/// Scores the results again by the new needle, without reading the files:
+/// the ones, that don't match, are removed, the rest are sorted
+/// by the new scores, best first.
+///
+/// Just like the [`refilter_records`], only the text of the `path:row:col:text`
+/// results is matched, so the list could be filtered again and again.
+///
+/// ```
+/// use fulf::filter::{refilter_records, rerank};
+///
+/// let records = ["a.rs:1:1:fn bar()", "b.rs:2:1:let fb = 1;", "c.rs:3:1:nothing"];
+/// let mut results = refilter_records("f", records.iter());
+/// assert_eq!(results.len(), 2);
+/// rerank(&mut results, "fb");
+/// assert_eq!(&*results[0].0, "b.rs:2:1:let fb = 1;");
+/// rerank(&mut results, "nothing");
+/// assert!(results.is_empty());
+/// ```
+///
+/// [`refilter_records`]: fn.refilter_records.html
+pub fn rerank(results: &mut Vec<MWP>, needle: &str) {
+    let mut prealloc = (Vec::new(), Vec::new());
+    let ascii_needle = needle.is_ascii();
+
+    *results = mem::take(results)
+        .into_iter()
+        .filter_map(|(record, _score, _positions)| {
+            let (score, positions) = score_record(needle, ascii_needle, &record, &mut prealloc)?;
+            Some((record, score, positions.into_boxed_slice()))
+        })
+        .collect();
+    results.sort_by_key(|m| Reverse(m.1));
+}
+
+/// Scores the text of the record, see the `text_start`;
+/// the positions point to the chars of the whole record.
+fn score_record(
+    needle: &str,
+    ascii_needle: bool,
+    record: &str,
+    prealloc: &mut (Vec<i32>, Vec<i32>),
+) -> Option<(i32, Vec<usize>)> {
+    let (meta, text) = record.split_at(text_start(record));
+    let (score, mut positions) = score_text(needle, ascii_needle, text, prealloc)?;
+    let meta_chars = meta.chars().count();
+    positions.iter_mut().for_each(|p| *p += meta_chars);
+    Some((score, positions))
+}
+
+/// The byte index of the text of the `path:row:col:text`
+/// or of the `path:row:text` record, 0 if there's no row.
+///
+/// The path itself could contain `:` chars,
+/// so the first `:row:` is the end of the path.
+fn text_start(record: &str) -> usize {
+    // The index after the digits and the `:` after them.
+    let number_end = |start: usize| {
+        let rest = &record[start..];
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        if digits != 0 && rest[digits..].starts_with(':') {
+            Some(start + digits + 1)
+        } else {
+            None
+        }
+    };
+
+    record
+        .match_indices(':')
+        .find_map(|(idx, _)| number_end(idx + 1))
+        .map_or(0, |row_end| number_end(row_end).unwrap_or(row_end))
+}
+
+/// The fields of the line to match, like the `--nth` of the fzf,
+/// e.g. only the text of the `path:line:text` lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields {
+    delimiter: char,
+    // The inclusive 1-based ranges, the negative ones count from the end.
+    ranges: Vec<(isize, isize)>,
+}
+
+impl Fields {
+    /// The fields split by the `delimiter`, chosen by the `nth`:
+    /// the comma-separated list of the 1-based field numbers and ranges,
+    /// just like the fzf has, e.g. `1,3`, `-1` (the last one), `2..`, `..-2`.
+    ///
+    /// `None` if the `nth` is not such a list, or has the field 0.
+    pub fn parse(delimiter: char, nth: &str) -> Option<Self> {
+        let number = |n: &str| match n.trim().parse::<isize>() {
+            Ok(0) | Err(_) => None,
+            Ok(n) => Some(n),
+        };
+        let ranges = nth
+            .split(',')
+            .map(|range| match range.find("..") {
+                Some(idx) => {
+                    let (start, end) = (&range[..idx], &range[idx + 2..]);
+                    let start = if start.trim().is_empty() {
+                        1
+                    } else {
+                        number(start)?
+                    };
+                    let end = if end.trim().is_empty() {
+                        -1
+                    } else {
+                        number(end)?
+                    };
+                    Some((start, end))
+                }
+                None => number(range).map(|n| (n, n)),
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { delimiter, ranges })
+    }
+
+    /// Collects the chosen fields of the line, in the order of the line.
+    fn choose(&self, line: &str, chosen: &mut Chosen) {
+        chosen.text.clear();
+        chosen.segments.clear();
+
+        let count = line.split(self.delimiter).count() as isize;
+        // The 1-based field number.
+        let resolve = |n: isize| if n < 0 { count + 1 + n } else { n };
+        let mut line_chars = 0;
+        for (idx, field) in line.split(self.delimiter).enumerate() {
+            let n = idx as isize + 1;
+            let is_chosen = self
+                .ranges
+                .iter()
+                .any(|&(start, end)| resolve(start) <= n && n <= resolve(end));
+            if is_chosen {
+                chosen
+                    .segments
+                    .push((chosen.text.chars().count(), line_chars));
+                chosen.text.push_str(field);
+            }
+            // The field with its delimiter.
+            line_chars += field.chars().count() + 1;
+        }
+    }
+}
+
+/// The text of the chosen fields, reused for every line.
+#[derive(Debug, Default)]
+struct Chosen {
+    text: String,
+    // The char index of every field in the `text` and in the line.
+    segments: Vec<(usize, usize)>,
+}
+
+impl Chosen {
+    /// The char index in the line of the char of the `text`.
+    fn line_char(&self, text_char: usize) -> usize {
+        let segment = match self.segments.binary_search_by_key(&text_char, |s| s.0) {
+            Ok(idx) => idx,
+            Err(idx) => idx - 1,
+        };
+        let (text_start, line_start) = self.segments[segment];
+        line_start + (text_char - text_start)
+    }
+}
+
+/// Like the [`filter_lines`], but the items are scored by the `key` line,
+/// and are returned as they are, e.g. the tags with their files and lines.
+///
+/// [`filter_lines`]: fn.filter_lines.html
+pub fn filter_by<T>(
+    needle: &str,
+    items: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> &str,
+) -> Vec<(T, i32, Box<[usize]>)> {
+    let mut prealloc = (Vec::new(), Vec::new());
+    let ascii_needle = needle.is_ascii();
+
+    let mut matched: Vec<(T, i32, Box<[usize]>)> = items
+        .into_iter()
+        .filter_map(|item| {
+            let (score, positions) = score_text(needle, ascii_needle, key(&item), &mut prealloc)?;
+            Some((item, score, positions.into_boxed_slice()))
+        })
+        .collect();
+
+    // Stable sort, so equal scores keep the order of the items,
+    // just like the `ByScore` does.
+    matched.sort_by_key(|m| Reverse(m.1));
+    matched
+}
+
+/// Scores the text with the fzy algorithm,
+/// the faster ASCII version if both the needle and the text are ASCII.
+fn score_text(
+    needle: &str,
+    ascii_needle: bool,
+    text: &str,
+    prealloc: &mut (Vec<i32>, Vec<i32>),
+) -> Option<(i32, Vec<usize>)> {
+    if ascii_needle && text.is_ascii() {
+        ascii::match_and_score_with_positions(needle.as_bytes(), text.as_bytes(), prealloc)
+    } else {
+        utf8::match_and_score_with_positions(needle, text, prealloc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_and_sorts() {
+        let lines = ["x = foo.bar", "nothing", "fn bar()", "ёж fb"];
+        let matched = filter_lines("fb", lines.iter());
+
+        assert_eq!(matched.len(), 3);
+        assert!(matched.windows(2).all(|w| w[0].1 >= w[1].1));
+        for (line, _score, positions) in &matched {
+            let chars: Vec<char> = line.chars().collect();
+            assert_eq!([chars[positions[0]], chars[positions[1]]], ['f', 'b']);
+        }
+    }
+
+    #[test]
+    fn filters_out_the_matched_lines() {
+        let lines = ["x = foo.bar", "nothing", "fn bar()", "ёж"];
+        let lines: Vec<String> = filter_out_lines("fb", lines.iter())
+            .into_iter()
+            .map(|(line, _score, _positions)| line)
+            .collect();
+        assert_eq!(lines, ["nothing", "ёж"]);
+    }
+
+    #[test]
+    fn record_texts_are_found() {
+        assert_eq!(text_start("a.rs:12:3:fn x"), 10);
+        assert_eq!(text_start("a.rs:12:fn x"), 8);
+        assert_eq!(text_start(r"C:\a.rs:1:2:x"), 12);
+        assert_eq!(text_start("no row: here"), 0);
+        assert_eq!(text_start("a.rs:12:34"), 8);
+    }
+
+    #[test]
+    fn fields_are_chosen() {
+        let fields = |nth| Fields::parse(':', nth).unwrap();
+        let chosen = |fields: Fields, line| {
+            let mut chosen = Chosen::default();
+            fields.choose(line, &mut chosen);
+            chosen.text
+        };
+        assert_eq!(chosen(fields("1,3"), "a:b:c:d"), "ac");
+        assert_eq!(chosen(fields("-1"), "a:b:c:d"), "d");
+        assert_eq!(chosen(fields("..2"), "a:b:c:d"), "ab");
+        assert_eq!(chosen(fields("2..-2"), "a:b:c:d"), "bc");
+        assert_eq!(chosen(fields("5"), "a:b"), "");
+        assert_eq!(Fields::parse(':', "0"), None);
+        assert_eq!(Fields::parse(':', "x.."), None);
+
+        let matched = filter_fields("ac", ["ёa:b:c"].iter(), &fields("1,3"));
+        assert_eq!(&*matched[0].2, [1, 5]);
+    }
+}
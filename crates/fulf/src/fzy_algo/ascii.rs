@@ -7,10 +7,32 @@ use {
         score_with_positions,
         scoring_utils::{MatchWithPositions, Score},
     },
-    memchr::memchr,
-    std::cmp,
+    memchr::{memchr, memchr2},
+    std::{convert::TryInto, str},
 };
 
+/// Every byte in the lower case, indexed by the byte itself.
+pub(crate) static LOWERCASE: [u8; 256] = lowercase_table();
+
+const fn lowercase_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        table[byte] = match byte as u8 {
+            upper @ b'A'..=b'Z' => upper | 0b10_0000,
+            other => other,
+        };
+        byte += 1;
+    }
+    table
+}
+
+/// Compares two bytes case-insensitively, without any branches.
+#[inline]
+pub(crate) fn eq_ignore_case(a: u8, b: u8) -> bool {
+    LOWERCASE[a as usize] == LOWERCASE[b as usize]
+}
+
 #[inline]
 pub fn match_and_score_with_positions(
     needle: &[u8],
@@ -34,17 +56,14 @@ pub fn matcher(line: &[u8], needle: &[u8]) -> Option<LineMetaData> {
             return None;
         }
 
-        let rcase_idx = reverse_ascii_case(letter).and_then(|rev_letter| memchr(rev_letter, line));
-
-        let ocase_idx = memchr(letter, line);
+        // Both cases are found in one pass over the line.
+        let idx = match reverse_ascii_case(letter) {
+            Some(rev_letter) => memchr2(letter, rev_letter, line),
+            None => memchr(letter, line),
+        };
 
         // ASCII letter length is always 1, so this is always 1 + ...
-        let next_idx = 1 + match (ocase_idx, rcase_idx) {
-            (Some(o_idx), Some(r_idx)) => cmp::min(o_idx, r_idx),
-            (Some(o_idx), None) => o_idx,
-            (None, Some(r_idx)) => r_idx,
-            (None, None) => return None,
-        };
+        let next_idx = 1 + idx?;
 
         nee_len -= 1;
         line = &line[next_idx..];
@@ -53,6 +72,42 @@ pub fn matcher(line: &[u8], needle: &[u8]) -> Option<LineMetaData> {
     Some(())
 }
 
+/// The high bit of every byte of the word: it's set only for the non-ASCII bytes.
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// Returns the bytes as `&str`, if all of them are ASCII.
+///
+/// Runs over the whole text of every searched file, so it checks
+/// 16 bytes per iteration: two words are OR-ed together, and then
+/// the high bits of the result are tested at once.
+///
+/// # Examples
+///
+/// ```
+/// use fulf::fzy_algo::ascii::ascii_from_bytes;
+///
+/// assert_eq!(ascii_from_bytes(b"fn main() {}"), Some("fn main() {}"));
+/// assert_eq!(ascii_from_bytes("fn м() {}".as_bytes()), None);
+/// ```
+#[inline]
+pub fn ascii_from_bytes(bytes: &[u8]) -> Option<&str> {
+    let mut chunks = bytes.chunks_exact(16);
+    for chunk in &mut chunks {
+        let (a, b) = chunk.split_at(8);
+        let word =
+            u64::from_ne_bytes(a.try_into().unwrap()) | u64::from_ne_bytes(b.try_into().unwrap());
+        if word & HIGH_BITS != 0 {
+            return None;
+        }
+    }
+    if !chunks.remainder().iter().all(u8::is_ascii) {
+        return None;
+    }
+
+    // SAFETY: every byte is checked and is ASCII, which is always valid utf8.
+    Some(unsafe { str::from_utf8_unchecked(bytes) })
+}
+
 /// Reverses the case of a byte:
 /// lowercase letter becomes uppercased,
 /// and uppercase letter becomes lowercased.
@@ -100,4 +155,31 @@ mod tests {
 
         assert!(iter.eq(b"hELLOwORLD".iter().cloned()));
     }
+
+    #[test]
+    fn non_ascii_byte_is_found_anywhere() {
+        let text = [b'a'; 40];
+        assert!(ascii_from_bytes(&text).is_some());
+        assert!(ascii_from_bytes(&[]).is_some());
+        for idx in 0..text.len() {
+            let mut text = text;
+            text[idx] = 0x80;
+            assert_eq!(ascii_from_bytes(&text), None, "{}", idx);
+        }
+    }
+
+    #[test]
+    fn tables_fold_only_ascii_letters() {
+        for byte in 0..=u8::MAX {
+            assert_eq!(LOWERCASE[byte as usize], byte.to_ascii_lowercase());
+            for other in 0..=u8::MAX {
+                assert_eq!(
+                    eq_ignore_case(byte, other),
+                    byte.eq_ignore_ascii_case(&other)
+                );
+            }
+        }
+        assert!(matcher(b"src/Main.RS", b"mainrs").is_some());
+        assert!(matcher(b"src/Main.RS", b"main_rs").is_none());
+    }
 }
@@ -0,0 +1,191 @@
+//! Why the line has its score: the bonuses and the gaps of the matched chars.
+//!
+//! The algorithm finds the best positions of the needle's chars,
+//! each matched char gets the bonus for the char before it
+//! (or for the match right after the previous one),
+//! and the unmatched chars around and between them are the penalties.
+//!
+//! # Examples
+//!
+//! ```
+//! use fulf::fzy_algo::explain::{explain, Bonus};
+//!
+//! let explanation = explain("mr", "src/main.rs").unwrap();
+//! assert_eq!(explanation.chars[0].bonus, Bonus::Slash);
+//! assert_eq!(explanation.chars[1].bonus, Bonus::Dot);
+//! println!("{}", explanation);
+//! ```
+
+use {
+    super::{scoring_utils::*, utf8, FzyItem},
+    std::fmt,
+};
+
+/// The kind of the bonus of the matched char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bonus {
+    /// Nothing special before the char.
+    None,
+    /// The char right after the `/`, the start of the file or folder name.
+    Slash,
+    /// The char right after the `-`, `_` or space, the start of the word.
+    Word,
+    /// The capital letter right after the lowercase one, the `camelCase` word.
+    Capital,
+    /// The char right after the `.`, the extension.
+    Dot,
+    /// The char right after the previous matched char.
+    Consecutive,
+}
+
+/// The matched char of the haystack and the part of the score it gives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchedChar {
+    /// Position of the char in the haystack, in chars.
+    pub position: usize,
+    pub ch: char,
+    pub bonus: Bonus,
+    /// The score of the bonus.
+    pub bonus_score: i32,
+    /// The penalty for the unmatched chars before this one:
+    /// the leading gap for the first char, the inner gap for others.
+    pub gap_score: i32,
+}
+
+/// The breakdown of the score of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The score, given by the algorithm.
+    pub score: i32,
+    /// The needle is the whole line, the best score there is;
+    /// the bonuses and gaps are not used then.
+    pub perfect: bool,
+    pub chars: Vec<MatchedChar>,
+    /// The penalty for the unmatched chars after the last matched one.
+    pub trailing_gap_score: i32,
+}
+
+impl Explanation {
+    /// The sum of all the bonuses and gaps.
+    ///
+    /// It's the `score`, unless the match is perfect,
+    /// or the score is saturated.
+    pub fn total(&self) -> i32 {
+        self.chars
+            .iter()
+            .fold(self.trailing_gap_score, |total, matched| {
+                total
+                    .saturating_add(matched.bonus_score)
+                    .saturating_add(matched.gap_score)
+            })
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.perfect {
+            return writeln!(f, "score {}: the perfect match", self.score);
+        }
+        writeln!(f, "score {}", self.score)?;
+        for matched in &self.chars {
+            writeln!(
+                f,
+                "{:?} at {}: {:+} {:?}, gap {:+}",
+                matched.ch, matched.position, matched.bonus_score, matched.bonus, matched.gap_score,
+            )?;
+        }
+        writeln!(f, "trailing gap {:+}", self.trailing_gap_score)
+    }
+}
+
+/// Scores the `haystack` just like the UTF-8 algorithm does,
+/// and explains the score.
+///
+/// `None` if the haystack doesn't have all the needle's chars.
+pub fn explain(needle: &str, haystack: &str) -> Option<Explanation> {
+    let (score, positions) =
+        utf8::match_and_score_with_positions(needle, haystack, &mut Default::default())?;
+    let chars: Vec<char> = haystack.chars().collect();
+
+    let mut explanation = Explanation {
+        score,
+        perfect: score == SCORE_MAX,
+        chars: Vec::with_capacity(positions.len()),
+        trailing_gap_score: 0,
+    };
+    if explanation.perfect {
+        return Some(explanation);
+    }
+
+    let mut prev_position: Option<usize> = None;
+    for &position in &positions {
+        let ch = chars[position];
+        let (bonus, bonus_score, gap_score) = match prev_position {
+            None => {
+                let (bonus, bonus_score) = char_bonus(&chars, position);
+                let gap = score_mul(score_from_usize(position), SCORE_GAP_LEADING);
+                (bonus, bonus_score, gap)
+            }
+            Some(prev) if prev + 1 == position => (Bonus::Consecutive, SCORE_MATCH_CONSECUTIVE, 0),
+            Some(prev) => {
+                let (bonus, bonus_score) = char_bonus(&chars, position);
+                let gap = score_mul(score_from_usize(position - prev - 1), SCORE_GAP_INNER);
+                (bonus, bonus_score, gap)
+            }
+        };
+        explanation.chars.push(MatchedChar {
+            position,
+            ch,
+            bonus,
+            bonus_score,
+            gap_score,
+        });
+        prev_position = Some(position);
+    }
+
+    let last = prev_position.unwrap_or(0);
+    explanation.trailing_gap_score = score_mul(
+        score_from_usize(chars.len().saturating_sub(last + 1)),
+        SCORE_GAP_TRAILING,
+    );
+    Some(explanation)
+}
+
+/// The bonus of the char for the char before it.
+fn char_bonus(chars: &[char], position: usize) -> (Bonus, Score) {
+    let prev = match position {
+        0 => <char as FzyItem>::INIT,
+        _ => chars[position - 1],
+    };
+    let score = <char as FzyItem>::bonus_for_char(prev, chars[position]);
+    let bonus = match score {
+        SCORE_MATCH_SLASH => Bonus::Slash,
+        SCORE_MATCH_WORD => Bonus::Word,
+        SCORE_MATCH_CAPITAL => Bonus::Capital,
+        SCORE_MATCH_DOT => Bonus::Dot,
+        _ => Bonus::None,
+    };
+    (bonus, score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_add_up_to_the_score() {
+        for (needle, haystack) in &[
+            ("mr", "src/main.rs"),
+            ("fb", "foo_bar"),
+            ("ab", "xaab"),
+            ("fooBar", "src/fooBarBaz.rs"),
+        ] {
+            let explanation = explain(needle, haystack).unwrap();
+            assert_eq!(explanation.total(), explanation.score, "{}", explanation);
+        }
+
+        let explanation = explain("abc", "aBc").unwrap();
+        assert!(explanation.perfect);
+        assert_eq!(explain("abc", "cba"), None);
+    }
+}
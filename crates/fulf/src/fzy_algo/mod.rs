@@ -1,4 +1,5 @@
 pub mod ascii;
+pub mod explain;
 pub mod scoring_utils;
 pub mod utf8;
 
@@ -29,7 +30,7 @@ impl FzyItem for &u8 {
 
     #[inline]
     fn eq(a: Self, b: Self) -> bool {
-        a.eq_ignore_ascii_case(b)
+        ascii::eq_ignore_case(*a, *b)
     }
 
     #[inline]
@@ -91,6 +92,36 @@ impl FzyItem for char {
     }
 }
 
+/// The match is one of the best there could be: the perfect one,
+/// or all the chars are consecutive, and the first one starts the word or the file name.
+///
+/// The `positions` are the char positions in the `line`, in the ascending order.
+#[cfg(feature = "search")]
+pub(crate) fn is_best_match(line: &str, score: Score, positions: &[usize]) -> bool {
+    if score == SCORE_MAX {
+        return true;
+    }
+    let (first, last) = match (positions.first(), positions.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return false,
+    };
+    if last - first + 1 != positions.len() {
+        return false;
+    }
+
+    let mut chars = line.chars().skip(first.saturating_sub(1));
+    let prev = match first {
+        0 => <char as FzyItem>::INIT,
+        _ => match chars.next() {
+            Some(prev) => prev,
+            None => return false,
+        },
+    };
+    chars.next().map_or(false, |current| {
+        <char as FzyItem>::bonus_for_char(prev, current) >= SCORE_MATCH_WORD
+    })
+}
+
 /// The `IntoIterator` trait is not implemented for strings.
 /// But this trait is implemented for strings via `.chars()` method.
 pub trait FzyScorable: Copy {
@@ -43,3 +43,48 @@ pub(crate) fn score_mul(score: Score, rhs: Score) -> Score {
 pub(crate) fn score_from_usize(u: usize) -> Score {
     Score::try_from(u).unwrap_or(SCORE_MAX)
 }
+
+/// The highest normalized score, the one of the perfect match.
+pub const NORMALIZED_MAX: u16 = 1000;
+
+/// The score on the `0..=1000` scale, which doesn't depend
+/// on the length of the needle, so the scores of different needles
+/// and algorithms could be compared, merged and thresholded.
+///
+/// The 1000 is the perfect match; the best imperfect match, the needle
+/// right after the `/` with all chars consecutive, is 999;
+/// the scores below zero, with the long gaps, are 0.
+///
+/// `needle_len` is the number of the needle's chars.
+pub fn normalize_score(score: i32, needle_len: usize) -> u16 {
+    if score == SCORE_MAX {
+        return NORMALIZED_MAX;
+    }
+    let best = score_add(
+        SCORE_MATCH_SLASH,
+        score_mul(
+            score_from_usize(needle_len.saturating_sub(1)),
+            SCORE_MATCH_CONSECUTIVE,
+        ),
+    );
+    if score <= 0 || best <= 0 {
+        return 0;
+    }
+    let scaled = i64::from(score) * i64::from(NORMALIZED_MAX - 1) / i64::from(best);
+    scaled.min(i64::from(NORMALIZED_MAX - 1)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_scores_are_in_the_scale() {
+        assert_eq!(normalize_score(SCORE_MAX, 3), NORMALIZED_MAX);
+        assert_eq!(normalize_score(SCORE_MIN, 3), 0);
+        assert_eq!(normalize_score(-5, 3), 0);
+        assert_eq!(normalize_score(180 + 2 * 200, 3), 999);
+        assert_eq!(normalize_score(290, 3), 499);
+        assert!(normalize_score(290, 3) < normalize_score(290, 2));
+    }
+}
@@ -0,0 +1,199 @@
+//! The files of the git repository, instead of the walk.
+//!
+//! The repository index already knows the files of the project,
+//! so there's nothing to walk, and nothing to ignore:
+//! these are the files, the user means by "search my project".
+//! And the files, changed since the `HEAD`, are the ones the user
+//! has just written something in, during a large refactor.
+//!
+//! The caches are relative to the given root, just like the ones
+//! of the [`serialize_relative`].
+//!
+//! [`serialize_relative`]: ../filepath_cache/fn.serialize_relative.html
+
+use {
+    crate::filepath_cache::{serialize_paths, IndexedCache},
+    git2::{Repository, Status, StatusOptions},
+    std::{
+        error::Error,
+        fmt, io,
+        path::{self, Path, PathBuf},
+        str,
+    },
+};
+
+/// The mode of the submodule entry, it's not a file.
+const GITLINK_MODE: u32 = 0o160_000;
+
+/// The files of the repository index inside the `root`,
+/// which could be the working directory or any folder inside it.
+///
+/// The index is sorted by the path, so are the files of the cache.
+/// Not UTF-8 paths and the submodules are left out, and the file
+/// with the merge conflict is listed once, not once per its stage.
+pub fn tracked_files(root: impl AsRef<Path>) -> Result<IndexedCache, GitError> {
+    let root = root.as_ref();
+    let (repo, prefix) = open(root)?;
+    let index = repo.index().map_err(GitError::Git)?;
+
+    let mut paths: Vec<String> = index
+        .iter()
+        .filter(|entry| entry.mode & GITLINK_MODE != GITLINK_MODE)
+        .filter_map(|entry| str::from_utf8(&entry.path).ok().map(String::from))
+        .filter_map(|path| relative_path(&path, &prefix))
+        .collect();
+    // The stages of one path are next to each other.
+    paths.dedup();
+    Ok(serialize_paths(paths.iter().map(String::as_str)))
+}
+
+/// The files inside the `root`, that were added or modified since the `HEAD`,
+/// staged or not, and the new untracked files; the ignored ones are left out.
+///
+/// The deleted files are left out too, and the renamed file is just the new one.
+pub fn changed_files(root: impl AsRef<Path>) -> Result<IndexedCache, GitError> {
+    let root = root.as_ref();
+    let (repo, prefix) = open(root)?;
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo.statuses(Some(&mut options)).map_err(GitError::Git)?;
+
+    let changed = Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_TYPECHANGE
+        | Status::WT_NEW
+        | Status::WT_MODIFIED
+        | Status::WT_TYPECHANGE;
+    let mut paths: Vec<String> = statuses
+        .iter()
+        .filter(|entry| {
+            let status = entry.status();
+            status.intersects(changed) && !status.intersects(Status::WT_DELETED)
+        })
+        .filter_map(|entry| entry.path().and_then(|path| relative_path(path, &prefix)))
+        .collect();
+    paths.sort_unstable();
+    Ok(serialize_paths(paths.iter().map(String::as_str)))
+}
+
+/// Opens the repository of the `root`, and gets the path of the `root`
+/// inside its working directory, with the `/` separators and the trailing one.
+fn open(root: &Path) -> Result<(Repository, String), GitError> {
+    let repo = Repository::discover(root).map_err(GitError::Git)?;
+    let workdir: PathBuf = repo
+        .workdir()
+        .ok_or(GitError::NoWorkdir)?
+        .canonicalize()
+        .map_err(GitError::Io)?;
+    let root = root.canonicalize().map_err(GitError::Io)?;
+
+    let prefix = root
+        .strip_prefix(&workdir)
+        .map_err(|_| GitError::OutsideWorkdir)?
+        .to_str()
+        .ok_or(GitError::OutsideWorkdir)?;
+    let mut prefix = prefix.replace(path::is_separator, "/");
+    if !prefix.is_empty() {
+        prefix.push('/');
+    }
+    Ok((repo, prefix))
+}
+
+/// The path of the repository relative to the root, that is the `prefix`
+/// relative to the working directory, with the native separators.
+fn relative_path(path: &str, prefix: &str) -> Option<String> {
+    let path = path.strip_prefix(prefix)?;
+    Some(if cfg!(windows) {
+        path.replace('/', "\\")
+    } else {
+        path.to_owned()
+    })
+}
+
+/// The error of the repository reading.
+#[derive(Debug)]
+pub enum GitError {
+    Git(git2::Error),
+    /// The root folder can't be canonicalized.
+    Io(io::Error),
+    /// The repository is a bare one, it has no files to search.
+    NoWorkdir,
+    /// The root folder is not inside the working directory,
+    /// e.g. it's inside the `.git` folder.
+    OutsideWorkdir,
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Git(e) => write!(f, "can't read the repository: {}", e),
+            GitError::Io(e) => write!(f, "can't find the root: {}", e),
+            GitError::NoWorkdir => f.write_str("the repository has no working directory"),
+            GitError::OutsideWorkdir => f.write_str("the root is outside of the working directory"),
+        }
+    }
+}
+
+impl Error for GitError {}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::temp_tree::TempTree,
+        git2::{IndexEntry, IndexTime, Oid},
+    };
+
+    #[test]
+    fn conflicted_file_is_listed_once() {
+        let tree = TempTree::new("git-conflict");
+        let repo = Repository::init(tree.path()).unwrap();
+        let blob = repo.blob(b"fn main() {}\n").unwrap();
+        let entry = |path: &str, id: Oid, stage: u16| IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100_644,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id,
+            // The stage is in the bits 12 and 13 of the flags.
+            flags: stage << 12,
+            flags_extended: 0,
+            path: path.into(),
+        };
+
+        let mut index = repo.index().unwrap();
+        index.add(&entry("a.rs", blob, 0)).unwrap();
+        // The base, ours and theirs.
+        for stage in 1..=3 {
+            index.add(&entry("b.rs", blob, stage)).unwrap();
+        }
+        index.write().unwrap();
+        assert_eq!(index.len(), 4);
+
+        assert_eq!(tracked_files(tree.path()).unwrap().files_count(), 2);
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_root() {
+        let native = |path: &str| path.replace('/', &path::MAIN_SEPARATOR.to_string());
+
+        assert_eq!(
+            relative_path("src/main.rs", ""),
+            Some(native("src/main.rs"))
+        );
+        assert_eq!(
+            relative_path("src/main.rs", "src/"),
+            Some(native("main.rs"))
+        );
+        assert_eq!(relative_path("srcs/main.rs", "src/"), None);
+        assert_eq!(relative_path("README.md", "src/"), None);
+    }
+}
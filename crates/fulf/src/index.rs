@@ -0,0 +1,603 @@
+//! The trigram index of the files, for the repeated searches in the same root.
+//!
+//! The index remembers every ASCII byte and every trigram of ASCII bytes
+//! of every file, case-insensitively, so the files, those can't have
+//! the matched lines, are left out of the search without reading them.
+//!
+//! The fuzzy needle is matched by the chars, those are not consecutive
+//! in the line, so only its bytes are looked up: the [`candidates`].
+//! The whole needle of the [`matcher::Substring`] is consecutive,
+//! so its trigrams are looked up too: the [`substring_candidates`].
+//!
+//! Both are not exact: the files with all the bytes or the trigrams
+//! of the needle are searched, even if those are on different lines.
+//!
+//! The index is built once by the [`build`], and then is kept up to date
+//! by the [`update`] of the changed files, e.g. on their save.
+//!
+//! The index could be [`save`]d and [`load`]ed on the next start.
+//! The file starts with the magic bytes and the version of the format,
+//! and ends with the checksum of everything between, so the file
+//! of another version, or the broken one, is never loaded:
+//! the [`load_or_build`] builds the index again instead.
+//!
+//! [`build`]: struct.Index.html#method.build
+//! [`update`]: struct.Index.html#method.update
+//! [`save`]: struct.Index.html#method.save
+//! [`load`]: struct.Index.html#method.load
+//! [`load_or_build`]: struct.Index.html#method.load_or_build
+//! [`candidates`]: struct.Index.html#method.candidates
+//! [`substring_candidates`]: struct.Index.html#method.substring_candidates
+//! [`matcher::Substring`]: ../matcher/struct.Substring.html
+
+use {
+    crate::{
+        filepath_cache::{
+            serialize_paths, serialize_relative, IndexedCache, InvalidCache, NotUtf8,
+            SerializeError,
+        },
+        Rules,
+    },
+    std::{
+        collections::HashMap,
+        convert::TryInto,
+        error::Error,
+        fmt,
+        fs::{self, File},
+        io::{self, BufWriter, Write},
+        path::{Path, PathBuf},
+    },
+};
+
+/// The first bytes of the index file.
+const MAGIC: &[u8; 8] = b"fulfidx\0";
+
+/// The version of the index file format, the files of other versions are not loaded.
+const FORMAT_VERSION: u32 = 1;
+
+/// The set of the lowercased ASCII bytes.
+type ByteSet = [u64; 2];
+
+/// The file could have any bytes: it wasn't read.
+const ANY_BYTES: ByteSet = [u64::MAX; 2];
+
+/// The index of the files of one root.
+///
+/// # Examples
+///
+/// ```no_run
+/// use {
+///     fulf::{fzy_search, index::Index, Rules},
+///     std::{path::Path, sync::Arc},
+/// };
+///
+/// let root = Path::new("monorepo");
+/// let mut index = Index::build(root, &Rules::new()).unwrap();
+///
+/// // Every search makes its own cache of the files, that could match.
+/// for needle in &["fn main", "impl Drop"] {
+///     let files = index.candidates(needle);
+///     let stats = fzy_search(root.into(), (*needle).into(), Arc::new(files), Rules::new(), |_| ());
+/// }
+///
+/// // The file was saved by the editor.
+/// index.update(&["src/main.rs"]);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Index {
+    root: PathBuf,
+    /// Relative to the root; the removed files keep their numbers with the empty paths.
+    paths: Vec<String>,
+    bytes: Vec<ByteSet>,
+    /// The sorted numbers of the files with the trigram.
+    postings: HashMap<u32, Vec<u32>>,
+    /// The sorted numbers of the files, those weren't read.
+    unread: Vec<u32>,
+}
+
+/// The error of the [`Index::build`].
+///
+/// [`Index::build`]: struct.Index.html#method.build
+#[derive(Debug)]
+pub enum IndexError {
+    /// One of the [`include_globs`] or of the [`exclude_globs`] is invalid.
+    ///
+    /// [`include_globs`]: ../struct.Rules.html#structfield.include_globs
+    /// [`exclude_globs`]: ../struct.Rules.html#structfield.exclude_globs
+    Glob(ignore::Error),
+    /// The root could not be walked.
+    Walk(SerializeError),
+    /// The cache of the root is already searched.
+    Cache(InvalidCache<()>),
+}
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexError::Glob(e) => write!(f, "invalid glob: {}", e),
+            IndexError::Walk(e) => write!(f, "can't walk the root: {:?}", e),
+            IndexError::Cache(_) => f.write_str("invalid cache"),
+        }
+    }
+}
+
+impl Error for IndexError {}
+
+impl Index {
+    /// Walks the root by the rules, like the search would, and reads every file.
+    ///
+    /// It takes as long as the search of every file, so it's better
+    /// done in the background, while the searches go without the index.
+    pub fn build(root: &Path, rules: &Rules) -> Result<Self, IndexError> {
+        let builder = rules.walk_builder(root).map_err(IndexError::Glob)?;
+        let cache = serialize_relative(
+            root,
+            builder,
+            rules.cache_options(),
+            NotUtf8::IgnorePath,
+            |_| (),
+        )
+        .map_err(IndexError::Walk)?;
+        Self::from_cache(root, &cache).map_err(IndexError::Cache)
+    }
+
+    /// Loads the index, saved by the [`save`], or builds it again by the [`build`]
+    /// and saves it, if it couldn't be loaded. The failed save is ignored:
+    /// the next start just builds the index again.
+    ///
+    /// [`save`]: #method.save
+    /// [`build`]: #method.build
+    pub fn load_or_build(
+        root: &Path,
+        rules: &Rules,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, IndexError> {
+        let path = path.as_ref();
+        match Self::load(root, path) {
+            Ok(index) => Ok(index),
+            Err(_) => {
+                let index = Self::build(root, rules)?;
+                let _any_result = index.save(path);
+                Ok(index)
+            }
+        }
+    }
+
+    /// Writes the index to the file, replacing the old one.
+    ///
+    /// The file is written next to the old one and then renamed,
+    /// so the interrupted save doesn't break the old index.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut body = Vec::new();
+        self.encode(&mut body);
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&body)?;
+        writer.write_all(&checksum(&body).to_le_bytes())?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(tmp, path)
+    }
+
+    /// Reads the index of the root, saved by the [`save`].
+    ///
+    /// Returns the `InvalidData` error for the file of another format version,
+    /// or with the wrong checksum.
+    ///
+    /// [`save`]: #method.save
+    pub fn load(root: &Path, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::read(path)?;
+        let header = MAGIC.len() + 4;
+        if file.len() < header + 8 || !file.starts_with(MAGIC) {
+            return Err(invalid("not an index file"));
+        }
+        let version = u32::from_le_bytes(file[MAGIC.len()..header].try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(invalid("unknown version of the index file"));
+        }
+        let (body, sum) = file[header..].split_at(file.len() - header - 8);
+        if checksum(body) != u64::from_le_bytes(sum.try_into().unwrap()) {
+            return Err(invalid("broken index file"));
+        }
+
+        let mut index =
+            Self::decode(&mut Reader(body)).ok_or_else(|| invalid("broken index file"))?;
+        index.root = root.into();
+        Ok(index)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_u32(out, self.paths.len() as u32);
+        for (path, bytes) in self.paths.iter().zip(&self.bytes) {
+            write_u32(out, path.len() as u32);
+            out.extend_from_slice(path.as_bytes());
+            out.extend_from_slice(&bytes[0].to_le_bytes());
+            out.extend_from_slice(&bytes[1].to_le_bytes());
+        }
+        write_files(out, &self.unread);
+        write_u32(out, self.postings.len() as u32);
+        for (&trigram, files) in &self.postings {
+            write_u32(out, trigram);
+            write_files(out, files);
+        }
+    }
+
+    /// `None` if the index is broken: the checksum can't catch everything.
+    fn decode(r: &mut Reader) -> Option<Self> {
+        let files_count = r.u32()? as usize;
+        let mut index = Self::default();
+        for _ in 0..files_count {
+            let len = r.u32()? as usize;
+            let path = std::str::from_utf8(r.bytes(len)?).ok()?;
+            index.paths.push(path.into());
+            index.bytes.push([r.u64()?, r.u64()?]);
+        }
+        index.unread = r.files(files_count)?;
+        for _ in 0..r.u32()? {
+            let trigram = r.u32()?;
+            index.postings.insert(trigram, r.files(files_count)?);
+        }
+        if r.0.is_empty() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Reads every file of the cache. The cache is used up, like by the search.
+    ///
+    /// The files, those can't be read, are kept in the index
+    /// and are the candidates for every needle.
+    /// So are the `.gz` files: the search unpacks them, but the index doesn't.
+    pub fn from_cache(root: &Path, cache: &IndexedCache) -> Result<Self, InvalidCache<()>> {
+        let relative = cache.is_relative();
+        let mut index = Self {
+            root: root.into(),
+            ..Self::default()
+        };
+
+        let mut files = cache.stream_iter()?;
+        while let Some(filepath) = files.read_next()? {
+            let path = if relative {
+                filepath
+            } else {
+                Path::new(filepath)
+                    .strip_prefix(root)
+                    .ok()
+                    .and_then(Path::to_str)
+                    .unwrap_or(filepath)
+            };
+            index.push(path);
+        }
+
+        Ok(index)
+    }
+
+    /// The number of the files in the index.
+    #[inline]
+    pub fn files_count(&self) -> usize {
+        self.paths.iter().filter(|path| !path.is_empty()).count()
+    }
+
+    /// Reads the changed files again: the new ones are added to the index,
+    /// and the ones, those don't exist anymore, are removed from it.
+    ///
+    /// The paths are either relative to the root, or start with it.
+    pub fn update<P: AsRef<Path>>(&mut self, paths: impl IntoIterator<Item = P>) {
+        let mut changed: Vec<u32> = Vec::new();
+        let mut added: Vec<String> = Vec::new();
+        for path in paths {
+            let path = path.as_ref();
+            let path = path.strip_prefix(&self.root).unwrap_or(path);
+            let path = match path.to_str() {
+                Some(path) if !path.is_empty() => path,
+                _ => continue,
+            };
+            match self.paths.iter().position(|known| known == path) {
+                Some(n) => changed.push(n as u32),
+                None => added.push(path.into()),
+            }
+        }
+
+        changed.sort_unstable();
+        changed.dedup();
+        let was_changed = |n: &u32| changed.binary_search(n).is_ok();
+        self.postings.retain(|_, files| {
+            files.retain(|n| !was_changed(n));
+            !files.is_empty()
+        });
+        self.unread.retain(|n| !was_changed(n));
+
+        for &n in &changed {
+            if self.root.join(&self.paths[n as usize]).is_file() {
+                self.read(n);
+            } else {
+                self.paths[n as usize].clear();
+                self.bytes[n as usize] = [0; 2];
+            }
+        }
+        for path in added {
+            if self.root.join(&path).is_file() && !self.paths.contains(&path) {
+                self.push(&path);
+            }
+        }
+    }
+
+    /// The files, those have every ASCII byte of the fuzzy needle.
+    ///
+    /// The paths of the cache are relative to the root of the index.
+    pub fn candidates(&self, needle: &str) -> IndexedCache {
+        let needle = byte_set(needle.as_bytes());
+        let paths = self
+            .bytes
+            .iter()
+            .zip(&self.paths)
+            .filter(|(bytes, path)| contains(bytes, &needle) && !path.is_empty())
+            .map(|(_, path)| path.as_str());
+        serialize_paths(paths)
+    }
+
+    /// The files, those have every ASCII trigram of the needle,
+    /// that is searched as a whole, like by the [`matcher::Substring`].
+    ///
+    /// The paths of the cache are relative to the root of the index.
+    ///
+    /// [`matcher::Substring`]: ../matcher/struct.Substring.html
+    pub fn substring_candidates(&self, needle: &str) -> IndexedCache {
+        let mut trigrams = trigrams(needle.as_bytes());
+        if trigrams.is_empty() {
+            return self.candidates(needle);
+        }
+        // The rarest trigrams go first, so the intersection shrinks fast.
+        trigrams.sort_by_key(|t| self.postings.get(t).map_or(0, Vec::len));
+
+        let mut files: Vec<u32> = self.postings.get(&trigrams[0]).cloned().unwrap_or_default();
+        for trigram in &trigrams[1..] {
+            if files.is_empty() {
+                break;
+            }
+            let posting = self.postings.get(trigram).map_or(&[][..], Vec::as_slice);
+            files.retain(|n| posting.binary_search(n).is_ok());
+        }
+        files.extend_from_slice(&self.unread);
+        files.sort_unstable();
+
+        serialize_paths(files.iter().map(|&n| self.paths[n as usize].as_str()))
+    }
+
+    fn push(&mut self, path: &str) {
+        let n = self.paths.len() as u32;
+        self.paths.push(path.into());
+        self.bytes.push(ANY_BYTES);
+        self.read(n);
+    }
+
+    /// Indexes the file, that isn't in any posting yet.
+    fn read(&mut self, n: u32) {
+        let path = &self.paths[n as usize];
+        let text = if path.ends_with(".gz") {
+            None
+        } else {
+            fs::read(self.root.join(path)).ok()
+        };
+        match text {
+            Some(text) => {
+                self.bytes[n as usize] = byte_set(&text);
+                for trigram in trigrams(&text) {
+                    insert_sorted(self.postings.entry(trigram).or_default(), n);
+                }
+            }
+            None => {
+                self.bytes[n as usize] = ANY_BYTES;
+                insert_sorted(&mut self.unread, n);
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The FNV-1a hash: the `DefaultHasher` may change between the Rust versions.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[inline]
+fn write_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend_from_slice(&x.to_le_bytes());
+}
+
+fn write_files(out: &mut Vec<u8>, files: &[u32]) {
+    write_u32(out, files.len() as u32);
+    files.iter().for_each(|&n| write_u32(out, n));
+}
+
+/// The rest of the index file.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    /// The sorted numbers of the files, every one is less than the `files_count`.
+    fn files(&mut self, files_count: usize) -> Option<Vec<u32>> {
+        let len = self.u32()? as usize;
+        let mut files = Vec::with_capacity(len.min(self.0.len() / 4));
+        for _ in 0..len {
+            let n = self.u32()?;
+            if n as usize >= files_count || files.last().map_or(false, |&last| last >= n) {
+                return None;
+            }
+            files.push(n);
+        }
+        Some(files)
+    }
+}
+
+/// The lowercased ASCII bytes of the text, except the whitespaces:
+/// those could be ignored by the search.
+fn byte_set(text: &[u8]) -> ByteSet {
+    let mut set = [0; 2];
+    for &b in text {
+        if b.is_ascii() && !b.is_ascii_whitespace() {
+            let b = b.to_ascii_lowercase();
+            set[usize::from(b >> 6)] |= 1 << (b & 63);
+        }
+    }
+    set
+}
+
+/// The numbers of the files are mostly pushed in their order,
+/// only the updated files go into the middle.
+#[inline]
+fn insert_sorted(files: &mut Vec<u32>, n: u32) {
+    match files.last() {
+        Some(&last) if last > n => {
+            if let Err(i) = files.binary_search(&n) {
+                files.insert(i, n);
+            }
+        }
+        _ => files.push(n),
+    }
+}
+
+#[inline]
+fn contains(set: &ByteSet, subset: &ByteSet) -> bool {
+    set[0] & subset[0] == subset[0] && set[1] & subset[1] == subset[1]
+}
+
+/// The sorted unique trigrams of the lowercased ASCII bytes,
+/// except the ones with the whitespaces.
+fn trigrams(text: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = text
+        .windows(3)
+        .filter(|w| w.iter().all(|b| b.is_ascii() && !b.is_ascii_whitespace()))
+        .map(|w| {
+            w.iter()
+                .fold(0, |t, b| t << 8 | u32::from(b.to_ascii_lowercase()))
+        })
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::temp_tree::TempTree};
+
+    fn paths(cache: IndexedCache) -> Vec<String> {
+        let mut paths = Vec::new();
+        let mut files = cache.stream_iter().unwrap();
+        while let Some(path) = files.read_next().unwrap() {
+            paths.push(path.replace('\\', "/"));
+        }
+        paths
+    }
+
+    #[test]
+    fn files_are_prefiltered() {
+        let root = TempTree::new("index");
+        root.write("src/main.rs", "fn Main() {}\n")
+            .write("src/lib.rs", "pub mod x;\n")
+            .write("notes.md", "the main\nfunction\n");
+        let cache = serialize_paths(vec!["src/main.rs", "src/lib.rs", "notes.md", "gone.txt"]);
+        let index = Index::from_cache(root.path(), &cache).unwrap();
+
+        assert_eq!(index.files_count(), 4);
+        assert_eq!(
+            paths(index.candidates("fnmain")),
+            ["src/main.rs", "notes.md", "gone.txt"]
+        );
+        assert_eq!(
+            paths(index.substring_candidates("MAIN(")),
+            ["src/main.rs", "gone.txt"]
+        );
+        assert_eq!(
+            paths(index.substring_candidates("n f")),
+            ["src/main.rs", "notes.md", "gone.txt"]
+        );
+        assert_eq!(paths(index.substring_candidates("zzz")), ["gone.txt"]);
+    }
+
+    #[test]
+    fn index_is_updated() {
+        let root = TempTree::new("index-update");
+        let mut rules = Rules::new();
+        rules.git_ignore = false;
+
+        root.write("a.rs", "fn alpha() {}\n")
+            .write("b.rs", "fn beta() {}\n");
+        let mut index = Index::build(root.path(), &rules).unwrap();
+        assert!(paths(index.substring_candidates("gamma")).is_empty());
+
+        root.write("a.rs", "fn gamma() {}\n");
+        fs::remove_file(root.join("b.rs")).unwrap();
+        root.write("c.rs", "fn delta() {}\n");
+        index.update(&[root.join("a.rs"), root.join("b.rs"), "c.rs".into()]);
+
+        assert_eq!(index.files_count(), 2);
+        assert_eq!(paths(index.substring_candidates("gamma")), ["a.rs"]);
+        assert!(paths(index.substring_candidates("alpha")).is_empty());
+        assert!(paths(index.substring_candidates("beta")).is_empty());
+        assert_eq!(paths(index.candidates("delta")), ["c.rs"]);
+    }
+
+    #[test]
+    fn index_is_saved_and_loaded() {
+        let tree = TempTree::new("index-file");
+        // The index file is next to the root, so it's not indexed itself.
+        tree.write("root/a.rs", "fn alpha() {}\n");
+        let (root, file) = (tree.join("root"), tree.join("root.idx"));
+        let mut rules = Rules::new();
+        rules.git_ignore = false;
+
+        let built = Index::load_or_build(&root, &rules, &file).unwrap();
+        let loaded = Index::load(&root, &file).unwrap();
+
+        let mut broken = fs::read(&file).unwrap();
+        let last = broken.len() - 9;
+        broken[last] ^= 1;
+        fs::write(&file, &broken).unwrap();
+        let broken = Index::load(&root, &file).map(|_| ());
+
+        let mut old = fs::read(&file).unwrap();
+        old[8] = 0;
+        fs::write(&file, &old).unwrap();
+        let old = Index::load(&root, &file).map(|_| ());
+        let rebuilt = Index::load_or_build(&root, &rules, &file).unwrap();
+
+        assert_eq!(loaded.paths, built.paths);
+        assert_eq!(loaded.bytes, built.bytes);
+        assert_eq!(loaded.postings, built.postings);
+        assert_eq!(paths(loaded.substring_candidates("alpha")), ["a.rs"]);
+        assert_eq!(broken.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(old.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(rebuilt.files_count(), 1);
+    }
+}
@@ -0,0 +1,658 @@
+use {
+    super::{CancelCheck, CancelToken, FileOrder, PositionUnit, Rules, SearchMode},
+    std::{borrow::Cow, error::Error, fmt, time::Duration},
+};
+
+/// The builder of the [`Rules`], that checks the values
+/// before the search is started with them.
+///
+/// Every field, that is not set, keeps its default value.
+///
+/// # Examples
+///
+/// ```
+/// let rules = fulf::Rules::builder()
+///     .results_cap(512)
+///     .threads(8)
+///     .build()
+///     .unwrap();
+/// assert_eq!(rules.threads.get(), 8);
+///
+/// assert!(fulf::Rules::builder().results_cap(0).build().is_err());
+/// ```
+///
+/// [`Rules`]: struct.Rules.html
+#[derive(Debug, Clone)]
+pub struct RulesBuilder {
+    rules: Rules,
+    threads: usize,
+}
+
+impl Rules {
+    /// Starts the builder from the default rules.
+    #[inline]
+    pub fn builder() -> RulesBuilder {
+        Rules::new().into()
+    }
+
+    /// Checks that the needle is not empty and not longer than the [`max_line_len`]:
+    /// such needle can't match any searched line.
+    ///
+    /// [`max_line_len`]: #structfield.max_line_len
+    ///
+    /// The needle is checked after the [`normalize_needle`], and without
+    /// the whitespaces with the [`ignore_whitespace`].
+    ///
+    /// [`normalize_needle`]: #method.normalize_needle
+    /// [`ignore_whitespace`]: #structfield.ignore_whitespace
+    pub fn check_needle(&self, needle: &str) -> Result<(), InvalidRules> {
+        let needle = self.normalize_needle(needle);
+        let len = if self.ignore_whitespace {
+            needle.split_whitespace().map(str::len).sum()
+        } else {
+            needle.len()
+        };
+        if len == 0 || len > self.max_line_len {
+            Err(InvalidRules::WrongSizeNeedle(len))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The needle, that is searched: trimmed with the [`trim_needle`],
+    /// and with every whitespace run turned into one space
+    /// with the [`collapse_needle_spaces`].
+    ///
+    /// ```
+    /// let rules = fulf::Rules::builder()
+    ///     .trim_needle(true)
+    ///     .collapse_needle_spaces(true)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(rules.normalize_needle(" fn \t main( ) "), "fn main( )");
+    /// ```
+    ///
+    /// [`trim_needle`]: #structfield.trim_needle
+    /// [`collapse_needle_spaces`]: #structfield.collapse_needle_spaces
+    pub fn normalize_needle<'n>(&self, needle: &'n str) -> Cow<'n, str> {
+        let needle = if self.trim_needle {
+            needle.trim()
+        } else {
+            needle
+        };
+        if !self.collapse_needle_spaces {
+            return Cow::Borrowed(needle);
+        }
+
+        let mut collapsed = String::with_capacity(needle.len());
+        let mut after_space = false;
+        for c in needle.chars() {
+            if !c.is_whitespace() {
+                collapsed.push(c);
+                after_space = false;
+            } else if !after_space {
+                collapsed.push(' ');
+                after_space = true;
+            }
+        }
+
+        if collapsed == needle {
+            Cow::Borrowed(needle)
+        } else {
+            Cow::Owned(collapsed)
+        }
+    }
+
+    /// Checks the values, just like the [`RulesBuilder::build`].
+    ///
+    /// Useful for the rules, that were deserialized or changed by hand.
+    ///
+    /// [`RulesBuilder::build`]: struct.RulesBuilder.html#method.build
+    pub fn validate(&self) -> Result<(), InvalidRules> {
+        if self.results_cap == 0 {
+            Err(InvalidRules::ZeroResultsCap)
+        } else if self.thread_local_results_cap == 0 {
+            Err(InvalidRules::ZeroBatchSize)
+        } else if self.flush_lines == 0 {
+            Err(InvalidRules::ZeroFlushLines)
+        } else if self.channel_capacity == Some(0) {
+            Err(InvalidRules::ZeroChannelCapacity)
+        } else if self.max_line_len == 0 {
+            Err(InvalidRules::ZeroMaxLineLen)
+        } else if self.max_memory_bytes == Some(0) {
+            Err(InvalidRules::ZeroMemoryBudget)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Starts the builder from the given rules, e.g. the ones from the config.
+impl From<Rules> for RulesBuilder {
+    #[inline]
+    fn from(rules: Rules) -> Self {
+        RulesBuilder {
+            threads: rules.threads_count(),
+            rules,
+        }
+    }
+}
+
+impl RulesBuilder {
+    /// See [`Rules::thread_local_results_cap`].
+    ///
+    /// [`Rules::thread_local_results_cap`]: struct.Rules.html#structfield.thread_local_results_cap
+    #[inline]
+    pub fn thread_local_results_cap(mut self, cap: usize) -> Self {
+        self.rules.thread_local_results_cap = cap;
+        self
+    }
+
+    /// See [`Rules::channel_capacity`].
+    ///
+    /// [`Rules::channel_capacity`]: struct.Rules.html#structfield.channel_capacity
+    #[inline]
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.rules.channel_capacity = Some(capacity);
+        self
+    }
+
+    /// See [`Rules::flush_lines`].
+    ///
+    /// [`Rules::flush_lines`]: struct.Rules.html#structfield.flush_lines
+    #[inline]
+    pub fn flush_lines(mut self, lines: usize) -> Self {
+        self.rules.flush_lines = lines;
+        self
+    }
+
+    /// See [`Rules::flush_interval`].
+    ///
+    /// [`Rules::flush_interval`]: struct.Rules.html#structfield.flush_interval
+    #[inline]
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.rules.flush_interval = Some(interval);
+        self
+    }
+
+    /// See [`Rules::send_timeout`].
+    ///
+    /// [`Rules::send_timeout`]: struct.Rules.html#structfield.send_timeout
+    #[inline]
+    pub fn send_timeout(mut self, timeout: Duration) -> Self {
+        self.rules.send_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`Rules::drop_lowest_scores`].
+    ///
+    /// [`Rules::drop_lowest_scores`]: struct.Rules.html#structfield.drop_lowest_scores
+    #[inline]
+    pub fn drop_lowest_scores(mut self, yes: bool) -> Self {
+        self.rules.drop_lowest_scores = yes;
+        self
+    }
+
+    /// See [`Rules::threads`].
+    ///
+    /// Unlike the [`Rules::set_threads`], 0 is an error here.
+    ///
+    /// [`Rules::threads`]: struct.Rules.html#structfield.threads
+    /// [`Rules::set_threads`]: struct.Rules.html#method.set_threads
+    #[inline]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// See [`Rules::worker_stack_size`].
+    ///
+    /// [`Rules::worker_stack_size`]: struct.Rules.html#structfield.worker_stack_size
+    #[inline]
+    pub fn worker_stack_size(mut self, size: usize) -> Self {
+        self.rules.worker_stack_size = Some(size);
+        self
+    }
+
+    /// See [`Rules::low_priority`].
+    ///
+    /// [`Rules::low_priority`]: struct.Rules.html#structfield.low_priority
+    #[inline]
+    pub fn low_priority(mut self, yes: bool) -> Self {
+        self.rules.low_priority = yes;
+        self
+    }
+
+    /// See [`Rules::max_memory_bytes`].
+    ///
+    /// [`Rules::max_memory_bytes`]: struct.Rules.html#structfield.max_memory_bytes
+    #[inline]
+    pub fn max_memory_bytes(mut self, bytes: usize) -> Self {
+        self.rules.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// See [`Rules::max_line_len`].
+    ///
+    /// [`Rules::max_line_len`]: struct.Rules.html#structfield.max_line_len
+    #[inline]
+    pub fn max_line_len(mut self, max_line_len: usize) -> Self {
+        self.rules.max_line_len = max_line_len;
+        self
+    }
+
+    /// See [`Rules::truncate_long_lines`].
+    ///
+    /// [`Rules::truncate_long_lines`]: struct.Rules.html#structfield.truncate_long_lines
+    #[inline]
+    pub fn truncate_long_lines(mut self, yes: bool) -> Self {
+        self.rules.truncate_long_lines = yes;
+        self
+    }
+
+    /// See [`Rules::chunk_long_lines`].
+    ///
+    /// [`Rules::chunk_long_lines`]: struct.Rules.html#structfield.chunk_long_lines
+    #[inline]
+    pub fn chunk_long_lines(mut self, yes: bool) -> Self {
+        self.rules.chunk_long_lines = yes;
+        self
+    }
+
+    /// See [`Rules::skip_minified`].
+    ///
+    /// [`Rules::skip_minified`]: struct.Rules.html#structfield.skip_minified
+    #[inline]
+    pub fn skip_minified(mut self, yes: bool) -> Self {
+        self.rules.skip_minified = yes;
+        self
+    }
+
+    /// See [`Rules::results_cap`].
+    ///
+    /// [`Rules::results_cap`]: struct.Rules.html#structfield.results_cap
+    #[inline]
+    pub fn results_cap(mut self, cap: usize) -> Self {
+        self.rules.results_cap = cap;
+        self
+    }
+
+    /// See [`Rules::max_results_per_dir`].
+    ///
+    /// [`Rules::max_results_per_dir`]: struct.Rules.html#structfield.max_results_per_dir
+    #[inline]
+    pub fn max_results_per_dir(mut self, max: usize) -> Self {
+        self.rules.max_results_per_dir = Some(max);
+        self
+    }
+
+    /// See [`Rules::results_dir_depth`].
+    ///
+    /// [`Rules::results_dir_depth`]: struct.Rules.html#structfield.results_dir_depth
+    #[inline]
+    pub fn results_dir_depth(mut self, depth: usize) -> Self {
+        self.rules.results_dir_depth = depth;
+        self
+    }
+
+    /// See [`Rules::deadline`].
+    ///
+    /// [`Rules::deadline`]: struct.Rules.html#structfield.deadline
+    #[inline]
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.rules.deadline = Some(deadline);
+        self
+    }
+
+    /// Adds the glob to the [`Rules::include_globs`].
+    ///
+    /// [`Rules::include_globs`]: struct.Rules.html#structfield.include_globs
+    #[inline]
+    pub fn include_glob(mut self, glob: impl Into<String>) -> Self {
+        self.rules.include_globs.push(glob.into());
+        self
+    }
+
+    /// Adds the glob to the [`Rules::exclude_globs`].
+    ///
+    /// [`Rules::exclude_globs`]: struct.Rules.html#structfield.exclude_globs
+    #[inline]
+    pub fn exclude_glob(mut self, glob: impl Into<String>) -> Self {
+        self.rules.exclude_globs.push(glob.into());
+        self
+    }
+
+    /// See [`Rules::max_depth`].
+    ///
+    /// [`Rules::max_depth`]: struct.Rules.html#structfield.max_depth
+    #[inline]
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.rules.max_depth = Some(depth);
+        self
+    }
+
+    /// See [`Rules::follow_links`].
+    ///
+    /// [`Rules::follow_links`]: struct.Rules.html#structfield.follow_links
+    #[inline]
+    pub fn follow_links(mut self, yes: bool) -> Self {
+        self.rules.follow_links = yes;
+        self
+    }
+
+    /// See [`Rules::sorted_walk`].
+    ///
+    /// [`Rules::sorted_walk`]: struct.Rules.html#structfield.sorted_walk
+    #[inline]
+    pub fn sorted_walk(mut self, yes: bool) -> Self {
+        self.rules.sorted_walk = yes;
+        self
+    }
+
+    /// See [`Rules::file_order`].
+    ///
+    /// [`Rules::file_order`]: struct.Rules.html#structfield.file_order
+    #[inline]
+    pub fn file_order(mut self, order: FileOrder) -> Self {
+        self.rules.file_order = order;
+        self
+    }
+
+    /// See [`Rules::dedupe_links`].
+    ///
+    /// [`Rules::dedupe_links`]: struct.Rules.html#structfield.dedupe_links
+    #[inline]
+    pub fn dedupe_links(mut self, yes: bool) -> Self {
+        self.rules.dedupe_links = yes;
+        self
+    }
+
+    /// See [`Rules::same_file_system`].
+    ///
+    /// [`Rules::same_file_system`]: struct.Rules.html#structfield.same_file_system
+    #[inline]
+    pub fn same_file_system(mut self, yes: bool) -> Self {
+        self.rules.same_file_system = yes;
+        self
+    }
+
+    /// See [`Rules::search_hidden`].
+    ///
+    /// [`Rules::search_hidden`]: struct.Rules.html#structfield.search_hidden
+    #[inline]
+    pub fn search_hidden(mut self, yes: bool) -> Self {
+        self.rules.search_hidden = yes;
+        self
+    }
+
+    /// See [`Rules::git_ignore`].
+    ///
+    /// [`Rules::git_ignore`]: struct.Rules.html#structfield.git_ignore
+    #[inline]
+    pub fn git_ignore(mut self, yes: bool) -> Self {
+        self.rules.git_ignore = yes;
+        self
+    }
+
+    /// See [`Rules::dot_ignore`].
+    ///
+    /// [`Rules::dot_ignore`]: struct.Rules.html#structfield.dot_ignore
+    #[inline]
+    pub fn dot_ignore(mut self, yes: bool) -> Self {
+        self.rules.dot_ignore = yes;
+        self
+    }
+
+    /// Adds the name to the [`Rules::custom_ignore_filenames`].
+    ///
+    /// [`Rules::custom_ignore_filenames`]: struct.Rules.html#structfield.custom_ignore_filenames
+    #[inline]
+    pub fn custom_ignore_filename(mut self, name: impl Into<String>) -> Self {
+        self.rules.custom_ignore_filenames.push(name.into());
+        self
+    }
+
+    /// See [`Rules::parent_ignores`].
+    ///
+    /// [`Rules::parent_ignores`]: struct.Rules.html#structfield.parent_ignores
+    #[inline]
+    pub fn parent_ignores(mut self, yes: bool) -> Self {
+        self.rules.parent_ignores = yes;
+        self
+    }
+
+    /// Disables all the ignore files, like the `rg --no-ignore`.
+    #[inline]
+    pub fn no_ignore(self) -> Self {
+        self.git_ignore(false)
+            .dot_ignore(false)
+            .parent_ignores(false)
+    }
+
+    /// See [`Rules::absolute_paths`].
+    ///
+    /// [`Rules::absolute_paths`]: struct.Rules.html#structfield.absolute_paths
+    #[inline]
+    pub fn absolute_paths(mut self, yes: bool) -> Self {
+        self.rules.absolute_paths = yes;
+        self
+    }
+
+    /// See [`Rules::forward_slashes`].
+    ///
+    /// [`Rules::forward_slashes`]: struct.Rules.html#structfield.forward_slashes
+    #[inline]
+    pub fn forward_slashes(mut self, yes: bool) -> Self {
+        self.rules.forward_slashes = yes;
+        self
+    }
+
+    /// See [`Rules::position_unit`].
+    ///
+    /// [`Rules::position_unit`]: struct.Rules.html#structfield.position_unit
+    #[inline]
+    pub fn position_unit(mut self, unit: PositionUnit) -> Self {
+        self.rules.position_unit = unit;
+        self
+    }
+
+    /// See [`Rules::search_mode`].
+    ///
+    /// [`Rules::search_mode`]: struct.Rules.html#structfield.search_mode
+    #[inline]
+    pub fn search_mode(mut self, mode: SearchMode) -> Self {
+        self.rules.search_mode = mode;
+        self
+    }
+
+    /// See [`Rules::trim_needle`].
+    ///
+    /// [`Rules::trim_needle`]: struct.Rules.html#structfield.trim_needle
+    #[inline]
+    pub fn trim_needle(mut self, yes: bool) -> Self {
+        self.rules.trim_needle = yes;
+        self
+    }
+
+    /// See [`Rules::collapse_needle_spaces`].
+    ///
+    /// [`Rules::collapse_needle_spaces`]: struct.Rules.html#structfield.collapse_needle_spaces
+    #[inline]
+    pub fn collapse_needle_spaces(mut self, yes: bool) -> Self {
+        self.rules.collapse_needle_spaces = yes;
+        self
+    }
+
+    /// See [`Rules::ignore_whitespace`].
+    ///
+    /// [`Rules::ignore_whitespace`]: struct.Rules.html#structfield.ignore_whitespace
+    #[inline]
+    pub fn ignore_whitespace(mut self, yes: bool) -> Self {
+        self.rules.ignore_whitespace = yes;
+        self
+    }
+
+    /// See [`Rules::invert_match`].
+    ///
+    /// [`Rules::invert_match`]: struct.Rules.html#structfield.invert_match
+    #[inline]
+    pub fn invert_match(mut self, yes: bool) -> Self {
+        self.rules.invert_match = yes;
+        self
+    }
+
+    /// See [`Rules::cancel`].
+    ///
+    /// [`Rules::cancel`]: struct.Rules.html#structfield.cancel
+    #[inline]
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.rules.cancel = cancel;
+        self
+    }
+
+    /// See [`Rules::cancel_check`].
+    ///
+    /// [`Rules::cancel_check`]: struct.Rules.html#structfield.cancel_check
+    #[inline]
+    pub fn cancel_check(mut self, check: CancelCheck) -> Self {
+        self.rules.cancel_check = check;
+        self
+    }
+
+    /// See [`Rules::stop_on_best_matches`].
+    ///
+    /// [`Rules::stop_on_best_matches`]: struct.Rules.html#structfield.stop_on_best_matches
+    #[inline]
+    pub fn stop_on_best_matches(mut self, yes: bool) -> Self {
+        self.rules.stop_on_best_matches = yes;
+        self
+    }
+
+    /// Checks the values and returns the rules.
+    ///
+    /// Returns the first found error.
+    pub fn build(self) -> Result<Rules, InvalidRules> {
+        let RulesBuilder { mut rules, threads } = self;
+
+        rules.validate()?;
+        if threads == 0 {
+            return Err(InvalidRules::ZeroThreads);
+        }
+        rules.set_threads(threads);
+
+        Ok(rules)
+    }
+}
+
+/// The error, indicating the value, that makes no sense for the search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidRules {
+    /// Nothing could be kept by the sorted search.
+    ZeroResultsCap,
+    /// The workers can't send any result.
+    ZeroBatchSize,
+    /// The workers would flush the batch after every line.
+    ZeroFlushLines,
+    /// The async channel can't have zero capacity.
+    ZeroChannelCapacity,
+    /// At least one thread is needed for the search.
+    ZeroThreads,
+    /// No line could be searched.
+    ZeroMaxLineLen,
+    /// No file fits the memory budget, so every one would be skipped.
+    ZeroMemoryBudget,
+    /// The needle is empty or longer than the `max_line_len`,
+    /// see the [`Rules::check_needle`].
+    ///
+    /// [`Rules::check_needle`]: struct.Rules.html#method.check_needle
+    WrongSizeNeedle(usize),
+}
+
+impl fmt::Display for InvalidRules {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = match self {
+            InvalidRules::ZeroResultsCap => "results_cap",
+            InvalidRules::ZeroBatchSize => "thread_local_results_cap",
+            InvalidRules::ZeroFlushLines => "flush_lines",
+            InvalidRules::ZeroChannelCapacity => "channel_capacity",
+            InvalidRules::ZeroThreads => "threads",
+            InvalidRules::ZeroMaxLineLen => "max_line_len",
+            InvalidRules::ZeroMemoryBudget => "max_memory_bytes",
+            InvalidRules::WrongSizeNeedle(len) => {
+                return write!(f, "wrong needle length: {}", len);
+            }
+        };
+        write!(f, "`{}` can't be 0", field)
+    }
+}
+
+impl Error for InvalidRules {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_error_is_returned() {
+        let err = Rules::builder()
+            .threads(0)
+            .results_cap(0)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, InvalidRules::ZeroResultsCap);
+        assert_eq!(err.to_string(), "`results_cap` can't be 0");
+    }
+
+    #[test]
+    fn zero_memory_budget_is_rejected() {
+        let err = Rules::builder().max_memory_bytes(0).build().unwrap_err();
+        assert_eq!(err, InvalidRules::ZeroMemoryBudget);
+        assert_eq!(err.to_string(), "`max_memory_bytes` can't be 0");
+        assert!(Rules::builder().max_memory_bytes(1).build().is_ok());
+    }
+
+    #[test]
+    fn needle_fits_the_line() {
+        let rules = Rules::builder().max_line_len(4).build().unwrap();
+        assert!(rules.check_needle("four").is_ok());
+        assert_eq!(
+            rules.check_needle("fives"),
+            Err(InvalidRules::WrongSizeNeedle(5))
+        );
+        assert_eq!(
+            rules.check_needle(""),
+            Err(InvalidRules::WrongSizeNeedle(0))
+        );
+    }
+
+    #[test]
+    fn needle_is_checked_normalized() {
+        let rules = Rules::builder().trim_needle(true).build().unwrap();
+        assert_eq!(
+            rules.check_needle("  "),
+            Err(InvalidRules::WrongSizeNeedle(0))
+        );
+        assert_eq!(rules.normalize_needle(" a  b "), "a  b");
+
+        // The whitespaces are not searched at all.
+        let rules = Rules::builder().ignore_whitespace(true).build().unwrap();
+        assert_eq!(
+            rules.check_needle(" \t "),
+            Err(InvalidRules::WrongSizeNeedle(0))
+        );
+        assert!(rules.check_needle(" a ").is_ok());
+        assert!(matches!(
+            rules.normalize_needle("a b"),
+            Cow::Borrowed("a b")
+        ));
+    }
+
+    #[test]
+    fn unset_fields_keep_defaults() {
+        let rules = Rules::builder().flush_lines(1).build().unwrap();
+        let default = Rules::new();
+        assert_eq!(rules.flush_lines, 1);
+        assert_eq!(rules.results_cap, default.results_cap);
+        assert_eq!(rules.threads, default.threads);
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// How often the workers of the [`spawner`] check the [`CancelToken`]
+/// and the deadline.
+///
+/// The coarse checks are cheaper, but the cancel takes longer to land.
+/// The [`rayon_spawner`] and the [`search_stream`] check before every file.
+///
+/// [`spawner`]: struct.SpecializedAscii.html#method.spawner
+/// [`rayon_spawner`]: struct.SpecializedAscii.html#method.rayon_spawner
+/// [`search_stream`]: struct.SpecializedAscii.html#method.search_stream
+/// [`CancelToken`]: struct.CancelToken.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum CancelCheck {
+    /// Before every file: the big file is read to the end, once started.
+    Files,
+    /// Before every file, and after every given number of lines of the file;
+    /// 0 is the same as 1.
+    Lines(usize),
+    /// After every batch of the results, that the worker sends,
+    /// and after every [`flush_lines`] lines, but not before every file:
+    /// the cheapest check for the thousands of the tiny files.
+    ///
+    /// [`flush_lines`]: struct.Rules.html#structfield.flush_lines
+    Batches,
+}
+
+impl Default for CancelCheck {
+    #[inline]
+    fn default() -> Self {
+        CancelCheck::Files
+    }
+}
+
+/// A shared flag to stop the running search.
+///
+/// All clones of the token share the same flag,
+/// so the search could be cancelled from any thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks the worker threads to stop.
+    ///
+    /// The workers will stop at the next check, see the [`CancelCheck`],
+    /// and the search will be marked as truncated.
+    ///
+    /// [`CancelCheck`]: enum.CancelCheck.html
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,43 @@
+use {
+    super::InvalidRules,
+    crate::filepath_cache::InvalidCache,
+    std::{error::Error, fmt, io},
+};
+
+/// The error of the search, see the [`SpecializedAscii::spawner`].
+///
+/// [`SpecializedAscii::spawner`]: struct.SpecializedAscii.html#method.spawner
+#[derive(Debug)]
+pub enum SearchError {
+    /// The cache is broken, or it is already searched.
+    Cache(InvalidCache<()>),
+    /// The needle is empty, once it's normalized by the rules,
+    /// see the [`Rules::check_needle`]: such needle matches every line.
+    ///
+    /// [`Rules::check_needle`]: struct.Rules.html#method.check_needle
+    Needle(InvalidRules),
+    /// One of the worker threads couldn't be spawned,
+    /// e.g. with the too big [`Rules::worker_stack_size`].
+    ///
+    /// [`Rules::worker_stack_size`]: struct.Rules.html#structfield.worker_stack_size
+    Spawn(io::Error),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::Cache(_) => f.write_str("invalid cache"),
+            SearchError::Needle(e) => e.fmt(f),
+            SearchError::Spawn(e) => write!(f, "can't spawn the worker: {}", e),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+impl From<InvalidCache<()>> for SearchError {
+    #[inline]
+    fn from(e: InvalidCache<()>) -> Self {
+        SearchError::Cache(e)
+    }
+}
@@ -0,0 +1,703 @@
+use {
+    super::fmt_usize,
+    crate::fzy_algo::scoring_utils::{normalize_score, Score, MWP},
+    std::{collections::HashMap, sync::Arc},
+};
+
+/// A matched line with all the info about it.
+///
+/// The result item itself is the `(String, Score, Box<[usize]>)` tuple,
+/// which is (de)serializable with the `serde` feature out of the box;
+/// the matched line only borrows, thus it's only serializable.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MatchedLine<'a> {
+    /// Path of the file without the root folder.
+    pub path: &'a str,
+    /// Number of the line, starting from 1.
+    pub row: usize,
+    /// Column of the first non-whitespace char, starting from 1.
+    pub col: usize,
+    /// The line without leading and trailing whitespaces.
+    pub line: &'a str,
+    /// The whole line, as it is in the file.
+    pub raw_line: &'a str,
+    pub score: Score,
+    /// Positions of the matched chars in the `line`, in chars.
+    pub positions: &'a [usize],
+    /// The unit of the positions of the formatted result,
+    /// see the [`result_positions`].
+    ///
+    /// [`result_positions`]: #method.result_positions
+    pub unit: PositionUnit,
+}
+
+/// The unit of the positions of the result.
+///
+/// The ASCII lines have the same positions in both units,
+/// the UTF-8 ones don't, and the UI should know which ones it highlights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum PositionUnit {
+    /// Positions are the indices of the chars.
+    Chars,
+    /// Positions are the byte offsets of the matched chars,
+    /// like Vim's `matchaddpos()` columns (minus one).
+    Bytes,
+    /// Positions are the indices of the grapheme clusters,
+    /// the ones the user sees as single chars: the emoji sequences,
+    /// the letters with combining marks, the Indic syllables.
+    ///
+    /// The cluster of several matched chars is a single position.
+    #[cfg(feature = "graphemes")]
+    Graphemes,
+}
+
+impl Default for PositionUnit {
+    #[inline]
+    fn default() -> Self {
+        PositionUnit::Chars
+    }
+}
+
+impl MatchedLine<'_> {
+    /// Positions moved right by `offset` chars.
+    ///
+    /// Useful, when the line is not at the start of the formatted string.
+    #[inline]
+    pub fn shifted_positions(&self, offset: usize) -> Box<[usize]> {
+        self.positions.iter().map(|p| p + offset).collect()
+    }
+
+    /// Positions moved right by `offset` chars, in the `unit` of the `text`,
+    /// which is the text the positions point to (the formatted string,
+    /// or the `raw_line` for example).
+    ///
+    /// That's the positions the formatters should return.
+    pub fn result_positions(&self, text: &str, offset: usize) -> Box<[usize]> {
+        let positions = self.shifted_positions(offset);
+        match self.unit {
+            PositionUnit::Chars => positions,
+            PositionUnit::Bytes => char_to_byte_positions(text, &positions),
+            #[cfg(feature = "graphemes")]
+            PositionUnit::Graphemes => char_to_grapheme_positions(text, &positions),
+        }
+    }
+
+    /// The score on the `0..=1000` scale, see the [`normalize_score`].
+    ///
+    /// [`normalize_score`]: fzy_algo/scoring_utils/fn.normalize_score.html
+    #[inline]
+    pub fn normalized_score(&self) -> u16 {
+        normalize_score(self.score, self.positions.len())
+    }
+
+    /// Column of the first matched char in the `raw_line`, starting from 1.
+    ///
+    /// Unlike `col`, it's counted in bytes, just like grep and Vim do.
+    pub fn match_col(&self) -> usize {
+        let first_char = self.positions.first().map_or(0, |p| p + self.col - 1);
+
+        1 + self
+            .raw_line
+            .char_indices()
+            .nth(first_char)
+            .map_or(self.raw_line.len(), |(idx, _ch)| idx)
+    }
+
+    /// The `raw_line`, where the text from the first matched char
+    /// to the last one is replaced with the `template`.
+    ///
+    /// The `$0` of the template is the replaced text itself, the `$$` is the `$`.
+    pub fn replaced(&self, template: &str) -> String {
+        let raw_line = self.raw_line;
+        let offset = self.col - 1;
+        let (first, last) = match (self.positions.first(), self.positions.last()) {
+            (Some(first), Some(last)) => (first + offset, last + offset),
+            _ => return raw_line.to_owned(),
+        };
+        let start = raw_line
+            .char_indices()
+            .nth(first)
+            .map_or(raw_line.len(), |(idx, _ch)| idx);
+        let end = raw_line
+            .char_indices()
+            .nth(last)
+            .map_or(raw_line.len(), |(idx, ch)| idx + ch.len_utf8());
+        let matched = &raw_line[start..end];
+
+        let mut replaced = String::with_capacity(raw_line.len() + template.len());
+        replaced.push_str(&raw_line[..start]);
+        let mut template = template;
+        while let Some(idx) = template.find('$') {
+            replaced.push_str(&template[..idx]);
+            template = &template[idx + 1..];
+            if template.starts_with('0') {
+                replaced.push_str(matched);
+                template = &template[1..];
+            } else {
+                // Both the `$$` and the lone `$`.
+                replaced.push('$');
+                template = template.strip_prefix('$').unwrap_or(template);
+            }
+        }
+        replaced.push_str(template);
+        replaced.push_str(&raw_line[end..]);
+        replaced
+    }
+}
+
+/// Converts the char positions of the `text` into the byte offsets of those chars.
+///
+/// Positions should be sorted; the ones past the end of the `text`
+/// become its length.
+pub fn char_to_byte_positions(text: &str, positions: &[usize]) -> Box<[usize]> {
+    let mut char_indices = text.char_indices().enumerate();
+    positions
+        .iter()
+        .map(|&position| {
+            char_indices
+                .find(|(char_idx, _byte_idx_ch)| *char_idx == position)
+                .map_or(text.len(), |(_char_idx, (byte_idx, _ch))| byte_idx)
+        })
+        .collect()
+}
+
+/// Converts the char positions of the `text` into the indices
+/// of the extended grapheme clusters, those chars are parts of.
+///
+/// Positions should be sorted; the chars of the same cluster
+/// become a single position, the ones past the end of the `text`
+/// become its number of clusters.
+#[cfg(feature = "graphemes")]
+pub fn char_to_grapheme_positions(text: &str, positions: &[usize]) -> Box<[usize]> {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    let mut graphemes = text.graphemes(true).enumerate();
+    // The index of the current cluster, and the number of chars up to its end.
+    let mut current: Option<(usize, usize)> = None;
+    let mut grapheme_positions: Vec<usize> = Vec::with_capacity(positions.len());
+    for &position in positions {
+        let grapheme_idx = loop {
+            match current {
+                Some((idx, chars_end)) if position < chars_end => break idx,
+                _ => match graphemes.next() {
+                    Some((idx, grapheme)) => {
+                        let chars_start = current.map_or(0, |(_idx, chars_end)| chars_end);
+                        current = Some((idx, chars_start + grapheme.chars().count()));
+                    }
+                    None => break current.map_or(0, |(idx, _chars_end)| idx + 1),
+                },
+            }
+        };
+        if grapheme_positions.last() != Some(&grapheme_idx) {
+            grapheme_positions.push(grapheme_idx);
+        }
+    }
+    grapheme_positions.into_boxed_slice()
+}
+
+/// Converts the byte offsets of the `text` into the char positions,
+/// the offset inside the char points to that char.
+///
+/// Positions should be sorted; the ones past the end of the `text`
+/// become its number of chars.
+pub fn byte_to_char_positions(text: &str, positions: &[usize]) -> Box<[usize]> {
+    let bytes = text.as_bytes();
+    // The bytes and chars counted so far.
+    let (mut counted_bytes, mut counted_chars) = (0, 0);
+    positions
+        .iter()
+        .map(|&position| {
+            let position = position.min(bytes.len()).max(counted_bytes);
+            // The first bytes of the chars are not `0b10xx_xxxx`.
+            counted_chars += bytes[counted_bytes..position]
+                .iter()
+                .filter(|&&byte| byte & 0xC0 != 0x80)
+                .count();
+            counted_bytes = position;
+            // The offset inside the char is counted, when the char has started.
+            let inside_char = bytes
+                .get(position)
+                .map_or(false, |&byte| byte & 0xC0 == 0x80);
+            counted_chars - inside_char as usize
+        })
+        .collect()
+}
+
+/// Merges the sorted positions into the `(start, len)` ranges
+/// of the consecutive positions, like `[1, 2, 3, 7]` into `[(1, 3), (7, 1)]`.
+///
+/// That's what the highlight APIs, like Vim's `matchaddpos()`, want,
+/// and it's much smaller for the long consecutive matches.
+pub fn coalesce_positions(positions: &[usize]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &position in positions {
+        match ranges.last_mut() {
+            Some((start, len)) if *start + *len == position => *len += 1,
+            _ => ranges.push((position, 1)),
+        }
+    }
+    ranges
+}
+
+/// The matched line, that is not formatted yet: it owns its text,
+/// and the path is shared by all the matches of its file.
+///
+/// Those are the items of the [`match_spawner`]: the path is stored
+/// once per file, instead of once per result string,
+/// and the matches could be grouped by their files, see the [`group_by_path`].
+///
+/// [`match_spawner`]: struct.SpecializedAscii.html#method.match_spawner
+/// [`group_by_path`]: fn.group_by_path.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMatch {
+    /// Path of the file without the root folder, the same `Arc` for the whole file.
+    pub path: Arc<str>,
+    /// Number of the line, starting from 1.
+    pub row: usize,
+    /// Column of the first non-whitespace char, starting from 1.
+    pub col: usize,
+    raw_line: Box<str>,
+    /// The bytes of the trimmed line in the `raw_line`.
+    line: (usize, usize),
+    pub score: Score,
+    /// Positions of the matched chars in the [`line`], in chars.
+    ///
+    /// [`line`]: #method.line
+    pub positions: Box<[usize]>,
+    /// The unit of the positions of the formatted result.
+    pub unit: PositionUnit,
+}
+
+impl LineMatch {
+    /// Takes the text of the `matched`, its `line` should be a part of its `raw_line`.
+    #[cfg(feature = "search")]
+    pub(crate) fn new(matched: &MatchedLine<'_>, path: Arc<str>) -> Self {
+        let start = matched.line.as_ptr() as usize - matched.raw_line.as_ptr() as usize;
+        let end = start + matched.line.len();
+        debug_assert_eq!(matched.raw_line.get(start..end), Some(matched.line));
+
+        Self {
+            path,
+            row: matched.row,
+            col: matched.col,
+            raw_line: matched.raw_line.into(),
+            line: (start, end),
+            score: matched.score,
+            positions: matched.positions.into(),
+            unit: matched.unit,
+        }
+    }
+
+    /// The line without leading and trailing whitespaces.
+    #[inline]
+    pub fn line(&self) -> &str {
+        &self.raw_line[self.line.0..self.line.1]
+    }
+
+    /// The whole line, as it is in the file.
+    #[inline]
+    pub fn raw_line(&self) -> &str {
+        &self.raw_line
+    }
+
+    #[inline]
+    pub fn matched_line(&self) -> MatchedLine<'_> {
+        MatchedLine {
+            path: &self.path,
+            row: self.row,
+            col: self.col,
+            line: self.line(),
+            raw_line: &self.raw_line,
+            score: self.score,
+            positions: &self.positions,
+            unit: self.unit,
+        }
+    }
+
+    /// Turns the match into the result string with the `formatter`.
+    #[inline]
+    pub fn format(&self, formatter: &impl ItemFormatter) -> MWP {
+        formatter.format(&self.matched_line())
+    }
+}
+
+/// Groups the matches by their files, the files are in the order
+/// of their first matches, and so are the matches of every file.
+///
+/// # Examples
+///
+/// ```
+/// use fulf::group_by_path;
+///
+/// assert!(group_by_path(Vec::new()).is_empty());
+/// ```
+pub fn group_by_path(matches: Vec<LineMatch>) -> Vec<(Arc<str>, Vec<LineMatch>)> {
+    let mut groups: Vec<(Arc<str>, Vec<LineMatch>)> = Vec::new();
+    let mut indices: HashMap<Arc<str>, usize> = HashMap::new();
+    for matched in matches {
+        // The matches of one file mostly come one after another.
+        let idx = match groups.last() {
+            Some((path, _matches)) if Arc::ptr_eq(path, &matched.path) => groups.len() - 1,
+            _ => *indices.entry(Arc::clone(&matched.path)).or_insert_with(|| {
+                groups.push((Arc::clone(&matched.path), Vec::new()));
+                groups.len() - 1
+            }),
+        };
+        groups[idx].1.push(matched);
+    }
+    groups
+}
+
+/// Turns the matched line into the string, that is shown to the user.
+///
+/// Positions of the result should point to the returned string,
+/// thus if there's something before the line, positions should be shifted;
+/// the [`MatchedLine::result_positions`] does that in the right unit.
+///
+/// [`MatchedLine::result_positions`]: struct.MatchedLine.html#method.result_positions
+pub trait ItemFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP;
+}
+
+/// Formats the line as `path:row:col:line`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl ItemFormatter for DefaultFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+        let bufs = (&mut [0_u8; 20], &mut [0_u8; 20]);
+        let row = fmt_usize(matched.row, bufs.0);
+        let col = fmt_usize(matched.col, bufs.1);
+        // Three `:` chars, plus all other chars;
+        // `row` and `len` are ascii digits, thus `len()`, not `chars().count()`.
+        let path_row_col_len = 3 + matched.path.chars().count() + row.len() + col.len();
+
+        let item = format!(
+            "{}:{row}:{col}:{line}",
+            matched.path,
+            row = row,
+            col = col,
+            line = matched.line,
+        );
+        let positions = matched.result_positions(&item, path_row_col_len);
+
+        (item, matched.score, positions)
+    }
+}
+
+/// Formats the line as `path:row:col:raw_line`, just like `grep -n --column`.
+///
+/// Unlike the [`DefaultFormatter`], the column points to the first matched
+/// byte and the line is not trimmed, so the output could be parsed
+/// by any tool, that understands grep's output (e.g. Vim's `errorformat`).
+///
+/// [`DefaultFormatter`]: struct.DefaultFormatter.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GrepFormatter;
+
+impl ItemFormatter for GrepFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+        let bufs = (&mut [0_u8; 20], &mut [0_u8; 20]);
+        let row = fmt_usize(matched.row, bufs.0);
+        let col = fmt_usize(matched.match_col(), bufs.1);
+        let path_row_col_len = 3 + matched.path.chars().count() + row.len() + col.len();
+
+        let item = format!(
+            "{}:{row}:{col}:{line}",
+            matched.path,
+            row = row,
+            col = col,
+            line = matched.raw_line,
+        );
+        // Trimmed chars are back in the line.
+        let positions = matched.result_positions(&item, path_row_col_len + matched.col - 1);
+
+        (item, matched.score, positions)
+    }
+}
+
+/// Formats the line as a Vim quickfix item:
+/// `{'filename': 'path', 'lnum': 1, 'col': 1, 'text': 'raw_line'}`.
+///
+/// The item is a Vim dictionary literal, so the plugin could `eval()`
+/// the list of items made by [`QuickfixFormatter::list`]
+/// and pass it to the `setqflist()` right away.
+///
+/// Unlike other formatters, positions point to the `text`,
+/// not to the whole formatted string.
+///
+/// [`QuickfixFormatter::list`]: struct.QuickfixFormatter.html#method.list
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuickfixFormatter;
+
+impl QuickfixFormatter {
+    /// Joins formatted items into a Vim list.
+    pub fn list<'a>(items: impl IntoIterator<Item = &'a MWP>) -> String {
+        let mut list = String::from("[");
+        for (idx, item) in items.into_iter().enumerate() {
+            if idx != 0 {
+                list.push_str(", ");
+            }
+            list.push_str(&item.0);
+        }
+        list.push(']');
+        list
+    }
+}
+
+impl ItemFormatter for QuickfixFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+        let mut item = String::with_capacity(64 + matched.path.len() + matched.raw_line.len());
+
+        item.push_str("{'filename': ");
+        push_vim_string(&mut item, matched.path);
+        item.push_str(", 'lnum': ");
+        item.push_str(fmt_usize(matched.row, &mut [0_u8; 20]));
+        item.push_str(", 'col': ");
+        item.push_str(fmt_usize(matched.match_col(), &mut [0_u8; 20]));
+        item.push_str(", 'text': ");
+        push_vim_string(&mut item, matched.raw_line);
+        item.push('}');
+
+        (
+            item,
+            matched.score,
+            matched.result_positions(matched.raw_line, matched.col - 1),
+        )
+    }
+}
+
+/// Formats the line as the preview of the replacement, a Vim dictionary:
+/// `{'filename': 'path', 'lnum': 1, 'col': 1, 'text': 'raw_line', 'replaced': 'new_line'}`.
+///
+/// The `replaced` is the [`MatchedLine::replaced`] with the template,
+/// so the UI could show both lines before the user confirms the edits.
+/// Just like with the [`QuickfixFormatter`], positions point to the `text`.
+///
+/// [`MatchedLine::replaced`]: struct.MatchedLine.html#method.replaced
+/// [`QuickfixFormatter`]: struct.QuickfixFormatter.html
+#[derive(Debug, Clone)]
+pub struct ReplaceFormatter {
+    template: Arc<str>,
+}
+
+impl ReplaceFormatter {
+    #[inline]
+    pub fn new(template: impl Into<Arc<str>>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+}
+
+impl ItemFormatter for ReplaceFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+        let replaced = matched.replaced(&self.template);
+        let mut item = String::with_capacity(
+            80 + matched.path.len() + matched.raw_line.len() + replaced.len(),
+        );
+
+        item.push_str("{'filename': ");
+        push_vim_string(&mut item, matched.path);
+        item.push_str(", 'lnum': ");
+        item.push_str(fmt_usize(matched.row, &mut [0_u8; 20]));
+        item.push_str(", 'col': ");
+        item.push_str(fmt_usize(matched.match_col(), &mut [0_u8; 20]));
+        item.push_str(", 'text': ");
+        push_vim_string(&mut item, matched.raw_line);
+        item.push_str(", 'replaced': ");
+        push_vim_string(&mut item, &replaced);
+        item.push('}');
+
+        (
+            item,
+            matched.score,
+            matched.result_positions(matched.raw_line, matched.col - 1),
+        )
+    }
+}
+
+/// Pushes the single-quoted Vim string literal;
+/// the only char to escape in such literal is `'` itself.
+fn push_vim_string(buf: &mut String, s: &str) {
+    buf.push('\'');
+    s.split('\'').enumerate().for_each(|(idx, part)| {
+        if idx != 0 {
+            buf.push_str("''");
+        }
+        buf.push_str(part);
+    });
+    buf.push('\'');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_positions_point_to_the_line() {
+        let (s, score, pos) = DefaultFormatter.format(&MatchedLine {
+            path: "src/лол.rs",
+            row: 12,
+            col: 5,
+            line: "fn main()",
+            raw_line: "    fn main()",
+            score: 42,
+            positions: &[0, 1],
+            unit: PositionUnit::Chars,
+        });
+
+        assert_eq!(s, "src/лол.rs:12:5:fn main()");
+        assert_eq!(score, 42);
+        let chars: Vec<char> = s.chars().collect();
+        assert_eq!([chars[pos[0]], chars[pos[1]]], ['f', 'n']);
+    }
+
+    #[cfg(feature = "search")]
+    #[test]
+    fn line_matches_are_grouped_by_path() {
+        let (a, b): (Arc<str>, Arc<str>) = ("a.rs".into(), "b.rs".into());
+        let matched = |path: &Arc<str>, row| {
+            let raw_line = "  fn main() ";
+            LineMatch::new(
+                &MatchedLine {
+                    path,
+                    row,
+                    col: 3,
+                    line: &raw_line[2..11],
+                    raw_line,
+                    score: 1,
+                    positions: &[3, 4],
+                    unit: PositionUnit::Chars,
+                },
+                Arc::clone(path),
+            )
+        };
+
+        let first = matched(&a, 1);
+        assert_eq!(first.line(), "fn main()");
+        assert_eq!(first.format(&DefaultFormatter).0, "a.rs:1:3:fn main()");
+
+        let groups = group_by_path(vec![first, matched(&b, 4), matched(&a, 7)]);
+        let rows: Vec<(&str, Vec<usize>)> = groups
+            .iter()
+            .map(|(path, matches)| (&**path, matches.iter().map(|m| m.row).collect()))
+            .collect();
+        assert_eq!(rows, [("a.rs", vec![1, 7]), ("b.rs", vec![4])]);
+    }
+
+    #[test]
+    fn grep_column_is_in_bytes() {
+        let matched = MatchedLine {
+            path: "a.rs",
+            row: 3,
+            col: 3,
+            line: "ёж = main()",
+            raw_line: "\t ёж = main()",
+            score: 0,
+            positions: &[5, 6],
+            unit: PositionUnit::Chars,
+        };
+
+        let (s, _score, pos) = GrepFormatter.format(&matched);
+        // Tab, space and two 2-byte chars, plus ` = ` before the `m`.
+        assert_eq!(s, "a.rs:3:10:\t ёж = main()");
+        let chars: Vec<char> = s.chars().collect();
+        assert_eq!([chars[pos[0]], chars[pos[1]]], ['m', 'a']);
+    }
+
+    #[test]
+    fn quickfix_item_is_vim_dict() {
+        let matched = MatchedLine {
+            path: "it's.vim",
+            row: 7,
+            col: 1,
+            line: "echo 'hi'",
+            raw_line: "echo 'hi'",
+            score: 0,
+            positions: &[0],
+            unit: PositionUnit::Chars,
+        };
+
+        let item = QuickfixFormatter.format(&matched);
+        assert_eq!(
+            item.0,
+            "{'filename': 'it''s.vim', 'lnum': 7, 'col': 1, 'text': 'echo ''hi'''}"
+        );
+        assert_eq!(
+            QuickfixFormatter::list(vec![&item, &item]),
+            format!("[{0}, {0}]", item.0)
+        );
+    }
+
+    #[test]
+    fn replaced_from_first_to_last_match() {
+        let matched = MatchedLine {
+            path: "a.rs",
+            row: 1,
+            col: 2,
+            line: "let ёж = foo_bar;",
+            raw_line: " let ёж = foo_bar;",
+            score: 0,
+            positions: &[9, 15],
+            unit: PositionUnit::Chars,
+        };
+
+        assert_eq!(matched.replaced("baz"), " let ёж = baz;");
+        assert_eq!(matched.replaced("$$($0)"), " let ёж = $(foo_bar);");
+
+        let item = ReplaceFormatter::new("x").format(&matched);
+        assert!(item
+            .0
+            .ends_with("'text': ' let ёж = foo_bar;', 'replaced': ' let ёж = x;'}"));
+    }
+
+    #[test]
+    fn consecutive_positions_are_coalesced() {
+        assert_eq!(coalesce_positions(&[]), []);
+        assert_eq!(coalesce_positions(&[4]), [(4, 1)]);
+        assert_eq!(
+            coalesce_positions(&[0, 1, 2, 5, 7, 8]),
+            [(0, 3), (5, 1), (7, 2)]
+        );
+    }
+
+    #[test]
+    fn positions_are_converted_between_units() {
+        let text = "ёж: x";
+        assert_eq!(&*char_to_byte_positions(text, &[0, 1, 4, 9]), [0, 2, 6, 7]);
+        assert_eq!(
+            &*byte_to_char_positions(text, &[0, 1, 2, 6, 9]),
+            [0, 0, 1, 4, 5]
+        );
+
+        let matched = MatchedLine {
+            path: "a.rs",
+            row: 1,
+            col: 1,
+            line: text,
+            raw_line: text,
+            score: 0,
+            positions: &[1, 4],
+            unit: PositionUnit::Bytes,
+        };
+        let (s, _score, pos) = GrepFormatter.format(&matched);
+        assert_eq!(&s[pos[0]..pos[1]], "ж: ");
+        assert_eq!(&*QuickfixFormatter.format(&matched).2, [2, 6]);
+    }
+
+    #[cfg(feature = "graphemes")]
+    #[test]
+    fn combined_chars_are_one_grapheme() {
+        // `e` with the combining acute accent, then `x`.
+        let text = "e\u{301}x";
+        assert_eq!(&*char_to_grapheme_positions(text, &[0, 1, 2]), [0, 1]);
+        assert_eq!(&*char_to_grapheme_positions(text, &[1, 5]), [0, 2]);
+    }
+}
@@ -0,0 +1,26 @@
+//! The search inside the `.gz` files: rotated logs, man pages and the like.
+
+use {super::MEGABYTE, flate2::read::GzDecoder, std::io::Read};
+
+/// The text of the file to search: the decompressed one for the `.gz` files,
+/// which is kept in the `unpacked` buffer.
+///
+/// `None` if the file is not a valid gzip, or if it's bigger than
+/// a megabyte decompressed, just like the plain files.
+pub(super) fn unpacked<'b>(
+    path: &str,
+    filebuf: &'b [u8],
+    unpacked: &'b mut Vec<u8>,
+) -> Option<&'b [u8]> {
+    if !path.ends_with(".gz") {
+        return Some(filebuf);
+    }
+
+    unpacked.clear();
+    // One byte more, to know that the file is too big.
+    let mut decoder = GzDecoder::new(filebuf).take(MEGABYTE as u64 + 1);
+    match decoder.read_to_end(unpacked) {
+        Ok(len) if len <= MEGABYTE => Some(unpacked),
+        _ => None,
+    }
+}
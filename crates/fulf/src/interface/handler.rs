@@ -0,0 +1,89 @@
+use crate::fzy_algo::scoring_utils::MWP;
+
+/// The progress of a running search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Progress {
+    /// Number of files, that were taken by the worker threads.
+    pub files_processed: usize,
+    /// Number of files in the cache.
+    pub files_total: usize,
+    /// Number of results, that were received so far.
+    pub matches: usize,
+}
+
+/// Takes the results from busy worker threads and handles those results.
+///
+/// Implemented for any `FnMut(Vec<MWP>)` closure,
+/// so in the simplest case there's no need to implement it by hand.
+pub trait HandleResults {
+    /// Handles one batch of results, sent by some worker thread.
+    fn handle_results(&mut self, results: Vec<MWP>);
+
+    /// Called periodically during the search, and once after it's done.
+    ///
+    /// Does nothing by default.
+    #[inline]
+    fn progress(&mut self, _progress: Progress) {}
+
+    /// Adds the progress hook to the handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fulf::{fzy_algo::scoring_utils::MWP, HandleResults, Progress};
+    ///
+    /// let handler = (|_results: Vec<MWP>| ()).with_progress(|p: Progress| {
+    ///     println!("{}/{} files", p.files_processed, p.files_total)
+    /// });
+    /// # let _ = handler;
+    /// ```
+    #[inline]
+    fn with_progress<P>(self, progress: P) -> WithProgress<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(Progress),
+    {
+        WithProgress {
+            handler: self,
+            progress,
+        }
+    }
+}
+
+impl<F> HandleResults for F
+where
+    F: FnMut(Vec<MWP>),
+{
+    #[inline]
+    fn handle_results(&mut self, results: Vec<MWP>) {
+        self(results)
+    }
+}
+
+/// A handler with the progress hook.
+///
+/// Created by [`HandleResults::with_progress`].
+///
+/// [`HandleResults::with_progress`]: trait.HandleResults.html#method.with_progress
+pub struct WithProgress<H, P> {
+    handler: H,
+    progress: P,
+}
+
+impl<H, P> HandleResults for WithProgress<H, P>
+where
+    H: HandleResults,
+    P: FnMut(Progress),
+{
+    #[inline]
+    fn handle_results(&mut self, results: Vec<MWP>) {
+        self.handler.handle_results(results)
+    }
+
+    #[inline]
+    fn progress(&mut self, progress: Progress) {
+        self.handler.progress(progress);
+        (self.progress)(progress)
+    }
+}
@@ -0,0 +1,137 @@
+use {
+    super::{Algo, CancelToken, ItemFormatter, Rules, SearchError, SearchStats, SpecializedAscii},
+    crate::{filepath_cache::IndexedCache, fzy_algo::scoring_utils::MWP},
+    std::{
+        sync::Arc,
+        thread::{self, JoinHandle},
+        vec,
+    },
+};
+
+/// The results of the search, pulled one by one, see the [`search_iter`].
+///
+/// Dropping the iterator before its end cancels the search and waits for it.
+///
+/// [`search_iter`]: struct.SpecializedAscii.html#method.search_iter
+pub struct SearchIter {
+    batch: vec::IntoIter<MWP>,
+    // Both are `Option` only to be taken in the `Drop`.
+    receiver: Option<flume::Receiver<Vec<MWP>>>,
+    thread: Option<JoinHandle<Result<SearchStats, SearchError>>>,
+    cancel: CancelToken,
+    // Every result was pulled.
+    done: bool,
+}
+
+impl Iterator for SearchIter {
+    type Item = MWP;
+
+    fn next(&mut self) -> Option<MWP> {
+        loop {
+            if let Some(item) = self.batch.next() {
+                return Some(item);
+            }
+            match self.receiver.as_ref()?.recv() {
+                Ok(batch) => self.batch = batch.into_iter(),
+                Err(_) => {
+                    self.done = true;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl SearchIter {
+    /// Stops the search, if the results are not pulled to the end,
+    /// and returns its statistics.
+    pub fn finish(mut self) -> Result<SearchStats, SearchError> {
+        self.stop().unwrap_or_else(|| Ok(SearchStats::default()))
+    }
+
+    fn stop(&mut self) -> Option<Result<SearchStats, SearchError>> {
+        if !self.done {
+            self.cancel.cancel();
+        }
+        // Unblocks the search, if it waits for the results to be pulled.
+        drop(self.receiver.take());
+        // A panicked search is just a finished search there.
+        self.thread.take()?.join().ok()
+    }
+}
+
+impl Drop for SearchIter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo,
+    U: Algo,
+    F: ItemFormatter + Clone + Send + 'static,
+{
+    /// Like the [`spawner`], but the results are pulled from the iterator.
+    ///
+    /// The search goes only as far as the results are pulled:
+    /// once `channel_capacity` batches are waiting, the threads wait too.
+    /// So the first results cost only the files, read to find them.
+    ///
+    /// The iterator, dropped before its end, cancels the `cancel` token of the rules.
+    ///
+    /// [`spawner`]: #method.spawner
+    pub fn search_iter(self, cache: Arc<IndexedCache>, r: Rules) -> SearchIter {
+        let (sender, receiver) = flume::bounded(r.channel_capacity.unwrap_or(2));
+        let cancel = r.cancel.clone();
+
+        let thread = thread::spawn(move || {
+            self.spawner(cache, r, move |batch: Vec<MWP>| {
+                // The iterator is dropped, so the search is cancelled.
+                let _any_result = sender.send(batch);
+            })
+        });
+
+        SearchIter {
+            batch: Vec::new().into_iter(),
+            receiver: Some(receiver),
+            thread: Some(thread),
+            cancel,
+            done: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{filepath_cache::serialize_paths, fzy_search_iter, temp_tree::TempTree, Rules},
+        std::sync::Arc,
+    };
+
+    #[test]
+    fn results_are_pulled() {
+        let root = TempTree::new("iter");
+        let names: Vec<String> = (0..50).map(|n| format!("{}.rs", n)).collect();
+        for name in &names {
+            root.write(name, "fn main() {}\n");
+        }
+        let search = |rules| {
+            let cache = Arc::new(serialize_paths(names.iter().map(String::as_str)));
+            fzy_search_iter(root.path().into(), "main".into(), cache, rules)
+        };
+
+        let all = search(Rules::new()).count();
+        let mut rules = Rules::new();
+        rules.threads = std::num::NonZeroUsize::new(1).unwrap();
+        rules.thread_local_results_cap = 1;
+        let mut first = search(rules.clone());
+        let one = first.next();
+        let stats = first.finish();
+
+        assert_eq!(all, 50);
+        assert!(one.unwrap().0.ends_with(".rs:1:1:fn main() {}"));
+        assert!(stats.unwrap().files_scanned < 50);
+        assert!(rules.cancel.is_cancelled());
+    }
+}
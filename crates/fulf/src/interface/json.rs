@@ -0,0 +1,58 @@
+use {
+    super::format::{coalesce_positions, ItemFormatter, MatchedLine},
+    crate::fzy_algo::scoring_utils::{Score, MWP},
+    serde::Serialize,
+};
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    path: &'a str,
+    lnum: usize,
+    col: usize,
+    text: &'a str,
+    score: Score,
+    normalized_score: u16,
+    positions: &'a [usize],
+    ranges: &'a [(usize, usize)],
+}
+
+/// Formats the line as a one-line JSON object:
+/// `{"path":"a.rs","lnum":1,"col":1,"text":"raw_line","score":1,"normalized_score":1,"positions":[0,1],"ranges":[[0,2]]}`.
+///
+/// The `normalized_score` is the [`MatchedLine::normalized_score`].
+///
+/// Write the results with the [`LineWriter`] to get the JSON Lines output.
+///
+/// Just like with the [`QuickfixFormatter`], positions point
+/// to the `text`, and the `col` is counted in bytes.
+/// The `ranges` are the same positions as the `[start, len]` pairs,
+/// see the [`coalesce_positions`].
+///
+/// [`LineWriter`]: struct.LineWriter.html
+/// [`QuickfixFormatter`]: struct.QuickfixFormatter.html
+/// [`coalesce_positions`]: fn.coalesce_positions.html
+/// [`MatchedLine::normalized_score`]: struct.MatchedLine.html#method.normalized_score
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl ItemFormatter for JsonFormatter {
+    fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+        let positions = matched.result_positions(matched.raw_line, matched.col - 1);
+        let ranges = coalesce_positions(&positions);
+
+        let json = serde_json::to_string(&JsonItem {
+            path: matched.path,
+            lnum: matched.row,
+            col: matched.match_col(),
+            text: matched.raw_line,
+            score: matched.score,
+            normalized_score: matched.normalized_score(),
+            positions: &positions,
+            ranges: &ranges,
+        })
+        // There are only strings and numbers, nothing could fail.
+        .expect("JSON serialization of the matched line");
+
+        (json, matched.score, positions)
+    }
+}
@@ -0,0 +1,119 @@
+//! The Windows extended-length paths, the `\\?\C:\...` ones.
+//!
+//! The usual Windows path can't be longer than the `MAX_PATH`,
+//! so the files deep inside the `node_modules`-like trees
+//! are opened with the extended-length path,
+//! and the `\\?\` prefix is removed from the paths in the results.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// The longest usual path on Windows, with the terminating nul.
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+const VERBATIM: &str = r"\\?\";
+const VERBATIM_UNC: &str = r"\\?\UNC\";
+
+/// The path to open the file of the cache.
+///
+/// On Windows, too long path is turned into the extended-length one.
+#[inline]
+pub(super) fn file_path(root_folder: &Path, filepath: &str) -> PathBuf {
+    let path = root_folder.join(filepath);
+    #[cfg(windows)]
+    {
+        if path.as_os_str().len() >= MAX_PATH {
+            return extended(&path).unwrap_or(path);
+        }
+    }
+    path
+}
+
+/// The extended-length version of the path.
+///
+/// The extended-length paths are not normalized by Windows, so the `/`
+/// is replaced by the `\` and the `.` and `..` components are resolved here.
+///
+/// `None` if the path is already the extended-length one,
+/// or there's no current dir to make it absolute.
+#[cfg(windows)]
+fn extended(path: &Path) -> Option<PathBuf> {
+    use std::{env, path::Component};
+
+    if path.to_str().map_or(false, |s| s.starts_with(VERBATIM)) {
+        return None;
+    }
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().ok()?.join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+
+    let normalized = normalized.to_str()?.replace('/', "\\");
+    let extended = match normalized.strip_prefix(r"\\") {
+        Some(unc) => [VERBATIM_UNC, unc].concat(),
+        None => [VERBATIM, &normalized].concat(),
+    };
+    Some(PathBuf::from(extended))
+}
+
+/// Removes the extended-length prefix from the path in the results:
+/// `\\?\C:\x` becomes `C:\x`, and `\\?\UNC\server\x` becomes `\\server\x`.
+pub(super) fn strip_verbatim(path: Cow<'_, str>) -> Cow<'_, str> {
+    if let Some(unc) = path.strip_prefix(VERBATIM_UNC) {
+        Cow::Owned([r"\\", unc].concat())
+    } else if path.starts_with(VERBATIM) {
+        match path {
+            Cow::Borrowed(path) => Cow::Borrowed(&path[VERBATIM.len()..]),
+            Cow::Owned(path) => Cow::Owned(path[VERBATIM.len()..].to_owned()),
+        }
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbatim_prefix_is_stripped() {
+        let strip = |path: &str| strip_verbatim(Cow::Borrowed(path)).into_owned();
+        assert_eq!(strip(r"\\?\C:\proj\main.rs"), r"C:\proj\main.rs");
+        assert_eq!(
+            strip(r"\\?\UNC\server\share\main.rs"),
+            r"\\server\share\main.rs"
+        );
+        assert_eq!(strip(r"C:\proj\main.rs"), r"C:\proj\main.rs");
+        assert_eq!(strip("src/main.rs"), "src/main.rs");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_path_is_extended() {
+        let folder = "a".repeat(MAX_PATH);
+        let path = file_path(Path::new(r"C:\proj\."), &format!("{}/main.rs", folder));
+        assert_eq!(
+            path,
+            PathBuf::from(format!(r"\\?\C:\proj\{}\main.rs", folder))
+        );
+        assert_eq!(
+            file_path(Path::new(r"C:\proj"), "main.rs"),
+            Path::new(r"C:\proj\main.rs")
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! The line matchers, those could be picked at runtime.
+//!
+//! The [`SpecializedAscii`] is generic over its algorithms, so the choice
+//! of the algorithm from a config string monomorphizes every combination.
+//! The [`searcher`] takes any [`LineMatcher`] as the trait object instead:
+//!
+//! ```
+//! use {fulf::matcher, std::path::Path};
+//!
+//! let matcher = matcher::by_name("substring").unwrap();
+//! let searcher = matcher::searcher(Path::new(".").into(), "main".into(), matcher);
+//! ```
+//!
+//! [`SpecializedAscii`]: ../struct.SpecializedAscii.html
+//! [`searcher`]: fn.searcher.html
+//! [`LineMatcher`]: trait.LineMatcher.html
+
+use {
+    super::{Algo, SpecializedAscii},
+    crate::fzy_algo::{
+        ascii,
+        scoring_utils::{
+            score_add, score_from_usize, score_mul, MatchWithPositions, Score, SCORE_GAP_LEADING,
+            SCORE_GAP_TRAILING, SCORE_MATCH_CONSECUTIVE,
+        },
+        utf8, FzyItem,
+    },
+    std::{path::Path, sync::Arc},
+};
+
+/// Scores the line for the needle.
+///
+/// Implemented for the algorithm closures of the [`SpecializedAscii`] too,
+/// if they are `Sync`.
+///
+/// [`SpecializedAscii`]: ../struct.SpecializedAscii.html
+pub trait LineMatcher: Send + Sync {
+    /// The score and the char positions of the matched chars,
+    /// `None` if the line doesn't match.
+    ///
+    /// The `prealloc` is the reusable storage of the algorithm.
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions>;
+
+    /// The same as the [`match_line`], but the line is known to be ASCII.
+    ///
+    /// [`match_line`]: #tymethod.match_line
+    #[inline]
+    fn match_ascii_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        self.match_line(line, needle, prealloc)
+    }
+}
+
+impl<F> LineMatcher for F
+where
+    F: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions> + Send + Sync,
+{
+    #[inline]
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        self(line, needle, prealloc)
+    }
+}
+
+/// The fzy algorithm: the faster ASCII version for the ASCII lines and needles,
+/// the UTF-8 version for others.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fzy;
+
+impl LineMatcher for Fzy {
+    #[inline]
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        utf8::match_and_score_with_positions(needle, line, prealloc)
+    }
+
+    #[inline]
+    fn match_ascii_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        if needle.is_ascii() {
+            ascii::match_and_score_with_positions(needle.as_bytes(), line.as_bytes(), prealloc)
+        } else {
+            self.match_line(line, needle, prealloc)
+        }
+    }
+}
+
+/// The UTF-8 version of the fzy algorithm for all lines.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utf8Fzy;
+
+impl LineMatcher for Utf8Fzy {
+    #[inline]
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        utf8::match_and_score_with_positions(needle, line, prealloc)
+    }
+}
+
+/// The first case-insensitive occurrence of the whole needle.
+///
+/// It's scored like the fzy algorithm would score the consecutive match,
+/// so the scores of both are on the same scale.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Substring;
+
+impl LineMatcher for Substring {
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        _prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        let needle: Vec<char> = needle.chars().collect();
+        let line: Vec<char> = line.chars().collect();
+        if needle.is_empty() || needle.len() > line.len() {
+            return None;
+        }
+
+        let start = line.windows(needle.len()).position(|window| {
+            window
+                .iter()
+                .zip(&needle)
+                .all(|(&l, &n)| <char as FzyItem>::eq(l, n))
+        })?;
+        let prev = match start {
+            0 => <char as FzyItem>::INIT,
+            _ => line[start - 1],
+        };
+        let trailing = line.len() - start - needle.len();
+
+        let score = score_add(
+            score_add(
+                <char as FzyItem>::bonus_for_char(prev, line[start]),
+                score_mul(score_from_usize(needle.len() - 1), SCORE_MATCH_CONSECUTIVE),
+            ),
+            score_add(
+                score_mul(score_from_usize(start), SCORE_GAP_LEADING),
+                score_mul(score_from_usize(trailing), SCORE_GAP_TRAILING),
+            ),
+        );
+        Some((score, (start..start + needle.len()).collect()))
+    }
+}
+
+/// The matcher by its config name: `fzy`, `utf8` or `substring`.
+pub fn by_name(name: &str) -> Option<Arc<dyn LineMatcher>> {
+    match name {
+        "fzy" => Some(Arc::new(Fzy)),
+        "utf8" => Some(Arc::new(Utf8Fzy)),
+        "substring" => Some(Arc::new(Substring)),
+        _ => None,
+    }
+}
+
+/// The searcher with the dynamically dispatched matcher.
+///
+/// The ASCII lines are passed to the [`LineMatcher::match_ascii_line`].
+///
+/// [`LineMatcher::match_ascii_line`]: trait.LineMatcher.html#method.match_ascii_line
+pub fn searcher(
+    root_folder: Arc<Path>,
+    needle: Arc<str>,
+    matcher: Arc<dyn LineMatcher>,
+) -> SpecializedAscii<impl Algo, impl Algo> {
+    let ascii_matcher = Arc::clone(&matcher);
+    let ascii_algo = move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+        ascii_matcher.match_ascii_line(line, needle, prealloc)
+    };
+    let utf8_algo = move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+        matcher.match_line(line, needle, prealloc)
+    };
+    SpecializedAscii::new(root_folder, needle, ascii_algo, utf8_algo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substring_is_scored_like_consecutive_fzy() {
+        let prealloc = &mut Default::default();
+        let substring = by_name("substring").unwrap();
+        assert_eq!(substring.match_line("fn main()", "xyz", prealloc), None);
+
+        let (score, positions) = substring
+            .match_line("src/Main.rs", "main", prealloc)
+            .unwrap();
+        assert_eq!(positions, [4, 5, 6, 7]);
+        let fzy = Fzy
+            .match_ascii_line("src/Main.rs", "main", prealloc)
+            .unwrap();
+        assert_eq!((score, positions), fzy);
+
+        assert!(by_name("regex").is_none());
+    }
+}
@@ -0,0 +1,213 @@
+use {
+    super::{MemoryUsage, ResultSink, SinkError, StopCondition},
+    crate::{filepath_cache::IndexedCache, fzy_algo::scoring_utils::MWP},
+    std::{
+        mem,
+        sync::{
+            atomic::{AtomicUsize, Ordering::Relaxed},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+/// How long the worker sleeps before it checks the exhausted budget again.
+const BUDGET_WAIT: Duration = Duration::from_millis(1);
+
+/// The bounds of the file buffers and of the sent result batches,
+/// shared by all the workers of the search.
+///
+/// Only the files wait for the budget: the batches are already scored,
+/// and the handler frees them anyway, so the workers never wait for each other.
+#[derive(Debug, Clone)]
+pub(super) struct MemoryBudget {
+    usage: MemoryUsage,
+    max: Option<usize>,
+}
+
+impl MemoryBudget {
+    #[inline]
+    pub(super) fn new(max: Option<usize>, usage: MemoryUsage) -> Self {
+        Self { usage, max }
+    }
+
+    /// The file of this size could ever be loaded.
+    #[inline]
+    pub(super) fn fits(&self, bytes: usize) -> bool {
+        self.max.map_or(true, |max| bytes <= max)
+    }
+
+    /// Takes the bytes of the file buffer until the reservation is dropped,
+    /// waits while the budget is exhausted.
+    ///
+    /// `None` if the search should stop while waiting.
+    pub(super) fn reserve(&self, bytes: usize, stop: &StopCondition) -> Option<Reservation<'_>> {
+        let counters = self.usage.counters();
+        let max = match self.max {
+            Some(max) => max,
+            None => return Some(Reservation::new(&counters.file_buffers, bytes)),
+        };
+
+        let mut files = counters.file_buffers.load(Relaxed);
+        loop {
+            let held = files.saturating_add(counters.result_batches.load(Relaxed));
+            // Even the file bigger than the whole budget is loaded alone, instead of waiting forever.
+            if held == 0 || held.saturating_add(bytes) <= max {
+                match counters.file_buffers.compare_exchange_weak(
+                    files,
+                    files + bytes,
+                    Relaxed,
+                    Relaxed,
+                ) {
+                    Ok(_) => {
+                        return Some(Reservation {
+                            counter: &counters.file_buffers,
+                            bytes,
+                        })
+                    }
+                    Err(actual) => files = actual,
+                }
+            } else if stop.should_stop() {
+                return None;
+            } else {
+                thread::sleep(BUDGET_WAIT);
+                files = counters.file_buffers.load(Relaxed);
+            }
+        }
+    }
+
+    /// Counts the cache as held until the reservation is dropped.
+    #[inline]
+    pub(super) fn hold_cache(&self, cache: &IndexedCache) -> Reservation<'_> {
+        Reservation::new(&self.usage.counters().caches, cache.heap_size())
+    }
+
+    /// Sends the batch, its bytes are held until the [`received`].
+    ///
+    /// [`received`]: #method.received
+    pub(super) fn send<T: Held>(
+        &self,
+        sender: &flume::Sender<Vec<T>>,
+        batch: Vec<T>,
+    ) -> Result<(), flume::SendError<Vec<T>>> {
+        let counter = &self.usage.counters().result_batches;
+        let bytes = batch_bytes(&batch);
+        counter.fetch_add(bytes, Relaxed);
+        sender.send(batch).map_err(|e| {
+            counter.fetch_sub(bytes, Relaxed);
+            e
+        })
+    }
+
+    /// Like the [`send`], but waits for the room in the channel
+    /// only for the `timeout`.
+    ///
+    /// [`send`]: #method.send
+    pub(super) fn send_timeout<T: Held>(
+        &self,
+        sender: &flume::Sender<Vec<T>>,
+        batch: Vec<T>,
+        timeout: Duration,
+    ) -> Result<(), flume::SendTimeoutError<Vec<T>>> {
+        let counter = &self.usage.counters().result_batches;
+        let bytes = batch_bytes(&batch);
+        counter.fetch_add(bytes, Relaxed);
+        sender.send_timeout(batch, timeout).map_err(|e| {
+            counter.fetch_sub(bytes, Relaxed);
+            e
+        })
+    }
+
+    /// The batch is taken by the handler.
+    #[inline]
+    pub(super) fn received<T: Held>(&self, batch: &[T]) {
+        let counter = &self.usage.counters().result_batches;
+        counter.fetch_sub(batch_bytes(batch), Relaxed);
+    }
+}
+
+/// The sink of the [`spawner`]: the batches are held by the budget,
+/// until the handler takes them.
+///
+/// [`spawner`]: ../struct.SpecializedAscii.html#method.spawner
+pub(super) struct BudgetedSender<T = MWP> {
+    pub(super) sender: flume::Sender<Vec<T>>,
+    pub(super) budget: MemoryBudget,
+}
+
+// Not derived: the items themselves are never cloned.
+impl<T> Clone for BudgetedSender<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl<T: Held + Send> ResultSink<T> for BudgetedSender<T> {
+    #[inline]
+    fn send(&self, batch: Vec<T>) -> Result<(), Vec<T>> {
+        self.budget.send(&self.sender, batch).map_err(|e| e.0)
+    }
+
+    #[inline]
+    fn send_timeout(&self, batch: Vec<T>, timeout: Duration) -> Result<(), SinkError<T>> {
+        self.budget
+            .send_timeout(&self.sender, batch, timeout)
+            .map_err(SinkError::from)
+    }
+}
+
+/// The bytes of the memory usage counter, released on drop.
+pub(super) struct Reservation<'a> {
+    counter: &'a AtomicUsize,
+    bytes: usize,
+}
+
+impl<'a> Reservation<'a> {
+    #[inline]
+    fn new(counter: &'a AtomicUsize, bytes: usize) -> Self {
+        counter.fetch_add(bytes, Relaxed);
+        Self { counter, bytes }
+    }
+}
+
+impl Drop for Reservation<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.bytes, Relaxed);
+    }
+}
+
+/// The results, those the budget counts.
+pub(super) trait Held {
+    /// The approximate heap and inline size of the result.
+    fn held_bytes(&self) -> usize;
+}
+
+impl Held for MWP {
+    #[inline]
+    fn held_bytes(&self) -> usize {
+        let (line, _score, positions) = self;
+        mem::size_of::<MWP>() + line.len() + mem::size_of_val::<[usize]>(positions)
+    }
+}
+
+impl Held for (MWP, Arc<str>) {
+    #[inline]
+    fn held_bytes(&self) -> usize {
+        // The path is shared by all the results of the file.
+        self.0.held_bytes() + mem::size_of::<Arc<str>>()
+    }
+}
+
+/// The approximate heap and inline size of the batch.
+///
+/// The batch is not changed between the send and the receive,
+/// so both see the same number.
+fn batch_bytes<T: Held>(batch: &[T]) -> usize {
+    batch.iter().map(Held::held_bytes).sum()
+}
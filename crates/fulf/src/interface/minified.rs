@@ -0,0 +1,59 @@
+//! The guess of the minified or generated files, see the [`Rules::skip_minified`].
+//!
+//! [`Rules::skip_minified`]: ../struct.Rules.html#structfield.skip_minified
+
+use memchr::memchr_iter;
+
+/// The average line length of the minified file, in bytes.
+///
+/// The hand-written code rarely has even one line this long,
+/// and the minified one has few lines of thousands of bytes.
+const MINIFIED_LINE_LEN: usize = 512;
+
+/// Only the end of the file is checked for the source map comment.
+const SOURCE_MAP_TAIL: usize = 512;
+
+/// The comment of the bundlers, that points to the source map of the generated file.
+const SOURCE_MAP: &[u8] = b"# sourceMappingURL=";
+
+/// The file looks minified or generated: it has the `.min.js`-like name,
+/// the source map comment at the end, or too long lines on average.
+pub(super) fn looks_minified(path: &str, text: &[u8]) -> bool {
+    if [".min.js", ".min.css", ".min.mjs"]
+        .iter()
+        .any(|suffix| path.ends_with(suffix))
+    {
+        return true;
+    }
+
+    let tail = &text[text.len().saturating_sub(SOURCE_MAP_TAIL)..];
+    if tail
+        .windows(SOURCE_MAP.len())
+        .any(|window| window == SOURCE_MAP)
+    {
+        return true;
+    }
+
+    let lines = memchr_iter(b'\n', text).count() + 1;
+    text.len() / lines > MINIFIED_LINE_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minified_files_are_guessed() {
+        assert!(looks_minified("dist/app.min.js", b"let a = 1;\n"));
+        assert!(looks_minified(
+            "dist/app.js",
+            b"let a=1;\n//# sourceMappingURL=app.js.map\n"
+        ));
+        assert!(looks_minified("dist/app.js", "a;".repeat(1000).as_bytes()));
+        assert!(!looks_minified(
+            "src/app.js",
+            "let a = 1;\n".repeat(100).as_bytes()
+        ));
+        assert!(!looks_minified("src/empty.js", b""));
+    }
+}
@@ -1,45 +1,590 @@
+use std::{num::NonZeroUsize, time::Duration};
+#[cfg(feature = "search")]
 use {
     crate::{
         bytelines::{ByteLines, Line},
         filepath_cache::{IndexedCache, InvalidCache},
-        fzy_algo::scoring_utils::{MatchWithPositions, Score, MWP},
+        fzy_algo::{
+            is_best_match,
+            scoring_utils::{score_from_usize, MatchWithPositions, Score, MWP},
+        },
+        merger::{Delta, Merger, SortStrategy},
     },
-    std::{fs, io::Read, mem, path::MAIN_SEPARATOR, sync::Arc, thread},
+    memory::{BudgetedSender, Held, MemoryBudget},
+    output::{Output, ScoreFloor, Scored, Unformatted, WithPath},
+    scratch::Scratch,
+    std::{
+        borrow::Cow,
+        cmp::Reverse,
+        env, fs,
+        io::{self, Read},
+        mem,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering::Relaxed},
+            Arc,
+        },
+        thread::{self, JoinHandle},
+        time::Instant,
+    },
+};
+
+mod builder;
+mod cancel;
+#[cfg(feature = "search")]
+mod error;
+mod format;
+#[cfg(all(feature = "flate2", feature = "search"))]
+mod gzip;
+mod handler;
+#[cfg(feature = "search")]
+mod iter;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "search")]
+mod longpath;
+#[cfg(feature = "search")]
+pub mod matcher;
+#[cfg(feature = "search")]
+mod memory;
+#[cfg(feature = "search")]
+mod minified;
+#[cfg(feature = "search")]
+mod output;
+#[cfg(all(feature = "rayon", feature = "search"))]
+mod par;
+#[cfg(feature = "search")]
+mod priority;
+#[cfg(feature = "search")]
+mod roots;
+#[cfg(feature = "search")]
+mod scratch;
+#[cfg(feature = "search")]
+mod sink;
+mod stats;
+#[cfg(all(feature = "tokio", feature = "search"))]
+mod stream;
+#[cfg(feature = "search")]
+mod walk;
+mod writer;
+#[cfg(feature = "search")]
+pub use error::SearchError;
+#[cfg(feature = "graphemes")]
+pub use format::char_to_grapheme_positions;
+#[cfg(feature = "search")]
+pub use iter::SearchIter;
+#[cfg(feature = "json")]
+pub use json::JsonFormatter;
+#[cfg(feature = "search")]
+pub use roots::{MultiRootError, SearchRoot};
+#[cfg(feature = "search")]
+pub use sink::{ResultSink, SinkError};
+pub use {
+    builder::{InvalidRules, RulesBuilder},
+    cancel::{CancelCheck, CancelToken},
+    format::{
+        byte_to_char_positions, char_to_byte_positions, coalesce_positions, group_by_path,
+        DefaultFormatter, GrepFormatter, ItemFormatter, LineMatch, MatchedLine, PositionUnit,
+        QuickfixFormatter, ReplaceFormatter,
+    },
+    handler::{HandleResults, Progress, WithProgress},
+    stats::{MemoryUsage, SearchStats},
+    writer::LineWriter,
 };
 
+/// How often the progress hook of the handler is called.
+#[cfg(feature = "search")]
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// SAFETY: 1 is not 0.
+const ONE_THREAD: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(1) };
+
+//x XXX: is megabyte enough for any text file?
+/// Bigger files are skipped.
+#[cfg(feature = "search")]
+const MEGABYTE: usize = 1_048_576;
+
 /// A struct to define rules to run fuzzy-search.
 ///
 /// Read fields' documentation for more.
+///
+/// With the `serde` feature, missing fields are deserialized
+/// with their default values, and the [`cancel`] token is never serialized.
+///
+/// [`cancel`]: #structfield.cancel
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct Rules {
     /// Maximum number of matched and fuzzed results
     /// that will remain in memory of every spawned thread
     /// until passed down to the synchronization function.
+    ///
+    /// That's the size of the batches, the handler gets:
+    /// bigger batches mean better throughput, smaller ones mean
+    /// the first results are shown sooner.
     pub thread_local_results_cap: usize,
 
-    /// The number of bonus threads to spawn.
+    /// Number of the batches, that could wait for the handler.
+    /// When the channel is full, the worker threads wait too.
+    ///
+    /// `None` means twice the number of the worker threads.
+    pub channel_capacity: Option<usize>,
+
+    /// Number of lines, after which the worker sends its partially filled
+    /// batch, so rare results are not stuck in a worker for too long.
+    pub flush_lines: usize,
+
+    /// Time, after which the worker sends its partially filled batch.
+    ///
+    /// `None` means that only the [`flush_lines`] matters.
+    ///
+    /// [`flush_lines`]: #structfield.flush_lines
+    pub flush_interval: Option<Duration>,
+
+    /// How long the worker waits for the room in the full channel,
+    /// so the slow handler doesn't stall the search.
+    ///
+    /// The batch, that wasn't sent in time, stays with the worker,
+    /// and goes with its next batch, while the worker searches on:
+    /// the send is tried again only after the next
+    /// [`thread_local_results_cap`] results or the [`flush_interval`].
+    /// Without the [`drop_lowest_scores`], the worker, that holds twice
+    /// the [`thread_local_results_cap`], waits as long as it takes,
+    /// so the results of the whole search are not piled up.
+    ///
+    /// `None` means the worker waits as long as it takes.
+    ///
+    /// [`thread_local_results_cap`]: #structfield.thread_local_results_cap
+    /// [`flush_interval`]: #structfield.flush_interval
+    /// [`drop_lowest_scores`]: #structfield.drop_lowest_scores
+    pub send_timeout: Option<Duration>,
+
+    /// The results, waiting for the room in the channel
+    /// (see the [`send_timeout`]), are cut to the best
+    /// [`thread_local_results_cap`] of them, once there are twice as many,
+    /// so the worker doesn't pile up the results of the whole search.
+    ///
+    /// The dropped results are counted by the [`results_dropped`].
+    ///
+    /// [`send_timeout`]: #structfield.send_timeout
+    /// [`thread_local_results_cap`]: #structfield.thread_local_results_cap
+    /// [`results_dropped`]: struct.SearchStats.html#structfield.results_dropped
+    pub drop_lowest_scores: bool,
+
+    /// The number of the searching threads.
     ///
-    /// If it is 0, the main thread will be used anyway.
+    /// By default, it's [`threads_auto`]. Use [`set_threads`]
+    /// to set it from a plain number.
     ///
     /// Fat OS threads are spawned, so there's no point
-    /// in any number bigger than `(maximum OS threads) - 1`.
+    /// in any number bigger than the maximum OS threads.
     /// Even worse, any number bigger than this will
     /// decrease performance.
+    ///
+    /// [`threads_auto`]: #method.threads_auto
+    /// [`set_threads`]: #method.set_threads
+    pub threads: NonZeroUsize,
+
+    /// The number of threads, spawned in addition to the first one.
+    ///
+    /// The old way to set the [`threads`]: if it's not 0,
+    /// `bonus_threads + 1` threads search instead of the `threads`.
+    /// The [`set_threads`] resets it to 0.
+    ///
+    /// [`threads`]: #structfield.threads
+    /// [`set_threads`]: #method.set_threads
+    #[deprecated(note = "use the `threads` field instead")]
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub bonus_threads: u8,
+
+    /// The stack size of the searching threads, in bytes;
+    /// the default one of the `std::thread` if `None`.
+    ///
+    /// The threads are named `fulf-worker-N`, so the profilers and the debuggers
+    /// know whose they are. The `rayon_spawner` uses the rayon's pool instead.
+    pub worker_stack_size: Option<usize>,
+
+    /// Run the searching threads at the lowest scheduling priority:
+    /// the idle class on Linux, the background QoS on macOS,
+    /// the background mode on Windows; nothing on other platforms.
+    ///
+    /// For the background re-index or warm-up search, that shouldn't compete
+    /// with the compiler or the editor UI. The [`rayon_spawner`]
+    /// and the [`search_stream`] don't own their threads, so they ignore it.
+    ///
+    /// [`rayon_spawner`]: struct.SpecializedAscii.html#method.rayon_spawner
+    /// [`search_stream`]: struct.SpecializedAscii.html#method.search_stream
+    pub low_priority: bool,
+
+    /// Maximum number of bytes of the loaded files and of the result batches,
+    /// that wait for the handler, summed over all the workers.
+    ///
+    /// When it's exhausted, the workers wait for the other files to be scored
+    /// and for the handler to take the batches, before loading the next file;
+    /// the files bigger than the whole budget are skipped.
+    /// The [`search_stream`] loads one file at a time, so it ignores the budget.
+    ///
+    /// `None` means there's no limit, other than the one of the [`channel_capacity`].
+    ///
+    /// The searches, that share the [`memory`] counter, share the budget too.
+    ///
+    /// [`search_stream`]: struct.SpecializedAscii.html#method.search_stream
+    /// [`channel_capacity`]: #structfield.channel_capacity
+    /// [`memory`]: #structfield.memory
+    pub max_memory_bytes: Option<usize>,
+
+    /// The counter of the bytes, that the searches with these rules hold right now.
+    ///
+    /// It's never serialized, just like the [`cancel`] token.
+    ///
+    /// [`cancel`]: #structfield.cancel
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub memory: MemoryUsage,
+
+    /// Maximum number of bytes in any line: longer lines are not searched.
+    ///
+    /// The speed of line-fuzzing is non-linear, thus lines too big
+    /// can slow down the task significantly, and such lines are rarely
+    /// a code: texts, generated or minified files and the like.
+    /// The needle can't be longer than this either, see [`check_needle`].
+    ///
+    /// [`check_needle`]: #method.check_needle
+    pub max_line_len: usize,
+
+    /// Search the start of the lines longer than the [`max_line_len`],
+    /// cut to it, instead of skipping them, so the minified files
+    /// still have the matches.
+    ///
+    /// [`max_line_len`]: #structfield.max_line_len
+    pub truncate_long_lines: bool,
+
+    /// Search the lines longer than the [`max_line_len`] by the windows
+    /// of that size, overlapping by a half, instead of skipping them.
+    ///
+    /// The result is the best-scoring window, its column is the one of
+    /// the window start; the formatters, that show the whole line,
+    /// still get it. Takes priority over the [`truncate_long_lines`].
+    /// The files, that look minified, are still skipped by the [`skip_minified`].
+    ///
+    /// [`max_line_len`]: #structfield.max_line_len
+    /// [`truncate_long_lines`]: #structfield.truncate_long_lines
+    /// [`skip_minified`]: #structfield.skip_minified
+    pub chunk_long_lines: bool,
+
+    /// Skip the files, that look minified or generated: the `.min.js`
+    /// and the like, the files with the source map comment,
+    /// and the files with too long lines on average.
+    ///
+    /// Those files are slow to search, and their results are just a noise.
+    /// The skipped ones are counted in the [`files_skipped`].
+    ///
+    /// [`files_skipped`]: struct.SearchStats.html#structfield.files_skipped
+    pub skip_minified: bool,
+
+    /// Maximum number of results, that are kept in order by the
+    /// [`sorted_spawner`]. All other results are thrown away.
+    ///
+    /// [`sorted_spawner`]: struct.SpecializedAscii.html#method.sorted_spawner
+    pub results_cap: usize,
+
+    /// Maximum number of the [`sorted_spawner`] results of one folder,
+    /// so the folders like the `locales/` don't take the whole list.
+    ///
+    /// `None` means there's no such limit.
+    ///
+    /// [`sorted_spawner`]: struct.SpecializedAscii.html#method.sorted_spawner
+    pub max_results_per_dir: Option<usize>,
+
+    /// The depth of the folders of the [`max_results_per_dir`]:
+    /// 1 means the folders right in the root, 2 are their subfolders and so on.
+    ///
+    /// [`max_results_per_dir`]: #structfield.max_results_per_dir
+    pub results_dir_depth: usize,
+
+    /// Time after which the worker threads stop reading new files.
+    ///
+    /// The results found so far are still passed to the handler,
+    /// and the search is marked as [`truncated`] in the statistics.
+    ///
+    /// `None` means there's no deadline.
+    ///
+    /// [`truncated`]: struct.SearchStats.html#structfield.truncated
+    pub deadline: Option<Duration>,
+
+    /// Globs of the files to search, relative to the root, like `src/**/*.rs`.
+    /// If there are any, all other files are skipped by the walk.
+    ///
+    /// Applied by the [`walk_builder`].
+    ///
+    /// [`walk_builder`]: #method.walk_builder
+    pub include_globs: Vec<String>,
+
+    /// Globs of the files and folders to skip, like `**/testdata/**`.
+    ///
+    /// Applied by the [`walk_builder`], win over the [`include_globs`].
+    ///
+    /// [`walk_builder`]: #method.walk_builder
+    /// [`include_globs`]: #structfield.include_globs
+    pub exclude_globs: Vec<String>,
+
+    /// Maximum depth of the walk: 1 means only the files right in the root.
+    ///
+    /// `None` means there's no limit.
+    pub max_depth: Option<usize>,
+
+    /// Walk into the symlinked folders and read the symlinked files.
+    ///
+    /// The symlink loops are detected by the walk and reported
+    /// as the [`ignore::Error::Loop`] walk errors, wrapped in the
+    /// [`ignore::Error::WithDepth`] of the folder, where the loop was found.
+    ///
+    /// [`ignore::Error::Loop`]: https://docs.rs/ignore/0.4.15/ignore/enum.Error.html#variant.Loop
+    /// [`ignore::Error::WithDepth`]: https://docs.rs/ignore/0.4.15/ignore/enum.Error.html#variant.WithDepth
+    pub follow_links: bool,
+
+    /// Sort the entries of every folder: the files go first,
+    /// then the folders, both sorted by their paths.
+    ///
+    /// With the sort, the order of the cached files, and so the order
+    /// of the search, is the same on every run and on every platform.
+    /// Without it, the walk is a bit faster.
+    pub sorted_walk: bool,
+
+    /// The order of the files in the cache, and so in the search.
+    ///
+    /// See the [`cache_options`].
+    ///
+    /// [`cache_options`]: #method.cache_options
+    pub file_order: FileOrder,
+
+    /// Search the file, reached through the hardlinks or symlinks, only once.
+    ///
+    /// See the [`cache_options`].
+    ///
+    /// [`cache_options`]: #method.cache_options
+    pub dedupe_links: bool,
+
+    /// Don't cross the file system boundaries, so the walk doesn't descend
+    /// into the network mounts and the like.
+    pub same_file_system: bool,
+
+    /// Walk the hidden files and folders too, the ones that start with a dot.
+    pub search_hidden: bool,
+
+    /// Respect the `.gitignore`, the `.git/info/exclude`
+    /// and the global gitignore file.
+    pub git_ignore: bool,
+
+    /// Respect the `.ignore` files and the [`custom_ignore_filenames`].
+    ///
+    /// [`custom_ignore_filenames`]: #structfield.custom_ignore_filenames
+    pub dot_ignore: bool,
+
+    /// Names of the additional ignore files, like the `.clapignore`,
+    /// that have the `.gitignore` syntax and the higher priority.
+    pub custom_ignore_filenames: Vec<String>,
+
+    /// Respect the ignore files of the folders above the root.
+    pub parent_ignores: bool,
+
+    /// The results have the absolute paths of the files,
+    /// instead of the paths relative to the root folder.
+    ///
+    /// The relative root is joined with the current dir.
+    pub absolute_paths: bool,
+
+    /// The results have the `/` separators instead of the `\` ones on Windows,
+    /// like many vim configurations and parsers expect.
+    ///
+    /// The `\` is a valid file name char on other systems, so it's left as is there.
+    pub forward_slashes: bool,
+
+    /// The positions of the results are the chars or the bytes of the line.
+    ///
+    /// Chars by default, see the [`PositionUnit`].
+    ///
+    /// [`PositionUnit`]: enum.PositionUnit.html
+    pub position_unit: PositionUnit,
+
+    /// What the search returns for the matched lines, see the [`SearchMode`].
+    ///
+    /// [`SearchMode`]: enum.SearchMode.html
+    pub search_mode: SearchMode,
+
+    /// Trim the whitespaces around the needle before the search,
+    /// see the [`normalize_needle`].
+    ///
+    /// [`normalize_needle`]: #method.normalize_needle
+    pub trim_needle: bool,
+
+    /// Turn every run of the whitespaces in the needle into one space
+    /// before the search, see the [`normalize_needle`]: the queries,
+    /// copied from the code, often have the stray spaces.
+    ///
+    /// [`normalize_needle`]: #method.normalize_needle
+    pub collapse_needle_spaces: bool,
+
+    /// Ignore the whitespaces of the lines and of the needle,
+    /// so the `foo(bar)` matches the `foo( bar )`: handy for the code,
+    /// formatted differently. The positions are still the ones of the line.
+    pub ignore_whitespace: bool,
+
+    /// Return the lines, that do NOT match the needle, like the `grep -v`;
+    /// those have the zero score and no positions.
+    ///
+    /// Works with every [`search_mode`]: the counts are the ones of the lines,
+    /// that don't match, and the files are the ones with such lines.
+    ///
+    /// [`search_mode`]: #structfield.search_mode
+    pub invert_match: bool,
+
+    /// The token to cancel the search from another thread.
+    ///
+    /// Just like with the [`deadline`], the results found so far
+    /// are still passed to the handler.
+    ///
+    /// [`deadline`]: #structfield.deadline
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub cancel: CancelToken,
+
+    /// How often the workers check the [`cancel`] token and the [`deadline`].
+    ///
+    /// [`cancel`]: #structfield.cancel
+    /// [`deadline`]: #structfield.deadline
+    pub cancel_check: CancelCheck,
+
+    /// Stop the search, once the [`results_cap`] of the best matches are found:
+    /// the perfect ones, or the ones with all the needle's chars consecutive
+    /// right at the start of the word or the file name.
+    ///
+    /// For the short common needles the search finishes in a fraction of the time.
+    /// The stop is checked just like the [`cancel`], and the search is marked
+    /// as [`truncated`]. The best matches in the long lines have the long trailing gaps,
+    /// so some better scored matches could be left unfound.
+    ///
+    /// [`results_cap`]: #structfield.results_cap
+    /// [`cancel`]: #structfield.cancel
+    /// [`truncated`]: struct.SearchStats.html#structfield.truncated
+    pub stop_on_best_matches: bool,
 }
 
 impl Rules {
     #[inline]
+    #[allow(deprecated)]
     pub fn new() -> Self {
         Self {
             thread_local_results_cap: 64,
-            bonus_threads: if cfg!(target_pointer_width = "64") {
-                2
+            channel_capacity: None,
+            flush_lines: 2048,
+            flush_interval: None,
+            send_timeout: None,
+            drop_lowest_scores: false,
+            max_line_len: 1024,
+            truncate_long_lines: false,
+            chunk_long_lines: false,
+            skip_minified: true,
+            results_cap: 512,
+            max_results_per_dir: None,
+            results_dir_depth: 1,
+            threads: Self::threads_auto(),
+            bonus_threads: 0,
+            worker_stack_size: None,
+            low_priority: false,
+            max_memory_bytes: None,
+            memory: MemoryUsage::new(),
+            deadline: None,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            max_depth: None,
+            follow_links: false,
+            sorted_walk: true,
+            file_order: FileOrder::Walk,
+            dedupe_links: false,
+            same_file_system: false,
+            search_hidden: false,
+            git_ignore: true,
+            dot_ignore: true,
+            custom_ignore_filenames: Vec::new(),
+            parent_ignores: true,
+            absolute_paths: false,
+            forward_slashes: false,
+            position_unit: PositionUnit::Chars,
+            search_mode: SearchMode::Lines,
+            trim_needle: false,
+            collapse_needle_spaces: false,
+            ignore_whitespace: false,
+            invert_match: false,
+            cancel: CancelToken::new(),
+            cancel_check: CancelCheck::Files,
+            stop_on_best_matches: false,
+        }
+    }
+
+    /// The number of threads to use all the available cores.
+    ///
+    /// If the number of cores is unknown, falls back to 3 threads
+    /// on 64-bit targets and 2 on others.
+    pub fn threads_auto() -> NonZeroUsize {
+        std::thread::available_parallelism().unwrap_or_else(|_| {
+            let threads = if cfg!(target_pointer_width = "64") {
+                3
             } else {
-                1
-            },
+                2
+            };
+            NonZeroUsize::new(threads).unwrap_or(ONE_THREAD)
+        })
+    }
+
+    /// The number of the searching threads: the [`threads`],
+    /// or the `bonus_threads + 1`, if the deprecated [`bonus_threads`] is set.
+    ///
+    /// [`threads`]: #structfield.threads
+    /// [`bonus_threads`]: #structfield.bonus_threads
+    #[inline]
+    #[allow(deprecated)]
+    pub fn threads_count(&self) -> usize {
+        match self.bonus_threads {
+            0 => self.threads.get(),
+            bonus_threads => bonus_threads as usize + 1,
         }
     }
+
+    /// Sets the number of the searching threads.
+    ///
+    /// 0 is clamped to 1, because at least one thread searches anyway.
+    #[inline]
+    #[allow(deprecated)]
+    pub fn set_threads(&mut self, threads: usize) -> &mut Self {
+        self.threads = NonZeroUsize::new(threads).unwrap_or(ONE_THREAD);
+        self.bonus_threads = 0;
+        self
+    }
+
+    /// The number of threads, spawned in addition to the first one,
+    /// saturated to `u8::MAX`.
+    #[deprecated(note = "use the `threads_count` instead")]
+    #[inline]
+    pub fn bonus_threads(&self) -> u8 {
+        (self.threads_count() - 1).min(u8::MAX as usize) as u8
+    }
+
+    /// Sets the number of threads, spawned in addition to the first one.
+    #[deprecated(note = "use `set_threads` instead")]
+    #[inline]
+    pub fn set_bonus_threads(&mut self, bonus_threads: u8) -> &mut Self {
+        self.set_threads(bonus_threads as usize + 1)
+    }
+
+    /// The number of bonus threads to use all the available cores.
+    #[deprecated(note = "use `threads_auto` instead")]
+    #[inline]
+    pub fn bonus_threads_auto() -> u8 {
+        (Self::threads_auto().get() - 1).min(u8::MAX as usize) as u8
+    }
 }
 
 impl Default for Rules {
@@ -49,178 +594,1410 @@ impl Default for Rules {
     }
 }
 
-#[derive(Clone)]
-pub struct SpecializedAscii<A, U>
+/// The order of the files in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum FileOrder {
+    /// The order of the walk, see the [`Rules::sorted_walk`].
+    ///
+    /// [`Rules::sorted_walk`]: struct.Rules.html#structfield.sorted_walk
+    Walk,
+    /// The most recently modified files first:
+    /// those are the files, the user is most likely looking for.
+    Modified,
+    /// The smallest files first: thousands of them are searched
+    /// before the few giant ones, so the first results come sooner.
+    Size,
+}
+
+impl Default for FileOrder {
+    #[inline]
+    fn default() -> Self {
+        FileOrder::Walk
+    }
+}
+
+/// What the search returns for the matched lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum SearchMode {
+    /// Every matched line, formatted by the formatter.
+    Lines,
+    /// One `path:count` item for every file with the matches, like the `grep -c`;
+    /// the score of the item is the number of the matched lines,
+    /// and there are no positions.
+    ///
+    /// The lines are only scored, not formatted, so it's much cheaper.
+    /// The total count is the [`lines_matched`] of the statistics.
+    ///
+    /// [`lines_matched`]: struct.SearchStats.html#structfield.lines_matched
+    Count,
+    /// The path of every file with the matches, like the `grep -l`;
+    /// the score of the item is the one of the first matched line,
+    /// and there are no positions.
+    ///
+    /// The rest of the file is skipped after its first matched line.
+    Files,
+}
+
+impl Default for SearchMode {
+    #[inline]
+    fn default() -> Self {
+        SearchMode::Lines
+    }
+}
+
+/// The algorithm of the [`SpecializedAscii`]: the score and the char positions
+/// of the matched chars of the line, the `Vec`s are its reusable storage.
+///
+/// Implemented for every such closure, see the [`matcher::LineMatcher`]
+/// for the algorithm, picked at runtime.
+///
+/// [`SpecializedAscii`]: struct.SpecializedAscii.html
+/// [`matcher::LineMatcher`]: matcher/trait.LineMatcher.html
+#[cfg(feature = "search")]
+pub trait Algo
 where
-    A: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
+    Self: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
         + Clone
         + Send
         + 'static,
-    U: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
+{
+}
+
+#[cfg(feature = "search")]
+impl<T> Algo for T where
+    T: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
         + Clone
         + Send
-        + 'static,
+        + 'static
 {
-    root_folder: Arc<str>,
+}
+
+#[cfg(feature = "search")]
+#[derive(Clone)]
+pub struct SpecializedAscii<A, U, F = DefaultFormatter>
+where
+    A: Algo,
+    U: Algo,
+{
+    root_folder: Arc<Path>,
+    path_display: PathDisplay,
+    position_unit: PositionUnit,
+    search_mode: SearchMode,
+    invert_match: bool,
+    truncate_long_lines: bool,
+    chunk_long_lines: bool,
+    skip_minified: bool,
+    ignore_whitespace: bool,
     needle: Arc<str>,
     ascii_algo: A,
     fallback_utf8_algo: U,
+    formatter: F,
+    floor: ScoreFloor,
 }
 
+#[cfg(feature = "search")]
 impl<A, U> SpecializedAscii<A, U>
 where
-    A: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
-        + Clone
-        + Send
-        + 'static,
-    U: Fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>
-        + Clone
-        + Send
-        + 'static,
+    A: Algo,
+    U: Algo,
 {
+    /// The `root_folder` is joined with the paths of the cache to open the files,
+    /// and is stripped from them in the results.
+    ///
+    /// The paths of the [`serialize_relative`] cache are relative to the root,
+    /// the paths of the [`serialize`] one already start with it.
+    ///
+    /// [`serialize_relative`]: filepath_cache/fn.serialize_relative.html
+    /// [`serialize`]: filepath_cache/fn.serialize.html
     pub fn new(
-        root_folder: Arc<str>,
+        root_folder: Arc<Path>,
         needle: Arc<str>,
         ascii_algo: A,
         fallback_utf8_algo: U,
     ) -> Self {
         Self {
             root_folder,
+            path_display: PathDisplay::default(),
+            position_unit: PositionUnit::Chars,
+            search_mode: SearchMode::Lines,
+            invert_match: false,
+            truncate_long_lines: false,
+            chunk_long_lines: false,
+            skip_minified: false,
+            ignore_whitespace: false,
             needle,
             ascii_algo,
             fallback_utf8_algo,
+            formatter: DefaultFormatter,
+            floor: ScoreFloor::default(),
+        }
+    }
+}
+
+#[cfg(feature = "search")]
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo,
+    U: Algo,
+    F: ItemFormatter + Clone + Send + 'static,
+{
+    /// Replaces the formatter of the results.
+    ///
+    /// By default the results are formatted with the [`DefaultFormatter`].
+    ///
+    /// [`DefaultFormatter`]: struct.DefaultFormatter.html
+    pub fn with_formatter<G>(self, formatter: G) -> SpecializedAscii<A, U, G>
+    where
+        G: ItemFormatter + Clone + Send + 'static,
+    {
+        self.with_output(formatter)
+    }
+
+    fn with_output<G: Output>(self, formatter: G) -> SpecializedAscii<A, U, G> {
+        SpecializedAscii {
+            root_folder: self.root_folder,
+            path_display: self.path_display,
+            position_unit: self.position_unit,
+            search_mode: self.search_mode,
+            invert_match: self.invert_match,
+            truncate_long_lines: self.truncate_long_lines,
+            chunk_long_lines: self.chunk_long_lines,
+            skip_minified: self.skip_minified,
+            ignore_whitespace: self.ignore_whitespace,
+            needle: self.needle,
+            ascii_algo: self.ascii_algo,
+            fallback_utf8_algo: self.fallback_utf8_algo,
+            formatter,
+            floor: self.floor,
         }
     }
 
     /// Spawns threads, those threads filter files from the cache.
+    ///
+    /// The results are passed to the `handler` as soon as they are received;
+    /// the progress of the search is reported to it every
+    /// 100 milliseconds (if the handler cares about it).
+    ///
+    /// Every matched line is formatted, even if the handler keeps only
+    /// the best ones: only the [`sorted_spawner`] knows the score of its
+    /// last kept result, and skips the lines, those score below it.
+    ///
+    /// Returns the statistics of all threads, summed up.
+    ///
+    /// [`sorted_spawner`]: #method.sorted_spawner
     pub fn spawner(
         self,
         cache: Arc<IndexedCache>,
         r: Rules,
-        handle_results: impl FnMut(Vec<MWP>),
-    ) -> Result<(), InvalidCache<()>> {
-        let (sx, rx) = flume::bounded((r.bonus_threads as usize + 1) * 2);
-        let mut threads = Vec::with_capacity(r.bonus_threads as usize + 1);
+        mut handler: impl HandleResults,
+    ) -> Result<SearchStats, SearchError> {
+        let stop = StopCondition::new(&r, Instant::now());
+        self.receiving_spawner(cache, r, &mut handler, stop)
+    }
+
+    /// Like the [`spawner`], but the workers put their batches
+    /// straight into the `sink`, e.g. the queue of the embedder,
+    /// without the thread, that receives them.
+    ///
+    /// The [`send_timeout`] works only for the sinks, those could wait
+    /// for the room only so long, see the [`ResultSink::send_timeout`].
+    /// The [`memory`] counter doesn't count the batches in the sink.
+    ///
+    /// [`spawner`]: #method.spawner
+    /// [`send_timeout`]: struct.Rules.html#structfield.send_timeout
+    /// [`memory`]: struct.Rules.html#structfield.memory
+    /// [`ResultSink::send_timeout`]: trait.ResultSink.html#method.send_timeout
+    pub fn sink_spawner<S>(
+        mut self,
+        cache: Arc<IndexedCache>,
+        r: Rules,
+        sink: S,
+    ) -> Result<SearchStats, SearchError>
+    where
+        S: ResultSink + Clone + 'static,
+    {
+        self.prepare(&cache, &r)?;
+        let start = Instant::now();
+        let files_processed = Arc::new(AtomicUsize::new(0));
+        let budget = MemoryBudget::new(r.max_memory_bytes, r.memory.clone());
+        let _cache_held = budget.hold_cache(&cache);
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "search",
+            threads = r.threads_count(),
+            files = cache.files_count()
+        );
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let threads = self
+            .spawn_workers(
+                cache,
+                &r,
+                sink,
+                StopCondition::new(&r, start),
+                &files_processed,
+                &budget,
+                #[cfg(feature = "tracing")]
+                &span,
+            )
+            .map_err(SpawnFailed::join)?;
+
+        Ok(join_workers(threads, start)?)
+    }
+
+    /// Like [`spawner`], but keeps the best [`results_cap`] results
+    /// in the order defined by the `strategy`.
+    ///
+    /// `display` is called only when some new results got into that list,
+    /// and it takes only those new results, along with their positions.
+    /// With the [`max_results_per_dir`], some old results could be pushed out
+    /// too, see the [`Delta::removed`].
+    ///
+    /// Returns the final list with the statistics.
+    ///
+    /// Once the list is full, the lines, those score below its last result,
+    /// are not formatted at all, if the `strategy` puts the better scores first,
+    /// see the [`Merger::floor`].
+    ///
+    /// [`spawner`]: #method.spawner
+    /// [`Merger::floor`]: ../merger/struct.Merger.html#method.floor
+    /// [`results_cap`]: struct.Rules.html#structfield.results_cap
+    /// [`max_results_per_dir`]: struct.Rules.html#structfield.max_results_per_dir
+    /// [`Delta::removed`]: ../merger/struct.Delta.html#method.removed
+    pub fn sorted_spawner(
+        mut self,
+        cache: Arc<IndexedCache>,
+        r: Rules,
+        strategy: impl SortStrategy,
+        mut display: impl FnMut(Delta<'_>),
+    ) -> Result<(Vec<MWP>, SearchStats), SearchError> {
+        let mut merger = Merger::new(r.results_cap, strategy);
+        if let Some(max) = r.max_results_per_dir {
+            merger = merger.dir_cap(max, r.results_dir_depth);
+        }
+        // Only this search raises the floor.
+        self.floor = ScoreFloor::default();
+        let floor = self.floor.clone();
+        // The folders are capped by the paths, not by the formatted results.
+        let formatter = self.formatter.clone();
+        let spec = self.with_output(WithPath(formatter));
+
+        let mut merge = |batch: Vec<(MWP, Arc<str>)>| {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::trace_span!("merge", batch = batch.len()).entered();
+            let delta = merger.merge_paths_delta(batch);
+            if delta.inserted() != 0 {
+                display(delta);
+            }
+            if let Some(score) = merger.floor() {
+                floor.raise(score);
+            }
+        };
+        let stop = StopCondition::new(&r, Instant::now());
+        let stats = spec.receiving_spawner(cache, r, &mut merge, stop)?;
+
+        Ok((merger.into_items(), stats))
+    }
+
+    /// Like the [`spawner`], but the matched lines are not formatted:
+    /// the `handler` takes the [`LineMatch`]es, those share the paths of their files.
+    ///
+    /// Only the lines are searched, the `search_mode` of the rules is ignored;
+    /// the formatter of the searcher is not used, and the memory budget doesn't
+    /// count the batches, those wait for the handler.
+    ///
+    /// [`spawner`]: #method.spawner
+    /// [`LineMatch`]: struct.LineMatch.html
+    pub fn match_spawner(
+        self,
+        cache: Arc<IndexedCache>,
+        mut r: Rules,
+        mut handler: impl FnMut(Vec<LineMatch>),
+    ) -> Result<SearchStats, SearchError> {
+        r.search_mode = SearchMode::Lines;
+        let mut spec = self.with_output(Unformatted);
+        spec.prepare(&cache, &r)?;
+        let start = Instant::now();
+        let files_processed = Arc::new(AtomicUsize::new(0));
+        let budget = MemoryBudget::new(r.max_memory_bytes, r.memory.clone());
+        let _cache_held = budget.hold_cache(&cache);
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "search",
+            threads = r.threads_count(),
+            files = cache.files_count()
+        );
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let threads_count = r.threads_count();
+        let (sx, rx) = flume::bounded(r.channel_capacity.unwrap_or(threads_count * 2));
+        let spawned = spec.spawn_workers(
+            cache,
+            &r,
+            sx,
+            StopCondition::new(&r, start),
+            &files_processed,
+            &budget,
+            #[cfg(feature = "tracing")]
+            &span,
+        );
+        let threads = match spawned {
+            Ok(threads) => threads,
+            Err(failed) => {
+                drop(rx);
+                return Err(failed.join());
+            }
+        };
+
+        // The last worker drops the last sender.
+        rx.iter().for_each(&mut handler);
+
+        Ok(join_workers(threads, start)?)
+    }
+}
+
+#[cfg(feature = "search")]
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo,
+    U: Algo,
+    F: Output,
+{
+    /// The [`spawner`] for any output of the workers;
+    /// the `stop` could be shared by several searches.
+    ///
+    /// [`spawner`]: #method.spawner
+    fn receiving_spawner<H>(
+        mut self,
+        cache: Arc<IndexedCache>,
+        r: Rules,
+        handler: &mut H,
+        stop: StopCondition,
+    ) -> Result<SearchStats, SearchError>
+    where
+        F::Item: Held,
+        H: ReceiveResults<F::Item>,
+    {
+        self.prepare(&cache, &r)?;
+        let start = Instant::now();
+        let files_total = cache.files_count();
+        let files_processed = Arc::new(AtomicUsize::new(0));
+        let budget = MemoryBudget::new(r.max_memory_bytes, r.memory.clone());
+        let _cache_held = budget.hold_cache(&cache);
+
+        let threads_count = r.threads_count();
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("search", threads = threads_count, files = files_total);
+        #[cfg(feature = "tracing")]
+        let _enter = span.enter();
+
+        let (sx, rx) = flume::bounded(r.channel_capacity.unwrap_or(threads_count * 2));
+        let sink = BudgetedSender {
+            sender: sx,
+            budget: budget.clone(),
+        };
+        let spawned = self.spawn_workers(
+            cache,
+            &r,
+            sink,
+            stop,
+            &files_processed,
+            &budget,
+            #[cfg(feature = "tracing")]
+            &span,
+        );
+        let threads = match spawned {
+            Ok(threads) => threads,
+            Err(failed) => {
+                drop(rx);
+                return Err(failed.join());
+            }
+        };
 
-        let thread_local_results_cap = r.thread_local_results_cap;
+        receive_results(rx, handler, &files_processed, files_total, &budget);
+
+        Ok(join_workers(threads, start)?)
+    }
+
+    /// Spawns the workers, the last one takes the `sink` itself,
+    /// so the sink is closed, once every worker is done.
+    ///
+    /// If one of the workers can't be spawned, the started ones are asked to stop,
+    /// see the [`SpawnFailed::join`].
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_workers<S>(
+        self,
+        cache: Arc<IndexedCache>,
+        r: &Rules,
+        sink: S,
+        stop: StopCondition,
+        files_processed: &Arc<AtomicUsize>,
+        budget: &MemoryBudget,
+        #[cfg(feature = "tracing")] span: &tracing::Span,
+    ) -> Result<Vec<Worker>, SpawnFailed>
+    where
+        S: ResultSink<F::Item> + Clone + 'static,
+    {
+        let threads_count = r.threads_count();
+        let mut threads = Vec::with_capacity(threads_count);
+
+        let flush = Flush {
+            results: r.thread_local_results_cap,
+            lines: r.flush_lines,
+            interval: r.flush_interval,
+            send_timeout: r.send_timeout,
+            drop_lowest: r.drop_lowest_scores,
+        };
+        let max_line_len = r.max_line_len;
+        let low_priority = r.low_priority;
+
+        // Every worker is the child of the search span.
+        #[cfg(feature = "tracing")]
+        let worker_span = |idx: usize| tracing::debug_span!(parent: span, "worker", idx);
+
+        let worker = |idx: usize| {
+            let builder = thread::Builder::new().name(format!("fulf-worker-{}", idx));
+            match r.worker_stack_size {
+                Some(size) => builder.stack_size(size),
+                None => builder,
+            }
+        };
 
-        for _ in 0..r.bonus_threads {
-            let t;
-            let sender = sx.clone();
+        let aborted = stop.aborted.clone();
+        for idx in 1..threads_count {
+            let sink = sink.clone();
             let self_ = self.clone();
             let cache = Arc::clone(&cache);
-            t = thread::spawn(move || self_.spawn_me(cache, sender, thread_local_results_cap));
+            let files_processed = Arc::clone(files_processed);
+            let stop = stop.clone();
+            let budget = budget.clone();
+            #[cfg(feature = "tracing")]
+            let span = worker_span(idx);
+            let spawned = worker(idx).spawn(move || {
+                #[cfg(feature = "tracing")]
+                let _enter = span.enter();
+                if low_priority {
+                    priority::lower_current_thread();
+                }
+                self_.spawn_me(
+                    cache,
+                    sink,
+                    flush,
+                    max_line_len,
+                    files_processed,
+                    stop,
+                    budget,
+                )
+            });
+            match spawned {
+                Ok(t) => threads.push(t),
+                Err(error) => return Err(SpawnFailed::new(error, threads, &aborted)),
+            }
+        }
+        {
+            let files_processed = Arc::clone(files_processed);
+            let budget = budget.clone();
+            #[cfg(feature = "tracing")]
+            let span = worker_span(threads_count);
+            let spawned = worker(threads_count).spawn(move || {
+                #[cfg(feature = "tracing")]
+                let _enter = span.enter();
+                if low_priority {
+                    priority::lower_current_thread();
+                }
+                self.spawn_me(
+                    cache,
+                    sink,
+                    flush,
+                    max_line_len,
+                    files_processed,
+                    stop,
+                    budget,
+                )
+            });
+            match spawned {
+                Ok(t) => threads.push(t),
+                Err(error) => return Err(SpawnFailed::new(error, threads, &aborted)),
+            }
+        }
 
-            threads.push(t);
+        Ok(threads)
+    }
+
+    /// Sets the options, that depend on the cache and the rules.
+    ///
+    /// The needle, that is empty once normalized, is an error: it matches every line.
+    fn prepare(&mut self, cache: &IndexedCache, r: &Rules) -> Result<(), SearchError> {
+        if let Cow::Owned(needle) = r.normalize_needle(&self.needle) {
+            self.needle = needle.into();
+        }
+        if r.ignore_whitespace && self.needle.contains(char::is_whitespace) {
+            let needle: String = self.needle.split_whitespace().collect();
+            self.needle = needle.into();
+        }
+        if self.needle.is_empty() {
+            return Err(SearchError::Needle(InvalidRules::WrongSizeNeedle(0)));
         }
-        threads.push(thread::spawn(move || {
-            self.spawn_me(cache, sx, thread_local_results_cap)
-        }));
+        self.ignore_whitespace = r.ignore_whitespace;
+        self.position_unit = r.position_unit;
+        self.search_mode = r.search_mode;
+        self.invert_match = r.invert_match;
+        self.truncate_long_lines = r.truncate_long_lines;
+        self.chunk_long_lines = r.chunk_long_lines;
+        self.skip_minified = r.skip_minified;
+        self.path_display.relative_cache = cache.is_relative();
+        self.path_display.forward_slashes = r.forward_slashes;
+        self.path_display.absolute_root = if !r.absolute_paths {
+            None
+        } else if self.root_folder.is_absolute() {
+            Some(Arc::clone(&self.root_folder))
+        } else {
+            // The root stays relative if there's no current dir.
+            Some(
+                env::current_dir()
+                    .map(|dir| dir.join(&self.root_folder).into())
+                    .unwrap_or_else(|_| Arc::clone(&self.root_folder)),
+            )
+        };
+        Ok(())
+    }
+
+    /// The path of the file in the results.
+    #[inline]
+    fn display_path<'p>(&self, filepath: &'p str) -> Cow<'p, str> {
+        self.path_display.path(filepath, &self.root_folder)
+    }
 
-        rx.iter().for_each(handle_results);
+    /// Scores the line by the algorithm of its encoding,
+    /// without its whitespaces, if those are ignored.
+    #[inline]
+    fn score_line(
+        &self,
+        encoding: Encoding,
+        line: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+        spaceless: &mut Spaceless,
+    ) -> Option<MatchWithPositions> {
+        let needle: &str = &self.needle;
+        let mut score = |line: &str| match encoding {
+            Encoding::Ascii => (self.ascii_algo)(line, needle, prealloc),
+            Encoding::Utf8 => (self.fallback_utf8_algo)(line, needle, prealloc),
+        };
+        if self.ignore_whitespace {
+            spaceless.score(line, score)
+        } else {
+            score(line)
+        }
+    }
+
+    /// Searches the text of one file, except the lines longer than `max_line_len`,
+    /// unless those are truncated or searched by the windows.
+    /// The results go to the `results` in the order of the lines,
+    /// the item of the whole file goes last, see the `SearchMode`.
+    ///
+    /// The `.gz` files are unpacked into the `scratch` first.
+    /// The file is skipped at its first line, that is not UTF-8 encoded.
+    ///
+    /// Returns `false`, if the search should stop, see the `StopCondition`.
+    #[allow(clippy::too_many_arguments)]
+    fn search_file(
+        &self,
+        filebuf: &[u8],
+        filepath: &str,
+        max_line_len: usize,
+        stop: &StopCondition,
+        stats: &mut SearchStats,
+        scratch: &mut Scratch,
+        results: &mut impl FileResults<F::Item>,
+    ) -> bool {
+        scratch.reset();
+        #[cfg(feature = "flate2")]
+        let text: &[u8] = match gzip::unpacked(filepath, filebuf, &mut scratch.unpacked) {
+            Some(text) => text,
+            None => {
+                stats.files_skipped += 1;
+                return true;
+            }
+        };
+        #[cfg(not(feature = "flate2"))]
+        let text: &[u8] = filebuf;
+
+        if self.skip_minified && minified::looks_minified(filepath, text) {
+            stats.files_skipped += 1;
+            return true;
+        }
+
+        let path: Arc<str> = self.display_path(filepath).into();
+        let prealloc = &mut scratch.matricies;
+        let spaceless = &mut scratch.spaceless;
+        let mut file_matches: usize = 0;
+        let mut file_score: Option<Score> = None;
+        // The lines since the last check of the stop condition.
+        let mut lines_unchecked: usize = 0;
+
+        // The long lines are searched by the windows.
+        let lines_len = if self.chunk_long_lines {
+            usize::MAX
+        } else {
+            max_line_len
+        };
 
-        let res = threads.into_iter().fold(Ok(()), |res, t| {
-            let other = t.join().unwrap();
+        for (line_idx, line) in ByteLines::new(text)
+            .max_len(lines_len)
+            .truncate(self.truncate_long_lines)
+            .enumerate()
+        {
+            let mut sent = results.line();
+
+            if let Some(every_lines) = stop.every_lines {
+                lines_unchecked += 1;
+                if lines_unchecked >= every_lines {
+                    lines_unchecked = 0;
+                    if stop.should_stop() {
+                        stats.truncated = true;
+                        stats.lines_matched += file_matches;
+                        return false;
+                    }
+                }
+            }
 
-            if res.is_ok() {
-                other
+            let (encoding, line) = match line {
+                Line::Ascii(line) => (Encoding::Ascii, line),
+                Line::Utf8(line) => (Encoding::Utf8, line),
+                // Skip the current file if not utf8-encoded,
+                // the results of its previous lines are already found.
+                Line::NotUtf8Line => {
+                    stats.files_skipped += 1;
+                    stats.lines_matched += file_matches;
+                    return true;
+                }
+                // Not searched, see the `Rules::max_line_len`
+                // and the `Rules::truncate_long_lines`.
+                Line::TooLong => continue,
+            };
+            stats.lines_scored += 1;
+
+            let raw_line = line;
+            let (line, skipped_chars) = if self.chunk_long_lines && line.len() > max_line_len {
+                best_window(line, max_line_len, |window| {
+                    self.score_line(encoding, window, prealloc, spaceless)
+                })
             } else {
-                res
+                (line, 0)
+            };
+
+            // One algorithm closure for both encodings,
+            // so the `apply` is instantiated only once.
+            let mut algo = |taken_line: &str| {
+                let mut matched = self.score_line(encoding, taken_line, prealloc, spaceless);
+                if self.invert_match {
+                    matched = invert(matched);
+                }
+                stop.count_best_match(taken_line, &matched);
+                if matched.is_some() {
+                    file_matches += 1;
+                }
+                matched
+            };
+            match self.search_mode {
+                SearchMode::Lines => {
+                    let f = |result| sent |= results.push(result);
+                    apply(
+                        encoding,
+                        algo,
+                        raw_line,
+                        (line, skipped_chars),
+                        &path,
+                        line_idx,
+                        self.position_unit,
+                        &self.formatter,
+                        self.floor.get(),
+                        f,
+                    );
+                }
+                // Only counted, nothing is formatted.
+                SearchMode::Count => {
+                    let _counted = algo(line);
+                }
+                SearchMode::Files => {
+                    if let Some((score, _positions)) = algo(line) {
+                        file_score = Some(score);
+                        break;
+                    }
+                }
+            }
+
+            if sent && !stop.per_file && stop.should_stop() {
+                stats.truncated = true;
+                stats.lines_matched += file_matches;
+                return false;
             }
+        }
+
+        stats.files_scanned += 1;
+        stats.lines_matched += file_matches;
+        if let Some(result) = self
+            .formatter
+            .file(self.search_mode, &path, file_matches, file_score)
+        {
+            results.push(result);
+        }
+        true
+    }
+
+    /// Reads the given files and filters them.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_me(
+        self,
+        files: Arc<IndexedCache>,
+        sink: impl ResultSink<F::Item>,
+        flush: Flush,
+        max_line_len: usize,
+        files_processed: Arc<AtomicUsize>,
+        stop: StopCondition,
+        budget: MemoryBudget,
+    ) -> Result<SearchStats, InvalidCache<()>> {
+        let root_folder: &Path = &self.root_folder;
+        let mut scratch = Scratch::default();
+        let mut batch = Batch::new(flush, &sink);
+        let mut filebuf: Vec<u8> = Vec::new();
+        let mut stats = SearchStats::default();
+
+        let mut files = files.stream_iter()?;
+        while let Some(filepath) = files.read_next()? {
+            if stop.per_file && stop.should_stop() {
+                stats.truncated = true;
+                break;
+            }
+
+            files_processed.fetch_add(1, Relaxed);
+
+            let mut file = match fs::File::open(longpath::file_path(root_folder, filepath)) {
+                Ok(file) => file,
+                Err(_) => {
+                    stats.io_errors += 1;
+                    continue;
+                }
+            };
+
+            let filesize = initial_buffer_size(&file);
+            if filesize > MEGABYTE || !budget.fits(filesize) {
+                stats.files_skipped += 1;
+                continue;
+            }
+            let _reservation = match budget.reserve(filesize, &stop) {
+                Some(reservation) => reservation,
+                None => {
+                    stats.truncated = true;
+                    break;
+                }
+            };
+
+            filebuf.clear();
+            filebuf.reserve_exact(filesize);
+            if file.read_to_end(&mut filebuf).is_err() {
+                stats.io_errors += 1;
+                continue;
+            }
+            stats.bytes_read += filebuf.len();
+
+            let go_on = self.search_file(
+                &filebuf,
+                filepath,
+                max_line_len,
+                &stop,
+                &mut stats,
+                &mut scratch,
+                &mut batch,
+            );
+            if !go_on {
+                break;
+            }
+        }
+
+        stats.results_dropped += batch.finish();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            files_scanned = stats.files_scanned,
+            lines_scored = stats.lines_scored,
+            truncated = stats.truncated,
+            "worker finished"
+        );
+        Ok(stats)
+    }
+}
+
+/// Searches the `needle` with the fzy algorithm and the default formatter.
+#[cfg(feature = "search")]
+pub fn fzy_search(
+    root_folder: Arc<Path>,
+    needle: Arc<str>,
+    cache: Arc<IndexedCache>,
+    r: Rules,
+    handler: impl HandleResults,
+) -> Result<SearchStats, SearchError> {
+    use crate::fzy_algo::{ascii, utf8};
+
+    let utf8_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+        utf8::match_and_score_with_positions(needle, line, prealloc)
+    };
+
+    if needle.is_ascii() {
+        let ascii_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+            ascii::match_and_score_with_positions(needle.as_bytes(), line.as_bytes(), prealloc)
+        };
+        SpecializedAscii::new(root_folder, needle, ascii_algo, utf8_algo).spawner(cache, r, handler)
+    } else {
+        SpecializedAscii::new(root_folder, needle, utf8_algo, utf8_algo).spawner(cache, r, handler)
+    }
+}
+
+/// Like the [`fzy_search`], but the results are pulled from the iterator,
+/// see the [`SpecializedAscii::search_iter`].
+///
+/// ```no_run
+/// use {
+///     fulf::{filepath_cache::serialize_paths, fzy_search_iter, Rules},
+///     std::{path::Path, sync::Arc},
+/// };
+///
+/// let cache = Arc::new(serialize_paths(vec!["src/main.rs", "src/lib.rs"]));
+/// let first: Vec<_> = fzy_search_iter(Path::new(".").into(), "fn".into(), cache, Rules::new())
+///     .take(10)
+///     .collect();
+/// ```
+///
+/// [`fzy_search`]: fn.fzy_search.html
+/// [`SpecializedAscii::search_iter`]: struct.SpecializedAscii.html#method.search_iter
+#[cfg(feature = "search")]
+pub fn fzy_search_iter(
+    root_folder: Arc<Path>,
+    needle: Arc<str>,
+    cache: Arc<IndexedCache>,
+    r: Rules,
+) -> SearchIter {
+    use crate::fzy_algo::{ascii, utf8};
+
+    let utf8_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+        utf8::match_and_score_with_positions(needle, line, prealloc)
+    };
+
+    if needle.is_ascii() {
+        let ascii_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+            ascii::match_and_score_with_positions(needle.as_bytes(), line.as_bytes(), prealloc)
+        };
+        SpecializedAscii::new(root_folder, needle, ascii_algo, utf8_algo).search_iter(cache, r)
+    } else {
+        SpecializedAscii::new(root_folder, needle, utf8_algo, utf8_algo).search_iter(cache, r)
+    }
+}
+
+/// The lines, that don't match, are the results of the [`Rules::invert_match`],
+/// with the zero score and no positions, and the matched ones are not.
+///
+/// [`Rules::invert_match`]: struct.Rules.html#structfield.invert_match
+#[cfg(feature = "search")]
+#[inline]
+fn invert(matched: Option<MatchWithPositions>) -> Option<MatchWithPositions> {
+    match matched {
+        Some(_matched) => None,
+        None => Some((0, Vec::new())),
+    }
+}
+
+/// The line without the whitespaces, see the [`Rules::ignore_whitespace`].
+///
+/// [`Rules::ignore_whitespace`]: struct.Rules.html#structfield.ignore_whitespace
+#[cfg(feature = "search")]
+#[derive(Debug, Default)]
+struct Spaceless {
+    line: String,
+    // The index of every char of the `line` in the original line.
+    chars: Vec<usize>,
+}
+
+#[cfg(feature = "search")]
+impl Spaceless {
+    /// Scores the line without its whitespaces,
+    /// the positions are moved back to the original line.
+    fn score(
+        &mut self,
+        line: &str,
+        algo: impl FnOnce(&str) -> Option<MatchWithPositions>,
+    ) -> Option<MatchWithPositions> {
+        self.line.clear();
+        self.chars.clear();
+        for (idx, c) in line.chars().enumerate() {
+            if !c.is_whitespace() {
+                self.line.push(c);
+                self.chars.push(idx);
+            }
+        }
+
+        let (score, mut positions) = algo(&self.line)?;
+        let chars = &self.chars;
+        positions.iter_mut().for_each(|p| *p = chars[*p]);
+        Some((score, positions))
+    }
+}
+
+/// The best-scoring window of the line longer than the `window` bytes,
+/// see the [`Rules::chunk_long_lines`]; the windows overlap by a half,
+/// so the match on the border of one window is inside the next one.
+///
+/// Returns the window with the number of chars before it,
+/// or the first window if nothing matches.
+///
+/// [`Rules::chunk_long_lines`]: struct.Rules.html#structfield.chunk_long_lines
+#[cfg(feature = "search")]
+fn best_window(
+    line: &str,
+    window: usize,
+    mut algo: impl FnMut(&str) -> Option<MatchWithPositions>,
+) -> (&str, usize) {
+    let step = (window / 2).max(1);
+    let mut best: Option<(Score, &str, usize)> = None;
+    let mut first: Option<&str> = None;
+    let mut start = 0;
+    let mut start_chars = 0;
+
+    loop {
+        let mut end = (start + window).min(line.len());
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        let current = &line[start..end];
+        first.get_or_insert(current);
+        if let Some((score, _positions)) = algo(current) {
+            if best.map_or(true, |(best_score, ..)| score > best_score) {
+                best = Some((score, current, start_chars));
+            }
+        }
+        if end == line.len() {
+            break;
+        }
+
+        let mut next = start + step;
+        while !line.is_char_boundary(next) {
+            next += 1;
+        }
+        start_chars += line[start..next].chars().count();
+        start = next;
+    }
+
+    match best {
+        Some((_score, best, skipped_chars)) => (best, skipped_chars),
+        None => (first.unwrap_or(line), 0),
+    }
+}
+
+/// The item of the whole file for the [`SearchMode::Count`]
+/// and the [`SearchMode::Files`], if the file has any matches.
+///
+/// [`SearchMode::Count`]: enum.SearchMode.html#variant.Count
+/// [`SearchMode::Files`]: enum.SearchMode.html#variant.Files
+#[cfg(feature = "search")]
+fn file_result(
+    mode: SearchMode,
+    path: &str,
+    matches: usize,
+    first_score: Option<Score>,
+) -> Option<MWP> {
+    match (mode, first_score) {
+        (SearchMode::Count, _) if matches != 0 => Some((
+            format!("{}:{}", path, matches),
+            score_from_usize(matches),
+            Box::new([]),
+        )),
+        (SearchMode::Files, Some(score)) => Some((path.to_owned(), score, Box::new([]))),
+        _ => None,
+    }
+}
+
+/// Takes the results of the file, see the `search_file`.
+#[cfg(feature = "search")]
+trait FileResults<T> {
+    /// Returns `true` if the results were sent, then the stop condition is checked.
+    fn push(&mut self, result: T) -> bool;
+
+    /// Called before every line of the file, returns `true` just like the `push`.
+    fn line(&mut self) -> bool;
+}
+
+/// The results of the pools' workers, those are sent once the file is searched.
+#[cfg(feature = "search")]
+impl<T> FileResults<T> for Vec<T> {
+    #[inline]
+    fn push(&mut self, result: T) -> bool {
+        Vec::push(self, result);
+        false
+    }
+
+    #[inline]
+    fn line(&mut self) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "search")]
+impl<'s, T: Scored, S: ResultSink<T>> FileResults<T> for Batch<'s, S, T> {
+    #[inline]
+    fn push(&mut self, result: T) -> bool {
+        Batch::push(self, result)
+    }
+
+    /// Sends the partially filled batch after the `Rules::flush_lines`,
+    /// or after the `Rules::flush_interval`, even if nothing else is found.
+    #[inline]
+    fn line(&mut self) -> bool {
+        self.lines += 1;
+        if self.lines >= self.flush.lines
+            || (self.lines % LATE_CHECK_LINES == 0 && self.flush.is_late(self.last_flush))
+        {
+            self.flush()
+        } else {
+            false
+        }
+    }
+}
+
+/// The lines between the checks of the `Rules::flush_interval`, the clock isn't free.
+#[cfg(feature = "search")]
+const LATE_CHECK_LINES: usize = 64;
+
+/// The results of the worker, those wait to be sent.
+#[cfg(feature = "search")]
+struct Batch<'s, S, T> {
+    inner: Vec<T>,
+    flush: Flush,
+    /// The lines since the last flush.
+    lines: usize,
+    last_flush: Instant,
+    sink: &'s S,
+    /// The results, dropped by the `Rules::drop_lowest_scores`.
+    dropped: usize,
+    /// The results, pushed since the send has timed out.
+    since_timeout: Option<usize>,
+}
+
+#[cfg(feature = "search")]
+impl<'s, T: Scored, S: ResultSink<T>> Batch<'s, S, T> {
+    fn new(flush: Flush, sink: &'s S) -> Self {
+        Self {
+            inner: Vec::with_capacity(flush.results),
+            flush,
+            lines: 0,
+            last_flush: Instant::now(),
+            sink,
+            dropped: 0,
+            since_timeout: None,
+        }
+    }
+
+    /// Sends the results when the buffer is full,
+    /// the partial results are sent by the lines, see the `FileResults::line`.
+    ///
+    /// Returns `true` if the previous results were sent.
+    fn push(&mut self, result: T) -> bool {
+        let sent = self.inner.len() >= self.flush.results && self.flush();
+        self.inner.push(result);
+        if let Some(pushed) = &mut self.since_timeout {
+            *pushed += 1;
+        }
+        if self.flush.drop_lowest && self.inner.len() >= 2 * self.flush.results.max(1) {
+            self.inner.sort_unstable_by_key(|r| Reverse(r.score()));
+            self.dropped += self.inner.len() - self.flush.results;
+            self.inner.truncate(self.flush.results);
+        }
+        sent
+    }
+
+    /// Sends the results, if there are any, and starts counting the lines
+    /// and the time again.
+    ///
+    /// After the timed out send, the results wait for the next full batch
+    /// or the `Rules::flush_interval`, so the slow handler doesn't make
+    /// the worker wait at every result.
+    fn flush(&mut self) -> bool {
+        self.lines = 0;
+        let retry = self.since_timeout.map_or(true, |pushed| {
+            pushed >= self.flush.results || self.flush.is_late(self.last_flush)
         });
+        if !retry {
+            return false;
+        }
+        self.last_flush = Instant::now();
+        // Only send non-empty buffers.
+        !self.inner.is_empty() && self.send()
+    }
+
+    /// Returns `false`, if the channel stayed full for the whole `send_timeout`:
+    /// then the results stay in the batch.
+    fn send(&mut self) -> bool {
+        let msg = mem::replace(&mut self.inner, Vec::with_capacity(self.flush.results));
+        // The held results are not piled up without a limit.
+        let timeout = self
+            .flush
+            .send_timeout
+            .filter(|_| self.flush.drop_lowest || msg.len() < 2 * self.flush.results.max(1));
+        match timeout {
+            Some(timeout) => match self.sink.send_timeout(msg, timeout) {
+                Err(SinkError::Full(msg)) => {
+                    self.inner = msg;
+                    self.since_timeout = Some(0);
+                    false
+                }
+                // The sink is closed, no one needs the results.
+                Ok(()) | Err(SinkError::Closed(_)) => {
+                    self.since_timeout = None;
+                    true
+                }
+            },
+            None => {
+                let _any_result = self.sink.send(msg);
+                self.since_timeout = None;
+                true
+            }
+        }
+    }
+
+    /// Sends the last batch, that could be empty or partially filled.
+    ///
+    /// Returns the number of the dropped results, those of the last batch too,
+    /// if it wasn't sent in time.
+    fn finish(mut self) -> usize {
+        if !self.inner.is_empty() && !self.send() {
+            self.dropped += self.inner.len();
+        }
+        self.dropped
+    }
+}
+
+/// When the worker sends its partially filled batch.
+#[cfg(feature = "search")]
+#[derive(Clone, Copy)]
+struct Flush {
+    results: usize,
+    lines: usize,
+    interval: Option<Duration>,
+    send_timeout: Option<Duration>,
+    drop_lowest: bool,
+}
+
+#[cfg(feature = "search")]
+impl Flush {
+    #[inline]
+    fn is_late(&self, last_flush: Instant) -> bool {
+        self.interval
+            .map_or(false, |interval| last_flush.elapsed() >= interval)
+    }
+}
+
+/// Conditions to stop the workers before all the files are read.
+#[cfg(feature = "search")]
+#[derive(Clone)]
+struct StopCondition {
+    deadline: Option<Instant>,
+    cancel: CancelToken,
+    /// Check before every file, see the `CancelCheck`.
+    per_file: bool,
+    /// Check after this number of lines.
+    every_lines: Option<usize>,
+    /// The best matches, found by all the workers, and how many of them are enough,
+    /// see the `Rules::stop_on_best_matches`.
+    best_matches: Option<(Arc<AtomicUsize>, usize)>,
+    /// Set, once the search failed: its results are of no use.
+    aborted: CancelToken,
+}
+
+#[cfg(feature = "search")]
+impl StopCondition {
+    fn new(r: &Rules, start: Instant) -> Self {
+        let (per_file, every_lines) = match r.cancel_check {
+            CancelCheck::Files => (true, None),
+            CancelCheck::Lines(lines) => (true, Some(lines.max(1))),
+            CancelCheck::Batches => (false, Some(r.flush_lines.max(1))),
+        };
+        Self {
+            deadline: r.deadline.map(|d| start + d),
+            cancel: r.cancel.clone(),
+            per_file,
+            every_lines,
+            best_matches: if r.stop_on_best_matches {
+                Some((Arc::new(AtomicUsize::new(0)), r.results_cap))
+            } else {
+                None
+            },
+            aborted: CancelToken::new(),
+        }
+    }
+
+    #[inline]
+    fn should_stop(&self) -> bool {
+        self.cancel.is_cancelled()
+            || self.aborted.is_cancelled()
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+            || self
+                .best_matches
+                .as_ref()
+                .map_or(false, |(found, enough)| found.load(Relaxed) >= *enough)
+    }
+
+    /// Counts the match of the line, if it's one of the best.
+    #[inline]
+    fn count_best_match(&self, line: &str, matched: &Option<MatchWithPositions>) {
+        if let (Some((found, _enough)), Some((score, positions))) = (&self.best_matches, matched) {
+            if is_best_match(line, *score, positions) {
+                found.fetch_add(1, Relaxed);
+            }
+        }
+    }
+}
+
+/// The worker thread, that returns its statistics.
+#[cfg(feature = "search")]
+type Worker = JoinHandle<Result<SearchStats, InvalidCache<()>>>;
+
+/// One of the workers couldn't be spawned, see the `spawn_workers`.
+#[cfg(feature = "search")]
+struct SpawnFailed {
+    error: io::Error,
+    started: Vec<Worker>,
+}
 
-        res
+#[cfg(feature = "search")]
+impl SpawnFailed {
+    /// Asks the `started` workers to stop.
+    fn new(error: io::Error, started: Vec<Worker>, aborted: &CancelToken) -> Self {
+        aborted.cancel();
+        Self { error, started }
     }
 
-    /// Reads the given files and filters them.
-    fn spawn_me(
-        self,
-        files: Arc<IndexedCache>,
-        sender: flume::Sender<Vec<MWP>>,
-        capnum: usize,
-    ) -> Result<(), InvalidCache<()>> {
-        let needle: &str = &self.needle;
-        let root_folder: &str = &self.root_folder;
+    /// Joins the started workers, those could wait for the room in the channel,
+    /// thus its receiver should be dropped first.
+    fn join(self) -> SearchError {
+        for t in self.started {
+            let _any_result = t.join();
+        }
+        SearchError::Spawn(self.error)
+    }
+}
 
-        let ascii_algo: A = self.ascii_algo;
+/// Joins the workers and sums up their statistics.
+#[cfg(feature = "search")]
+fn join_workers(threads: Vec<Worker>, start: Instant) -> Result<SearchStats, InvalidCache<()>> {
+    // The panic of one worker doesn't stop the others, all of them are joined.
+    let joined: Vec<_> = threads
+        .into_iter()
+        .map(|t| {
+            t.join()
+                .unwrap_or_else(|payload| Ok(SearchStats::from_panic(payload)))
+        })
+        .collect();
+    let res = joined
+        .into_iter()
+        .try_fold(SearchStats::default(), |mut stats, other| {
+            stats += other?;
+            Ok(stats)
+        });
 
-        let fallback_utf8_algo: U = self.fallback_utf8_algo;
+    res.map(|mut stats| {
+        stats.elapsed = start.elapsed();
+        stats
+    })
+}
 
-        let mut prealloc: (Vec<Score>, Vec<Score>) = (Vec::new(), Vec::new());
+/// The handler of the `receive_results`: the [`HandleResults`],
+/// or the merger of the results with their paths, see the `WithPath`.
+///
+/// [`HandleResults`]: trait.HandleResults.html
+#[cfg(feature = "search")]
+trait ReceiveResults<T> {
+    fn handle_results(&mut self, results: Vec<T>);
 
-        let mut inner = Vec::with_capacity(capnum);
-        let mut global_linecount: usize = 0;
-        let mut filebuf: Vec<u8> = Vec::new();
+    #[inline]
+    fn progress(&mut self, _progress: Progress) {}
+}
 
-        let mut files = files.stream_iter()?;
-        'file_loop: while let Some(filepath) = files.read_next()? {
-            if let Some(_) = fs::File::open(filepath).ok().and_then(|mut file| {
-                //x XXX: is megabyte enough for any text file?
-                const MEGABYTE: usize = 1_048_576;
-
-                let filesize = initial_buffer_size(&file);
-                if filesize > MEGABYTE {
-                    return None;
-                }
+#[cfg(feature = "search")]
+impl<H: HandleResults> ReceiveResults<MWP> for H {
+    #[inline]
+    fn handle_results(&mut self, results: Vec<MWP>) {
+        HandleResults::handle_results(self, results)
+    }
 
-                filebuf.clear();
-                filebuf.reserve_exact(filesize);
-                file.read_to_end(&mut filebuf).ok()
-            }) {
-                for (line_idx, line) in ByteLines::new(&filebuf).enumerate() {
-                    global_linecount += 1;
-
-                    // There are some mutable borrowing problems,
-                    // that this macro solves.
-                    macro_rules! apply {
-                        ($algo_name:ident, $encoding:expr, $line:expr) => {
-                            let algo =
-                                |taken_line: &str| $algo_name(taken_line, needle, &mut prealloc);
-                            let f = |result| {
-                                // Send the results when the buffer is full,
-                                // or force-send partial results after some time.
-                                if inner.len() == inner.capacity() || global_linecount >= 2048 {
-                                    global_linecount = 0;
-                                    // Only send non-empty buffers.
-                                    if !inner.is_empty() {
-                                        let msg =
-                                            mem::replace(&mut inner, Vec::with_capacity(capnum));
-                                        let _any_result = sender.send(msg);
-                                    }
-                                }
-                                inner.push(result);
-                            };
-
-                            apply($encoding, algo, $line, filepath, root_folder, line_idx, f);
-                        };
-                    }
+    #[inline]
+    fn progress(&mut self, progress: Progress) {
+        HandleResults::progress(self, progress)
+    }
+}
 
-                    match line {
-                        Line::Ascii(line) => {
-                            apply!(ascii_algo, Encoding::Ascii, line);
-                        }
-                        Line::Utf8(line) => {
-                            apply!(fallback_utf8_algo, Encoding::Utf8, line);
-                        }
-                        // Skip the current file if not utf8-encoded.
-                        Line::NotUtf8Line => continue 'file_loop,
-                    }
-                }
+#[cfg(feature = "search")]
+impl<F: FnMut(Vec<(MWP, Arc<str>)>)> ReceiveResults<(MWP, Arc<str>)> for F {
+    #[inline]
+    fn handle_results(&mut self, results: Vec<(MWP, Arc<str>)>) {
+        self(results)
+    }
+}
+
+/// Passes the results to the handler until all the senders are dropped,
+/// reports the progress every `PROGRESS_INTERVAL` and once at the end.
+#[cfg(feature = "search")]
+fn receive_results<T: Held>(
+    rx: flume::Receiver<Vec<T>>,
+    handler: &mut impl ReceiveResults<T>,
+    files_processed: &AtomicUsize,
+    files_total: usize,
+    budget: &MemoryBudget,
+) {
+    let mut matches: usize = 0;
+    let progress = |handler: &mut dyn ReceiveResults<T>, matches| {
+        handler.progress(Progress {
+            files_processed: files_processed.load(Relaxed),
+            files_total,
+            matches,
+        })
+    };
+    let mut last_progress = Instant::now();
+    loop {
+        match rx.recv_timeout(PROGRESS_INTERVAL) {
+            Ok(msg) => {
+                budget.received(&msg);
+                matches += msg.len();
+                handler.handle_results(msg);
             }
+            Err(flume::RecvTimeoutError::Timeout) => {}
+            Err(flume::RecvTimeoutError::Disconnected) => break,
         }
 
-        // The last vector could be empty or partially filled.
-        if !inner.is_empty() {
-            // Whatever is is, we will end this function's work right here anyway.
-            let _any_result = sender.send(inner);
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            last_progress = Instant::now();
+            progress(handler, matches);
         }
-
-        Ok(())
     }
+    progress(handler, matches);
 }
 
 // Copypasted from stdlib.
 /// Indicates how large a buffer to pre-allocate before reading the entire file.
+#[cfg(feature = "search")]
 fn initial_buffer_size(file: &fs::File) -> usize {
     // Allocate one extra byte so the buffer doesn't need to grow before the
     // final `read` call at the end of the file.  Don't worry about `usize`
@@ -228,43 +2005,108 @@ fn initial_buffer_size(file: &fs::File) -> usize {
     file.metadata().map(|m| m.len() as usize + 1).unwrap_or(0)
 }
 
+/// How the paths of the files are shown in the results.
+#[cfg(feature = "search")]
+#[derive(Clone, Default)]
+struct PathDisplay {
+    /// The paths of the relative cache are not stripped, even if they start with the root.
+    relative_cache: bool,
+    /// Joined with the relative paths, see the `Rules::absolute_paths`.
+    absolute_root: Option<Arc<Path>>,
+    /// See the `Rules::forward_slashes`.
+    forward_slashes: bool,
+    /// The first component of the relative paths, see the `SearchRoot::label`.
+    label: Option<Arc<str>>,
+}
+
+#[cfg(feature = "search")]
+impl PathDisplay {
+    /// The path of the file in the results: relative to the root,
+    /// or the absolute one with the `absolute_root`.
+    ///
+    /// Paths are converted lossily only if the root is not UTF-8.
+    /// The Windows extended-length prefix is removed.
+    fn path<'p>(&self, filepath: &'p str, root_folder: &Path) -> Cow<'p, str> {
+        let relative = if self.relative_cache {
+            filepath
+        } else {
+            let path = Path::new(filepath);
+            path.strip_prefix(root_folder)
+                .ok()
+                .or_else(|| {
+                    // `C:\Proj` and `c:\proj` are the same folder there.
+                    if cfg!(any(windows, target_os = "macos")) {
+                        strip_prefix_ignore_case(path, root_folder)
+                    } else {
+                        None
+                    }
+                })
+                .and_then(Path::to_str)
+                .unwrap_or(filepath)
+        };
+        let path = match (&self.absolute_root, &self.label) {
+            (Some(root), _) => Cow::Owned(root.join(relative).to_string_lossy().into_owned()),
+            (None, Some(label)) => Cow::Owned(
+                Path::new(&**label)
+                    .join(relative)
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            (None, None) => Cow::Borrowed(relative),
+        };
+        if !cfg!(windows) {
+            path
+        } else if self.forward_slashes {
+            Cow::Owned(longpath::strip_verbatim(path).replace('\\', "/"))
+        } else {
+            longpath::strip_verbatim(path)
+        }
+    }
+}
+
+/// Like the `Path::strip_prefix`, but the components are compared case-insensitively,
+/// for the case-insensitive file systems.
+#[cfg(feature = "search")]
+fn strip_prefix_ignore_case<'p>(path: &'p Path, prefix: &Path) -> Option<&'p Path> {
+    let mut components = path.components();
+    for prefix_component in prefix.components() {
+        let component = components.next()?.as_os_str().to_str()?;
+        let prefix_component = prefix_component.as_os_str().to_str()?;
+        if component != prefix_component
+            && component.to_lowercase() != prefix_component.to_lowercase()
+        {
+            return None;
+        }
+    }
+    Some(components.as_path())
+}
+
+/// The encoding of the line, which picks the algorithm and the trim.
+#[cfg(feature = "search")]
+#[derive(Clone, Copy)]
 enum Encoding {
     Ascii,
     Utf8,
 }
 
+#[cfg(feature = "search")]
 #[allow(clippy::too_many_arguments)]
-fn apply(
+fn apply<O: Output>(
     encoding: Encoding,
     mut takes_line: impl FnMut(&str) -> Option<MatchWithPositions>,
-    line: &str,
-    filepath: &str,
-    root_folder: &str,
+    raw_line: &str,
+    // The searched part of the `raw_line` and the chars before it:
+    // the window of the long line, see the `best_window`, or the whole line.
+    (line, skipped_chars): (&str, usize),
+    path: &Arc<str>,
     line_idx: usize,
-    mut f: impl FnMut(MWP),
+    unit: PositionUnit,
+    output: &O,
+    // The lower scores are not even formatted, see the `Merger::floor`.
+    floor: Score,
+    mut f: impl FnMut(O::Item),
 ) {
-    if let Some((score, pos)) = takes_line(line) {
-        let path_with_root = filepath;
-
-        let path_without_root = path_with_root
-            .get(root_folder.len()..)
-            .map(|path| {
-                path.chars()
-                    .next()
-                    .map(|ch| {
-                        if ch == MAIN_SEPARATOR {
-                            let mut buf = [0_u8; 4];
-                            let sep_len = ch.encode_utf8(&mut buf).len();
-
-                            &path[sep_len..]
-                        } else {
-                            path
-                        }
-                    })
-                    .unwrap_or(path)
-            })
-            .unwrap_or(path_with_root);
-
+    if let Some((score, pos)) = takes_line(line).filter(|(score, _pos)| *score >= floor) {
         // N.B. Cannot trim before the algorithm,
         // because this could change the result
         // (trailing or leading whitespaces are valid to search,
@@ -274,38 +2116,36 @@ fn apply(
             Encoding::Utf8 => trim_utf8_whitespace(line),
         };
 
-        let bufs = (&mut [0_u8; 20], &mut [0_u8; 20]);
-        // Humans' numbers start from 1.
-        let row = fmt_usize(1 + line_idx, bufs.0);
-        let col = fmt_usize(1 + add_col, bufs.1);
-        // Three `:` chars, plus all other chars;
-        // `row` and `len` are ascii digits, thus `len()`, not `chars().count()`.
-        let path_row_col_len = 3 + path_without_root.chars().count() + row.len() + col.len();
-        let mut pos = pos;
-        pos.iter_mut().for_each(|p| {
-            // Move right by the length of things before the line.
-            *p += path_row_col_len;
-            // Move left by the number of trimmed whitespace chars.
-            *p -= add_col;
-        });
+        let trimmed_len = match encoding {
+            Encoding::Ascii => trimmed_line.len(),
+            Encoding::Utf8 => trimmed_line.chars().count(),
+        };
 
-        f((
-            format!(
-                "{}:{row}:{col}:{line}",
-                path_without_root,
-                row = row,
-                col = col,
-                line = trimmed_line,
-            ),
+        let mut pos = pos;
+        // The whitespaces, matched around the trimmed line, are not in it;
+        // the rest are moved left by the number of trimmed chars.
+        pos.retain(|&p| p >= add_col && p < add_col + trimmed_len);
+        pos.iter_mut().for_each(|p| *p -= add_col);
+
+        let matched = MatchedLine {
+            path,
+            // Humans' numbers start from 1.
+            row: 1 + line_idx,
+            col: 1 + skipped_chars + add_col,
+            line: trimmed_line,
+            raw_line,
             score,
-            pos.into_boxed_slice(),
-        ))
+            positions: &pos,
+            unit,
+        };
+        f(output.line(&matched, path))
     }
 }
 
 /// Specialized trim function,
 /// that counts the number of chars trimmed
 /// from the start of the line.
+#[cfg(feature = "search")]
 fn trim_ascii_whitespace(line: &str) -> (&str, usize) {
     let mut iter = line.as_bytes().iter().enumerate();
 
@@ -338,6 +2178,7 @@ fn trim_ascii_whitespace(line: &str) -> (&str, usize) {
 /// Specialized trim function,
 /// that counts the number of chars trimmed
 /// from the start of the line.
+#[cfg(feature = "search")]
 fn trim_utf8_whitespace(line: &str) -> (&str, usize) {
     let mut trimmed_start: usize = 0;
     let line = line.trim_start_matches(|c: char| {
@@ -376,7 +2217,7 @@ fn fmt_usize(u: usize, buf: &mut [u8]) -> &mut str {
 }
 
 /// More of an example, than real thing, yeah. But could be useful.
-#[cfg(test)]
+#[cfg(all(test, feature = "search"))]
 mod showcase {
     use super::*;
     use crate::filepath_cache::SerializeError;
@@ -393,12 +2234,12 @@ mod showcase {
     ///
     /// `needle` - a string to fuzzy-search.
     ///
-    /// `handle_results` - a closure, that takes the results from
-    /// busy worker threads and handles those results.
+    /// `handler` - a closure (or any other `HandleResults` implementor),
+    /// that takes the results from busy worker threads and handles those results.
     ///
     /// # Returns
     ///
-    /// Returns what `spawner` returns.
+    /// Returns the statistics of the search.
     ///
     /// # Alternatives
     ///
@@ -414,16 +2255,17 @@ mod showcase {
     pub fn default_searcher(
         path: impl AsRef<Path>,
         needle: impl AsRef<str>,
-        handle_results: impl FnMut(Vec<MWP>),
-    ) -> Result<(), SetterError> {
-        with_fzy_algo(path, needle, 1024_usize.next_power_of_two(), handle_results)
+        handler: impl HandleResults,
+    ) -> Result<SearchStats, SetterError> {
+        with_fzy_algo(path, needle, 1024_usize.next_power_of_two(), handler)
     }
 
     /// A function to use default fuzzy-search algorithm.
     ///
     /// # Returns
     ///
-    /// Return `Err` if the root path cannot be represented as a utf8.
+    /// Return `Err` if the path of any file inside the root cannot be represented
+    /// as a utf8; the root itself could be any path.
     ///
     /// # Maximum line length
     ///
@@ -446,6 +2288,9 @@ mod showcase {
     /// 4. Some very rare other reasons, like giant right-shifted branching.
     ///
     /// And in any of those cases there's probably no point in fuzzing such line.
+    /// That's the [`Rules::max_line_len`] now, so every search has this protection.
+    ///
+    /// [`Rules::max_line_len`]: ../struct.Rules.html#structfield.max_line_len
     #[inline]
     pub fn with_fzy_algo(
         path: impl AsRef<Path>,
@@ -453,9 +2298,9 @@ mod showcase {
         needle: impl AsRef<str>,
         max_line_len: usize,
 
-        handle_results: impl FnMut(Vec<MWP>),
-    ) -> Result<(), SetterError> {
-        use crate::filepath_cache::{serialize, NotUtf8};
+        handler: impl HandleResults,
+    ) -> Result<SearchStats, SetterError> {
+        use crate::filepath_cache::{serialize_relative, CacheOptions, NotUtf8};
 
         let needle = needle.as_ref();
 
@@ -464,14 +2309,22 @@ mod showcase {
         }
 
         let path = path.as_ref();
-        let root_folder = path
-            .to_str()
-            .ok_or(SetterError::Serialize(SerializeError::NonUtf8Path))?;
+        let root_folder: Arc<Path> = Arc::from(path);
 
-        let builder = ignore::WalkBuilder::new(path);
+        // The default rules have no globs, that could be invalid.
+        let builder = Rules::new().walk_builder(path).unwrap();
+        // Walk errors don't stop the serialization, they are just collected;
+        // a real frontend would probably show them to the user.
+        let mut walk_errors: Vec<ignore::Error> = Vec::new();
         // Probably, those serialization errors should be handled right there,
         // but for a test it's okay to simply return those errors to the caller.
-        let idx_cache = serialize(root_folder, builder, NotUtf8::ReturnError)?;
+        let idx_cache = serialize_relative(
+            path,
+            builder,
+            CacheOptions::default(),
+            NotUtf8::ReturnError,
+            |e| walk_errors.push(e),
+        )?;
         let idx_cache = Arc::new(idx_cache);
 
         // If you don't plan on spawning a new thread to write one
@@ -483,48 +2336,37 @@ mod showcase {
         };
         write_cache(Arc::clone(&idx_cache));
 
-        let utf8_algo = move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
-            if line.len() > max_line_len {
-                None
-            } else {
-                crate::fzy_algo::utf8::match_and_score_with_positions(needle, line, prealloc)
-            }
+        let utf8_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+            crate::fzy_algo::utf8::match_and_score_with_positions(needle, line, prealloc)
         };
-        let r = Rules::new();
+        let mut r = Rules::new();
+        r.max_line_len = max_line_len;
 
         let is_ascii = needle.is_ascii();
         if is_ascii {
             // ascii
-            let ascii_algo =
-                move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
-                    if line.len() > max_line_len {
-                        None
-                    } else {
-                        crate::fzy_algo::ascii::match_and_score_with_positions(
-                            needle.as_bytes(),
-                            line.as_bytes(),
-                            prealloc,
-                        )
-                    }
-                };
-
-            let spec =
-                SpecializedAscii::new(root_folder.into(), needle.into(), ascii_algo, utf8_algo);
-            spec.spawner(idx_cache, r, handle_results).unwrap();
+            let ascii_algo = |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+                crate::fzy_algo::ascii::match_and_score_with_positions(
+                    needle.as_bytes(),
+                    line.as_bytes(),
+                    prealloc,
+                )
+            };
+
+            let spec = SpecializedAscii::new(root_folder, needle.into(), ascii_algo, utf8_algo);
+            Ok(spec.spawner(idx_cache, r, handler)?)
         } else {
             // utf8
             let unspec = SpecializedAscii::new(
-                root_folder.into(),
+                root_folder,
                 needle.into(),
                 // Just drop utf8 algorithm in both slots,
                 // and that algorithm will run for all lines.
                 utf8_algo,
                 utf8_algo,
             );
-            unspec.spawner(idx_cache, r, handle_results).unwrap();
+            Ok(unspec.spawner(idx_cache, r, handler)?)
         }
-
-        Ok(())
     }
 
     #[derive(Debug)]
@@ -533,9 +2375,14 @@ mod showcase {
         Serialize(SerializeError),
         InvalidCache,
     }
-    impl From<InvalidCache<()>> for SetterError {
-        fn from(_: InvalidCache<()>) -> Self {
-            Self::InvalidCache
+    impl From<SearchError> for SetterError {
+        fn from(e: SearchError) -> Self {
+            match e {
+                SearchError::Needle(InvalidRules::WrongSizeNeedle(len)) => {
+                    Self::WrongSizeNeedle(len)
+                }
+                _ => Self::InvalidCache,
+            }
         }
     }
     impl From<SerializeError> for SetterError {
@@ -545,60 +2392,32 @@ mod showcase {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "search"))]
 mod tests {
     use super::{showcase::*, *};
+    use crate::temp_tree::TempTree;
     use std::time::{Duration, SystemTime};
 
     #[test]
     fn basic_functionality_test() {
-        use std::{cmp::Ordering, io::Write};
-
-        fn insertion_sort_on_sorted(
-            global: &mut Vec<MWP>,
-            msg: impl IntoIterator<Item = MWP>,
-            mut cmp_by: impl FnMut(&MWP, &MWP) -> Ordering,
-        ) {
-            msg.into_iter().for_each(|x| {
-                let idx = global
-                    .binary_search_by(|probe| cmp_by(probe, &x))
-                    .unwrap_or_else(std::convert::identity);
-                global.insert(idx, x);
-            });
-        };
-
-        fn default_cmp(a: &MWP, b: &MWP) -> Ordering {
-            b.1.cmp(&a.1)
-        }
+        use crate::merger::{ByScore, Merger};
+        use std::io::Write;
 
         const YOUR_GLOBAL_CAPACITY: usize = 512;
         const YOUR_DYNAMIC_PRINTNUMBER: usize = 8;
         const DELAY: Duration = Duration::from_secs(2);
 
         macro_rules! test_init {
-            ($total: ident, $global_vec: ident, $closure_name:ident; $code:tt) => {{
-                let mut $global_vec = Vec::new();
+            ($total: ident, $merger: ident, $closure_name:ident; $code:tt) => {{
+                // If you need to collect all the items without cap,
+                // just use `usize::MAX` as the cap.
+                let mut $merger = Merger::new(YOUR_GLOBAL_CAPACITY, ByScore);
                 let mut past = SystemTime::now();
                 let mut $total: usize = 0;
 
-                let mut init_flag = true;
-
                 let $closure_name = |msg: Vec<MWP>| {
-                    // One-time capacity setter.
-                    if init_flag {
-                        init_flag = false;
-                        $global_vec.reserve(YOUR_GLOBAL_CAPACITY.saturating_sub($global_vec.len()));
-                    }
-                    // `msglen` will never be bigger than `thread_local_results_cap` from `Rules`,
-                    // so `truncate_len` could be evaluated just once:
-                    // `global_vec.capacity() - thread_local_results_cap`.
                     let msglen = msg.len();
-                    let truncate_len = $global_vec.capacity() - msglen;
-                    // If you need to collect all the items without cap,
-                    // just reserve `msglen` here instead of truncating.
-                    $global_vec.truncate(truncate_len);
-
-                    insertion_sort_on_sorted(&mut $global_vec, msg, default_cmp);
+                    $merger.merge(msg);
                     $total += msglen;
 
                     let now = SystemTime::now();
@@ -607,7 +2426,7 @@ mod tests {
                         if dur > DELAY {
                             past = now;
 
-                            let iter = $global_vec.iter().take(YOUR_DYNAMIC_PRINTNUMBER);
+                            let iter = $merger.items().iter().take(YOUR_DYNAMIC_PRINTNUMBER);
                             let stdout = std::io::stdout();
                             let mut stdout = stdout.lock();
 
@@ -629,18 +2448,937 @@ mod tests {
         let current_dir = std::env::current_dir().unwrap();
         let needle = "print";
         test_init! (
-            total, global_vec, handle_results;
+            total, merger, handle_results;
         {
-            default_searcher(current_dir.clone(), needle, handle_results).unwrap();
-            println!("Total: {}\nCapped results: {:?}", total, global_vec);
+            let stats = default_searcher(current_dir.clone(), needle, handle_results).unwrap();
+            println!("Total: {}\nCapped results: {:?}", total, merger.items());
+            assert!(stats.files_scanned > 0);
+            assert!(stats.bytes_read > 0);
         });
 
         let needle = "sоме Uпiсоdе техт";
         test_init! (
-            total, global_vec, handle_results;
+            total, merger, handle_results;
         {
-            with_fzy_algo(current_dir, needle, 1024, handle_results).unwrap();
-            println!("{:?}", global_vec);
+            let mut last_progress = None;
+            let handler = handle_results.with_progress(|p| last_progress = Some(p));
+            with_fzy_algo(current_dir, needle, 1024, handler).unwrap();
+            println!("{:?}", merger.items());
+
+            let last_progress = last_progress.unwrap();
+            assert_eq!(last_progress.files_processed, last_progress.files_total);
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn not_utf8_root_is_searched() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let tree = TempTree::new("not-utf8");
+        let name = OsStr::from_bytes(b"n\xffot");
+        tree.write(
+            Path::new(name).join("src").join("main.rs"),
+            "fn main() {}\n",
+        );
+        let root = tree.join(name);
+
+        let mut paths = Vec::new();
+        let stats = with_fzy_algo(&root, "main", 1024, |batch: Vec<MWP>| {
+            paths.extend(batch.into_iter().map(|(line, _score, _pos)| line))
+        });
+
+        assert_eq!(stats.unwrap().files_scanned, 1);
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].starts_with(&format!("src{}main.rs", std::path::MAIN_SEPARATOR)));
+    }
+
+    type Algo = fn(&str, &str, &mut (Vec<Score>, Vec<Score>)) -> Option<MatchWithPositions>;
+
+    fn utf8_algo(
+        line: &str,
+        needle: &str,
+        prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        crate::fzy_algo::utf8::match_and_score_with_positions(needle, line, prealloc)
+    }
+
+    /// Searches the files of the tree with the utf8 algorithm.
+    fn tree_searcher(
+        tree: &TempTree,
+        files: &[&str],
+        needle: &str,
+    ) -> (SpecializedAscii<Algo, Algo>, Arc<IndexedCache>) {
+        use crate::filepath_cache::serialize_paths;
+
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(tree.path().into(), needle.into(), algo, algo);
+        (spec, Arc::new(serialize_paths(files.iter().copied())))
+    }
+
+    #[test]
+    fn deadline_truncates_the_search() {
+        let tree = TempTree::new("deadline");
+        tree.write("a.rs", "fn a() {}\n")
+            .write("b.rs", "fn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs", "b.rs"], "fn");
+        let mut r = Rules::new();
+        r.deadline = Some(Duration::from_secs(0));
+
+        let stats = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap();
+        assert!(stats.truncated);
+        assert_eq!(stats.files_scanned, 0);
+    }
+
+    #[test]
+    fn workers_are_named() {
+        let tree = TempTree::new("named");
+        tree.write("a/a.rs", "fn a() {}\n")
+            .write("b/b.rs", "fn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a/a.rs", "b/b.rs"], "fn");
+        let named: Algo = |line, needle, prealloc| {
+            let name = thread::current().name().map(String::from);
+            assert!(name.unwrap_or_default().starts_with("fulf-worker-"));
+            utf8_algo(line, needle, prealloc)
+        };
+        let spec = SpecializedAscii::new(spec.root_folder, spec.needle, named, named);
+        let r = Rules::builder()
+            .threads(2)
+            .worker_stack_size(4 * 1024 * 1024)
+            .build()
+            .unwrap();
+
+        let stats = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap();
+        assert_eq!(stats.worker_panics, 0);
+        assert_eq!(stats.lines_matched, 2);
+    }
+
+    #[test]
+    fn unspawned_worker_is_an_error() {
+        let tree = TempTree::new("unspawned");
+        tree.write("a/a.rs", "fn a() {}\n")
+            .write("b/b.rs", "fn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a/a.rs", "b/b.rs"], "fn");
+        // No thread gets the stack of 64 TiB.
+        let r = Rules::builder()
+            .threads(2)
+            .worker_stack_size(1 << 46)
+            .build()
+            .unwrap();
+
+        let err = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap_err();
+        assert!(matches!(err, SearchError::Spawn(_)));
+    }
+
+    #[test]
+    fn worker_panics_are_reported() {
+        let tree = TempTree::new("panics");
+        tree.write("a/a.rs", "fn a() {}\n")
+            .write("b/b.rs", "fn b() {}\n");
+        // The folders are in the different chunks, every worker gets one and panics.
+        let (spec, cache) = tree_searcher(&tree, &["a/a.rs", "b/b.rs"], "fn");
+        let panicking: Algo = |_line, _needle, _prealloc| panic!("bad algorithm");
+        let spec = SpecializedAscii::new(spec.root_folder, spec.needle, panicking, panicking);
+        let mut r = Rules::new();
+        r.set_threads(2);
+
+        let stats = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap();
+        assert!(stats.truncated);
+        assert_eq!(stats.worker_panics, 2);
+        assert_eq!(stats.panic_message.as_deref(), Some("bad algorithm"));
+    }
+
+    #[test]
+    fn cancel_is_checked_every_lines() {
+        let tree = TempTree::new("cancel-lines");
+        tree.write("a.rs", "fn a() {}\nfn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs"], "fn");
+        let mut r = Rules::new();
+        r.cancel.cancel();
+        r.cancel_check = CancelCheck::Batches;
+        r.flush_lines = 1;
+
+        // Not checked before the file, but checked at its first line.
+        let stats = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap();
+        assert!(stats.truncated);
+        assert_eq!(stats.files_scanned, 0);
+        assert!(stats.bytes_read > 0);
+        assert_eq!((stats.lines_scored, stats.lines_matched), (0, 0));
+    }
+
+    #[test]
+    fn whitespaces_are_ignored() {
+        let substring = |line: &str| {
+            let idx = line.find("foo(bar)")?;
+            Some((1, (idx..idx + 8).collect()))
+        };
+        let mut spaceless = Spaceless::default();
+        assert_eq!(
+            spaceless.score("\tfoo( bar )", substring),
+            Some((1, vec![1, 2, 3, 4, 6, 7, 8, 10]))
+        );
+        assert_eq!(spaceless.score("foo(baz)", substring), None);
+    }
+
+    #[test]
+    fn roots_are_searched_with_labels() {
+        let tree = TempTree::new("multi-root");
+        let root = tree.path();
+        for folder in &["frontend", "backend"] {
+            tree.write(Path::new(folder).join("api.rs"), "fn call() {}\n");
+        }
+
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "call".into(), algo, algo);
+        let roots = [
+            SearchRoot::new(root.join("frontend")),
+            SearchRoot::new(root.join("backend")).label("server"),
+        ];
+        let mut results = Vec::new();
+        let stats = spec.multi_root_spawner(&roots, Rules::new(), |batch: Vec<MWP>| {
+            results.extend(batch.into_iter().map(|(item, ..)| item))
+        });
+
+        assert_eq!(stats.unwrap().files_scanned, 2);
+        let separator = std::path::MAIN_SEPARATOR;
+        assert_eq!(
+            results,
+            [
+                format!("frontend{}api.rs:1:1:fn call() {{}}", separator),
+                format!("server{}api.rs:1:1:fn call() {{}}", separator),
+            ]
+        );
+    }
+
+    #[test]
+    fn roots_share_the_best_matches() {
+        let tree = TempTree::new("multi-root-best");
+        let root = tree.path();
+        for folder in &["frontend", "backend"] {
+            tree.write(Path::new(folder).join("api.rs"), "fn call() {}\n");
+        }
+
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo, algo);
+        let roots = [
+            SearchRoot::new(root.join("frontend")),
+            SearchRoot::new(root.join("backend")),
+        ];
+        let r = Rules::builder()
+            .threads(1)
+            .results_cap(1)
+            .stop_on_best_matches(true)
+            .build()
+            .unwrap();
+
+        let stats = spec
+            .multi_root_spawner(&roots, r, |_: Vec<MWP>| ())
+            .unwrap();
+        assert!(stats.truncated);
+        assert_eq!(stats.files_scanned, 1);
+    }
+
+    #[test]
+    fn long_lines_are_searched_by_windows() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let tree = TempTree::new("long-lines");
+        let root = tree.path();
+        let minified = format!("{}needle{}\n", "a".repeat(100), "b".repeat(100));
+        tree.write("min.js", &minified);
+
+        let root_folder = root.to_str().unwrap();
+        let cache = || {
+            let cache = serialize(
+                root_folder,
+                Rules::new().walk_builder(root_folder).unwrap(),
+                NotUtf8::ReturnError,
+                |_| (),
+            )
+            .unwrap();
+            Arc::new(cache)
+        };
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "needle".into(), algo, algo);
+        let rules = |chunk: bool| {
+            Rules::builder()
+                .max_line_len(32)
+                .chunk_long_lines(chunk)
+                .build()
+                .unwrap()
+        };
+        let search = |chunk: bool| {
+            let mut results = Vec::new();
+            spec.clone()
+                .spawner(cache(), rules(chunk), |batch: Vec<MWP>| {
+                    results.extend(batch)
+                })
+                .unwrap();
+            results
+        };
+        let skipped = search(false);
+        let chunked = search(true);
+
+        // The column and the replaced text are the ones of the whole line.
+        let mut grep = Vec::new();
+        spec.clone()
+            .with_formatter(GrepFormatter)
+            .spawner(cache(), rules(true), |batch: Vec<MWP>| grep.extend(batch))
+            .unwrap();
+        assert_eq!(grep[0].0, format!("min.js:1:101:{}", minified.trim_end()));
+        let mut replaced = Vec::new();
+        spec.clone()
+            .with_formatter(ReplaceFormatter::new("[$0]"))
+            .spawner(cache(), rules(true), |batch: Vec<MWP>| {
+                replaced.extend(batch)
+            })
+            .unwrap();
+        assert!(replaced[0]
+            .0
+            .contains(&format!("{}[needle]{}", "a".repeat(100), "b".repeat(100))));
+
+        assert!(skipped.is_empty());
+        assert_eq!(chunked.len(), 1);
+        let (item, _score, positions) = &chunked[0];
+        // The column of the window start.
+        assert_eq!(item.rsplit(':').nth(1), Some("81"));
+        assert_eq!(item.rsplit(':').next().map(str::len), Some(32));
+        let matched = positions.iter().map(|&p| &item[p..=p]).collect::<String>();
+        assert_eq!(matched, "needle");
+    }
+
+    #[test]
+    fn windows_are_scored_without_whitespaces() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let tree = TempTree::new("spaceless-windows");
+        let root = tree.path();
+        let minified = format!("{}nee dle{}\n", "a".repeat(100), "b".repeat(100));
+        tree.write("min.js", &minified);
+
+        let root_folder = root.to_str().unwrap();
+        let cache = serialize(
+            root_folder,
+            Rules::new().walk_builder(root_folder).unwrap(),
+            NotUtf8::ReturnError,
+            |_| (),
+        )
+        .unwrap();
+        // Only the substring matcher needs the whitespaces to be dropped.
+        let spec = matcher::searcher(root.into(), "needle".into(), Arc::new(matcher::Substring));
+        let rules = Rules::builder()
+            .max_line_len(32)
+            .chunk_long_lines(true)
+            .ignore_whitespace(true)
+            .build()
+            .unwrap();
+        let mut results = Vec::new();
+        spec.spawner(Arc::new(cache), rules, |batch: Vec<MWP>| {
+            results.extend(batch)
+        })
+        .unwrap();
+
+        assert_eq!(results.len(), 1);
+        let (item, _score, positions) = &results[0];
+        let matched = positions.iter().map(|&p| &item[p..=p]).collect::<String>();
+        assert_eq!(matched, "needle");
+    }
+
+    #[test]
+    #[cfg(feature = "flate2")]
+    fn gz_files_are_searched() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let tree = TempTree::new("gzip");
+        let root = tree.path();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"started\npanicked at main\n").unwrap();
+        tree.write("app.log.1.gz", encoder.finish().unwrap());
+
+        let root_folder = root.to_str().unwrap();
+        let cache = serialize(
+            root_folder,
+            Rules::new().walk_builder(root_folder).unwrap(),
+            NotUtf8::ReturnError,
+            |_| (),
+        )
+        .unwrap();
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "panicked".into(), algo, algo);
+        let mut results = Vec::new();
+        let stats = spec.spawner(Arc::new(cache), Rules::new(), |batch: Vec<MWP>| {
+            results.extend(batch)
         });
+
+        assert_eq!(stats.unwrap().files_scanned, 1);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].0.starts_with("app.log.1.gz:2:"));
+    }
+
+    #[test]
+    fn files_over_the_memory_budget_are_skipped() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let tree = TempTree::new("memory-budget");
+        let root = tree.path();
+        tree.write("small.rs", "fn small() {}\n");
+        tree.write("big.rs", "fn big() {}\n".repeat(1000));
+
+        let root_folder = root.to_str().unwrap();
+        let cache = serialize(
+            root_folder,
+            Rules::new().walk_builder(root_folder).unwrap(),
+            NotUtf8::ReturnError,
+            |_| (),
+        )
+        .unwrap();
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo, algo);
+        let r = Rules::builder()
+            .threads(2)
+            .max_memory_bytes(4096)
+            .build()
+            .unwrap();
+        let stats = spec.spawner(Arc::new(cache), r, |_: Vec<MWP>| ());
+
+        let stats = stats.unwrap();
+        assert!(!stats.truncated);
+        assert_eq!(stats.files_scanned, 1);
+        assert_eq!(stats.files_skipped, 1);
+
+        let usage = MemoryUsage::new();
+        let budget = MemoryBudget::new(Some(10), usage.clone());
+        let reservation = budget.reserve(10, &StopCondition::new(&Rules::new(), Instant::now()));
+        assert_eq!(usage.file_buffers(), 10);
+        let r = Rules::new();
+        r.cancel.cancel();
+        // The exhausted budget waits until the search is cancelled.
+        assert!(budget
+            .reserve(1, &StopCondition::new(&r, Instant::now()))
+            .is_none());
+        drop(reservation);
+        assert_eq!(usage.total(), 0);
+    }
+
+    #[test]
+    fn memory_usage_is_released_after_the_search() {
+        let tree = TempTree::new("memory-usage");
+        tree.write("a.rs", "fn a() {}\n")
+            .write("b.rs", "fn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs", "b.rs"], "fn");
+        let r = Rules::new();
+        let usage = r.memory.clone();
+
+        let mut caches = Vec::new();
+        spec.spawner(cache, r, |_: Vec<MWP>| caches.push(usage.caches()))
+            .unwrap();
+        assert!(!caches.is_empty());
+        assert!(caches.iter().all(|&bytes| bytes > 0));
+        assert_eq!(usage.total(), 0);
+    }
+
+    #[test]
+    fn search_stops_on_enough_best_matches() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let tree = TempTree::new("best-matches");
+        let root = tree.path();
+        for idx in 0..4 {
+            tree.write(format!("{}.rs", idx), "    fn main() {}\n");
+        }
+
+        let root_folder = root.to_str().unwrap();
+        let cache = serialize(
+            root_folder,
+            Rules::new().walk_builder(root_folder).unwrap(),
+            NotUtf8::ReturnError,
+            |_| (),
+        )
+        .unwrap();
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo, algo);
+        let r = Rules::builder()
+            .threads(1)
+            .results_cap(2)
+            .stop_on_best_matches(true)
+            .build()
+            .unwrap();
+        let stats = spec.spawner(Arc::new(cache), r, |_: Vec<MWP>| ());
+
+        let stats = stats.unwrap();
+        assert!(stats.truncated);
+        assert_eq!(stats.files_scanned, 2);
+
+        assert!(is_best_match("    fn main()", -10, &[4, 5]));
+        assert!(!is_best_match("    fn main()", -10, &[4, 8]));
+        assert!(!is_best_match("    fn main()", -10, &[5, 6]));
+    }
+
+    #[test]
+    fn file_modes_return_one_item_per_file() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let tree = TempTree::new("count");
+        let root = tree.path();
+        tree.write("a.rs", "fn a() {}\nfn b() {}\nstruct C;\n");
+        tree.write("b.rs", "fn main() {}\n");
+        tree.write("c.rs", "struct D;\n");
+
+        let root_folder = root.to_str().unwrap();
+        // The workers take the files from the cache, so every search needs its own.
+        let cache = || {
+            let cache = serialize(
+                root_folder,
+                Rules::new().walk_builder(root_folder).unwrap(),
+                NotUtf8::ReturnError,
+                |_| (),
+            );
+            Arc::new(cache.unwrap())
+        };
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo, algo);
+        let mut r = Rules::new();
+        r.search_mode = SearchMode::Count;
+        let mut results = Vec::new();
+        let stats = spec
+            .clone()
+            .spawner(cache(), r.clone(), |batch: Vec<MWP>| results.extend(batch));
+        r.invert_match = true;
+        let mut inverted = Vec::new();
+        let inverted_stats = spec.spawner(cache(), r, |batch: Vec<MWP>| inverted.extend(batch));
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.lines_matched, 3);
+        results.sort();
+        assert_eq!(
+            results,
+            [
+                (String::from("a.rs:2"), 2, Box::new([]) as Box<[usize]>),
+                (String::from("b.rs:1"), 1, Box::new([]) as Box<[usize]>),
+            ]
+        );
+        assert_eq!(inverted_stats.unwrap().lines_matched, 2);
+        inverted.sort();
+        assert_eq!(inverted[0].0, "a.rs:1");
+        assert_eq!(inverted[1].0, "c.rs:1");
+
+        assert_eq!(
+            file_result(SearchMode::Files, "a.rs", 1, Some(-3)),
+            Some((String::from("a.rs"), -3, Box::new([]) as Box<[usize]>))
+        );
+        assert_eq!(file_result(SearchMode::Files, "c.rs", 0, None), None);
+        assert_eq!(file_result(SearchMode::Lines, "a.rs", 2, Some(-3)), None);
+    }
+
+    #[test]
+    fn line_matches_share_the_path() {
+        use crate::filepath_cache::serialize_paths;
+
+        let tree = TempTree::new("interned");
+        let root = tree.path();
+        tree.write("a.rs", "fn a() {}\n  fn b() {}\n")
+            .write("b.rs", "fn main() {}\n");
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo, algo);
+        let mut r = Rules::new();
+        // Ignored, only the lines are matched.
+        r.search_mode = SearchMode::Count;
+        let mut matches = Vec::new();
+        let cache = Arc::new(serialize_paths(vec!["a.rs", "b.rs"]));
+        let stats = spec.match_spawner(cache, r, |batch| matches.extend(batch));
+
+        assert_eq!(stats.unwrap().lines_matched, 3);
+        let mut groups = group_by_path(matches);
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(groups.len(), 2);
+        let (a, b) = (&groups[0].1, &groups[1].1);
+        assert!(Arc::ptr_eq(&a[0].path, &a[1].path));
+        assert_eq!(a[1].line(), "fn b() {}");
+        assert_eq!(a[1].raw_line(), "  fn b() {}");
+        assert_eq!(b[0].format(&DefaultFormatter).0, "b.rs:1:1:fn main() {}");
+    }
+
+    #[test]
+    fn matches_in_the_trimmed_whitespace_are_dropped() {
+        let path: Arc<str> = "a.rs".into();
+        let mut matches = Vec::new();
+        apply(
+            Encoding::Ascii,
+            // The space of the indentation, the `fn` and the trailing space are matched.
+            |_line| Some((1, vec![1, 2, 3, 11])),
+            "  fn b() {} ",
+            ("  fn b() {} ", 0),
+            &path,
+            0,
+            PositionUnit::Chars,
+            &Unformatted,
+            0,
+            |matched| matches.push(matched),
+        );
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line(), "fn b() {}");
+        assert_eq!(matches[0].col, 3);
+        assert_eq!(&*matches[0].positions, [0, 1]);
+    }
+
+    #[test]
+    fn flush_lines_sends_partial_batches() {
+        let tree = TempTree::new("flush-lines");
+        tree.write("a.rs", "fn a() {}\nlet b = 1;\nfn c() {}\nfn d() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs"], "fn");
+        let mut r = Rules::new();
+        r.set_threads(1);
+        r.thread_local_results_cap = 1_000_000;
+        r.flush_lines = 1;
+        r.channel_capacity = Some(1);
+
+        let mut batches = Vec::new();
+        spec.spawner(cache, r, |batch: Vec<MWP>| batches.push(batch.len()))
+            .unwrap();
+        // Every found result is sent before the next line.
+        assert_eq!(batches, [1, 1, 1]);
+    }
+
+    #[test]
+    fn single_match_is_sent_before_the_search_ends() {
+        use crate::filepath_cache::serialize_paths;
+        use std::{sync::atomic::AtomicBool, thread};
+
+        let tree = TempTree::new("early");
+        let root = tree.path();
+        // The only match, and the lines after it.
+        let text = format!("fn main() {{}}\n{}end\n", "x\n".repeat(8));
+        tree.write("a.rs", text);
+        let received = Arc::new(AtomicBool::new(false));
+        let in_time = Arc::new(AtomicBool::new(false));
+        let algo = {
+            let (received, in_time) = (Arc::clone(&received), Arc::clone(&in_time));
+            move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+                if line == "end" {
+                    // Waits for the handler, the search isn't over yet.
+                    let waiting = Instant::now();
+                    while !received.load(Relaxed) && waiting.elapsed() < Duration::from_secs(5) {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    in_time.store(received.load(Relaxed), Relaxed);
+                }
+                utf8_algo(line, needle, prealloc)
+            }
+        };
+        let spec = SpecializedAscii::new(root.into(), "fn".into(), algo.clone(), algo);
+        let mut r = Rules::new();
+        r.set_threads(1);
+        r.thread_local_results_cap = 1_000_000;
+        r.flush_lines = 4;
+        let cache = Arc::new(serialize_paths(vec!["a.rs"]));
+        let stats = spec.spawner(cache, r, |_batch: Vec<MWP>| received.store(true, Relaxed));
+
+        assert_eq!(stats.unwrap().lines_matched, 1);
+        assert!(in_time.load(Relaxed));
+    }
+
+    #[test]
+    fn slow_handler_drops_lowest_scores() {
+        use std::sync::atomic::AtomicBool;
+
+        let tree = TempTree::new("slow-handler");
+        tree.write("a.rs", format!("{}end\n", "fn main() {}\n".repeat(16)));
+        let (spec, cache) = tree_searcher(&tree, &["a.rs"], "fn");
+        let scored = Arc::new(AtomicBool::new(false));
+        let algo = {
+            let scored = Arc::clone(&scored);
+            move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+                scored.store(line == "end", Relaxed);
+                utf8_algo(line, needle, prealloc)
+            }
+        };
+        let spec = SpecializedAscii::new(spec.root_folder, spec.needle, algo.clone(), algo);
+        let mut r = Rules::new();
+        r.set_threads(1);
+        r.thread_local_results_cap = 1;
+        r.channel_capacity = Some(1);
+        r.results_cap = usize::MAX;
+        r.send_timeout = Some(Duration::from_millis(1));
+        r.drop_lowest_scores = true;
+
+        let mut received = 0;
+        let stats = spec
+            .spawner(cache, r, |batch: Vec<MWP>| {
+                // The first batch waits for the whole file to be scored.
+                let waiting = Instant::now();
+                while received == 0
+                    && !scored.load(Relaxed)
+                    && waiting.elapsed() < Duration::from_secs(5)
+                {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                received += batch.len();
+            })
+            .unwrap();
+        // Only the first batch, the one in the channel and the last one are sent.
+        assert_eq!(stats.lines_matched, 16);
+        assert!(received <= 3);
+        assert_eq!(received + stats.results_dropped, 16);
+    }
+
+    #[test]
+    fn slow_handler_doesnt_stall_the_worker() {
+        use std::sync::atomic::AtomicBool;
+
+        let tree = TempTree::new("stalled-worker");
+        tree.write("a.rs", format!("{}end\n", "fn main() {}\n".repeat(400)));
+        let (spec, cache) = tree_searcher(&tree, &["a.rs"], "fn");
+        let scored = Arc::new(AtomicBool::new(false));
+        let algo = {
+            let scored = Arc::clone(&scored);
+            move |line: &str, needle: &str, prealloc: &mut (Vec<Score>, Vec<Score>)| {
+                scored.store(line == "end", Relaxed);
+                utf8_algo(line, needle, prealloc)
+            }
+        };
+        let spec = SpecializedAscii::new(spec.root_folder, spec.needle, algo.clone(), algo);
+        let mut r = Rules::new();
+        r.set_threads(1);
+        r.thread_local_results_cap = 50;
+        r.channel_capacity = Some(1);
+        r.results_cap = usize::MAX;
+        r.send_timeout = Some(Duration::from_millis(50));
+        r.drop_lowest_scores = true;
+
+        let mut stall = None;
+        spec.spawner(cache, r, |_batch: Vec<MWP>| {
+            // The handler takes nothing, until the whole file is scored.
+            let waiting = Instant::now();
+            while stall.is_none()
+                && !scored.load(Relaxed)
+                && waiting.elapsed() < Duration::from_secs(20)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+            stall.get_or_insert(waiting.elapsed());
+        })
+        .unwrap();
+        // Waiting at every result would take about 350 timeouts, 17 seconds.
+        assert!(stall.unwrap() < Duration::from_secs(2), "{:?}", stall);
+    }
+
+    #[test]
+    fn whitespace_needle_is_rejected() {
+        let tree = TempTree::new("whitespace-needle");
+        tree.write("a.rs", "fn a() {}\nfn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs"], "  ");
+        let r = Rules::builder().ignore_whitespace(true).build().unwrap();
+
+        // Without the whitespaces, the needle would match every line.
+        let mut received = 0;
+        let err = spec
+            .spawner(cache, r, |batch: Vec<MWP>| received += batch.len())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SearchError::Needle(InvalidRules::WrongSizeNeedle(0))
+        ));
+        assert_eq!(received, 0);
+    }
+
+    #[test]
+    fn matches_before_the_not_utf8_line_are_counted() {
+        let tree = TempTree::new("half-utf8");
+        tree.write("a.rs", &b"fn main() {}\n\xff\n"[..]);
+
+        let mut received = 0;
+        let stats = with_fzy_algo(tree.path(), "fn", 1024, |batch: Vec<MWP>| {
+            received += batch.len()
+        })
+        .unwrap();
+
+        assert_eq!((received, stats.lines_matched), (1, 1));
+    }
+
+    #[test]
+    fn zero_threads_is_one_thread() {
+        let mut r = Rules::new();
+        r.set_threads(0);
+        assert_eq!(r.threads.get(), 1);
+
+        let tree = TempTree::new("zero-threads");
+        tree.write("a.rs", "fn a() {}\n")
+            .write("b.rs", "fn b() {}\n");
+        let (spec, cache) = tree_searcher(&tree, &["a.rs", "b.rs"], "fn");
+        let stats = spec.spawner(cache, r, |_: Vec<MWP>| ()).unwrap();
+        assert_eq!(stats.files_scanned, 2);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn old_bonus_threads_still_set_the_threads() {
+        let mut r = Rules::new();
+        r.bonus_threads = 2;
+        assert_eq!(r.threads_count(), 3);
+
+        r.set_threads(1);
+        assert_eq!((r.bonus_threads, r.threads_count()), (0, 1));
+    }
+
+    #[test]
+    fn display_path_strips_the_root() {
+        let sep = std::path::MAIN_SEPARATOR;
+        let root = format!("{}proj{}", sep, sep);
+        let file = format!("{}proj{}src{}main.rs", sep, sep, sep);
+        let relative = format!("src{}main.rs", sep);
+        let full_cache = PathDisplay::default();
+
+        // The trailing separator of the root is not a part of the relative path.
+        assert_eq!(full_cache.path(&file, Path::new(&root)), relative);
+        assert_eq!(
+            full_cache.path(&file, Path::new(&root[..root.len() - 1])),
+            relative
+        );
+        // Not the same folder, just the same prefix.
+        let other = format!("{}projects{}main.rs", sep, sep);
+        assert_eq!(full_cache.path(&other, Path::new(&root)), other);
+
+        // The relative cache is never stripped.
+        let relative_cache = PathDisplay {
+            relative_cache: true,
+            ..PathDisplay::default()
+        };
+        assert_eq!(relative_cache.path(&relative, Path::new("src")), relative);
+
+        let absolute = PathDisplay {
+            absolute_root: Some(Path::new(&root).into()),
+            ..relative_cache
+        };
+        assert_eq!(absolute.path(&relative, Path::new(&root)), file);
+    }
+
+    #[test]
+    fn prefix_case_is_ignored() {
+        let path = Path::new("/Proj/Src/main.rs");
+        assert_eq!(
+            strip_prefix_ignore_case(path, Path::new("/proj/")),
+            Some(Path::new("Src/main.rs"))
+        );
+        assert_eq!(
+            strip_prefix_ignore_case(path, Path::new("/PROJ/src")),
+            Some(Path::new("main.rs"))
+        );
+        assert_eq!(strip_prefix_ignore_case(path, Path::new("/pro")), None);
+        assert_eq!(
+            strip_prefix_ignore_case(path, Path::new("/Proj/Src/main.rs/x")),
+            None
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn forward_slashes_on_windows() {
+        let forward = PathDisplay {
+            forward_slashes: true,
+            ..PathDisplay::default()
+        };
+        assert_eq!(
+            forward.path(r"\\?\C:\proj\src\main.rs", Path::new(r"\\?\C:\proj")),
+            "src/main.rs"
+        );
+        let absolute = PathDisplay {
+            absolute_root: Some(Path::new(r"\\?\C:\proj").into()),
+            ..forward
+        };
+        assert_eq!(
+            absolute.path(r"\\?\C:\proj\src\main.rs", Path::new(r"\\?\C:\proj")),
+            "C:/proj/src/main.rs"
+        );
+    }
+
+    #[test]
+    fn lines_below_the_full_list_are_not_formatted() {
+        use crate::{filepath_cache::serialize_paths, merger::ByScore};
+
+        #[derive(Clone)]
+        struct Counting(Arc<AtomicUsize>);
+
+        impl ItemFormatter for Counting {
+            fn format(&self, matched: &MatchedLine<'_>) -> MWP {
+                self.0.fetch_add(1, Relaxed);
+                DefaultFormatter.format(matched)
+            }
+        }
+
+        let tree = TempTree::new("floor");
+        let root = tree.path();
+        // The best line goes first, all the others are worse.
+        let text = format!("main()\n{}", "m_a_i_n\n".repeat(500));
+        tree.write("a.rs", text);
+        let formatted = Arc::new(AtomicUsize::new(0));
+        let algo: Algo = utf8_algo;
+        let spec = SpecializedAscii::new(root.into(), "main".into(), algo, algo)
+            .with_formatter(Counting(Arc::clone(&formatted)));
+        let mut r = Rules::new();
+        r.results_cap = 1;
+        r.threads = NonZeroUsize::new(1).unwrap();
+        r.thread_local_results_cap = 1;
+        r.channel_capacity = Some(1);
+        let cache = Arc::new(serialize_paths(vec!["a.rs"]));
+        let (items, stats) = spec.sorted_spawner(cache, r, ByScore, |_delta| {}).unwrap();
+        assert_eq!(stats.lines_matched, 501);
+        assert_eq!(items[0].0, "a.rs:1:1:main()");
+        // Only the lines, formatted before the first merge.
+        assert!(formatted.load(Relaxed) < 100);
+    }
+
+    #[test]
+    fn folders_are_capped_by_the_paths() {
+        use crate::merger::ByScore;
+
+        let tree = TempTree::new("capped-folders");
+        let files = ["locales/a.po", "locales/b", "locales/de/c.po", "src/d.rs"];
+        for file in &files {
+            tree.write(file, "fn x() {}\n");
+        }
+        let (spec, cache) = tree_searcher(&tree, &files, "fn");
+        // The quickfix items start with the `{'filename': `, not with the path.
+        let spec = spec.with_formatter(QuickfixFormatter);
+        let r = Rules::builder().max_results_per_dir(1).build().unwrap();
+
+        let (items, stats) = spec.sorted_spawner(cache, r, ByScore, |_delta| ()).unwrap();
+        assert_eq!(stats.lines_matched, 4);
+        let locales = items.iter().filter(|i| i.0.contains("locales")).count();
+        assert_eq!((items.len(), locales), (2, 1));
+    }
+
+    #[test]
+    fn sorted_spawner_keeps_order() {
+        use crate::merger::ByScore;
+
+        let tree = TempTree::new("sorted-order");
+        // The farther the `n` is, the lower the score.
+        for folder in &["a", "b"] {
+            let lines: String = (0..12)
+                .map(|gap| format!("f{}n\n", "x".repeat(gap)))
+                .collect();
+            tree.write(Path::new(folder).join("gaps.rs"), &lines);
+        }
+        let (spec, cache) = tree_searcher(&tree, &["a/gaps.rs", "b/gaps.rs"], "fn");
+        let mut r = Rules::new();
+        r.results_cap = 16;
+
+        let mut shown: Vec<MWP> = Vec::new();
+        let (items, _stats) = spec
+            .sorted_spawner(cache, r, ByScore, |delta| {
+                // Incremental rendering.
+                delta
+                    .iter()
+                    .for_each(|(pos, item)| shown.insert(pos, item.clone()));
+                shown.truncate(delta.len());
+            })
+            .unwrap();
+
+        assert_eq!(items.len(), 16);
+        assert!(items.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(shown, items);
     }
 }
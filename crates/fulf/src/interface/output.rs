@@ -0,0 +1,155 @@
+//! What the workers make of the matched lines.
+//!
+//! The traits are public only to be the bounds of the workers,
+//! the module itself is private.
+
+use {
+    super::{file_result, ItemFormatter, LineMatch, MatchedLine, SearchMode},
+    crate::fzy_algo::scoring_utils::{Score, MWP},
+    std::sync::{
+        atomic::{AtomicI32, Ordering::Relaxed},
+        Arc,
+    },
+};
+
+/// What the workers make of the matched lines:
+/// the results of the formatter, or the [`LineMatch`]es as they are.
+///
+/// [`LineMatch`]: ../struct.LineMatch.html
+pub trait Output: Clone + Send + 'static {
+    type Item: Scored + Send + 'static;
+
+    /// The `path` is the same for all the lines of the file.
+    fn line(&self, matched: &MatchedLine<'_>, path: &Arc<str>) -> Self::Item;
+
+    /// The item of the whole file, see the `file_result`.
+    fn file(
+        &self,
+        mode: SearchMode,
+        path: &str,
+        matches: usize,
+        first_score: Option<Score>,
+    ) -> Option<Self::Item>;
+}
+
+impl<F: ItemFormatter + Clone + Send + 'static> Output for F {
+    type Item = MWP;
+
+    #[inline]
+    fn line(&self, matched: &MatchedLine<'_>, _path: &Arc<str>) -> MWP {
+        self.format(matched)
+    }
+
+    #[inline]
+    fn file(
+        &self,
+        mode: SearchMode,
+        path: &str,
+        matches: usize,
+        first_score: Option<Score>,
+    ) -> Option<MWP> {
+        file_result(mode, path, matches, first_score)
+    }
+}
+
+/// The output of the [`SpecializedAscii::match_spawner`], which searches only the lines.
+///
+/// [`SpecializedAscii::match_spawner`]: ../struct.SpecializedAscii.html#method.match_spawner
+#[derive(Clone, Copy)]
+pub(super) struct Unformatted;
+
+impl Output for Unformatted {
+    type Item = LineMatch;
+
+    #[inline]
+    fn line(&self, matched: &MatchedLine<'_>, path: &Arc<str>) -> LineMatch {
+        LineMatch::new(matched, Arc::clone(path))
+    }
+
+    #[inline]
+    fn file(&self, _: SearchMode, _: &str, _: usize, _: Option<Score>) -> Option<LineMatch> {
+        None
+    }
+}
+
+/// The output of the [`SpecializedAscii::sorted_spawner`]: the formatted results
+/// with the paths of their files, so the merger caps the folders by the paths.
+///
+/// [`SpecializedAscii::sorted_spawner`]: ../struct.SpecializedAscii.html#method.sorted_spawner
+#[derive(Clone)]
+pub(super) struct WithPath<F>(pub(super) F);
+
+impl<F: ItemFormatter + Clone + Send + 'static> Output for WithPath<F> {
+    type Item = (MWP, Arc<str>);
+
+    #[inline]
+    fn line(&self, matched: &MatchedLine<'_>, path: &Arc<str>) -> Self::Item {
+        (self.0.format(matched), Arc::clone(path))
+    }
+
+    #[inline]
+    fn file(
+        &self,
+        mode: SearchMode,
+        path: &str,
+        matches: usize,
+        first_score: Option<Score>,
+    ) -> Option<Self::Item> {
+        file_result(mode, path, matches, first_score).map(|item| (item, path.into()))
+    }
+}
+
+/// The items, those the `Rules::drop_lowest_scores` compares.
+pub trait Scored {
+    fn score(&self) -> Score;
+}
+
+impl Scored for MWP {
+    #[inline]
+    fn score(&self) -> Score {
+        self.1
+    }
+}
+
+impl Scored for (MWP, Arc<str>) {
+    #[inline]
+    fn score(&self) -> Score {
+        self.0 .1
+    }
+}
+
+impl Scored for LineMatch {
+    #[inline]
+    fn score(&self) -> Score {
+        self.score
+    }
+}
+
+/// The score, below which the matched lines are not formatted:
+/// those never get into the full list of the [`sorted_spawner`].
+///
+/// Shared by the workers of the search and raised by the merger.
+///
+/// [`sorted_spawner`]: ../struct.SpecializedAscii.html#method.sorted_spawner
+#[derive(Debug, Clone)]
+pub(super) struct ScoreFloor(Arc<AtomicI32>);
+
+impl Default for ScoreFloor {
+    #[inline]
+    fn default() -> Self {
+        // No score is below it.
+        Self(Arc::new(AtomicI32::new(Score::MIN)))
+    }
+}
+
+impl ScoreFloor {
+    #[inline]
+    pub(super) fn get(&self) -> Score {
+        self.0.load(Relaxed)
+    }
+
+    #[inline]
+    pub(super) fn raise(&self, score: Score) {
+        self.0.fetch_max(score, Relaxed);
+    }
+}
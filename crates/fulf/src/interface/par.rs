@@ -0,0 +1,174 @@
+use {
+    super::{
+        longpath, memory::MemoryBudget, receive_results, scratch::Scratch, Algo, HandleResults,
+        ItemFormatter, Rules, SearchError, SearchStats, SpecializedAscii, StopCondition, MEGABYTE,
+    },
+    crate::filepath_cache::IndexedCache,
+    rayon::prelude::*,
+    std::{
+        fs, mem,
+        panic::{self, AssertUnwindSafe},
+        sync::{
+            atomic::{AtomicUsize, Ordering::Relaxed},
+            Arc,
+        },
+        time::Instant,
+    },
+};
+
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo + Sync,
+    U: Algo + Sync,
+    F: ItemFormatter + Clone + Send + Sync + 'static,
+{
+    /// Like the [`spawner`], but the files are scored on the global rayon pool,
+    /// so the applications, that already use rayon, don't oversubscribe the CPU.
+    ///
+    /// `threads` is ignored, the pool decides how many threads to use;
+    /// batches are sent only when they are full or when the pool is done with them,
+    /// the flushing rules are ignored too.
+    /// The handler is still called on the current thread.
+    ///
+    /// [`spawner`]: #method.spawner
+    pub fn rayon_spawner(
+        mut self,
+        cache: Arc<IndexedCache>,
+        r: Rules,
+        mut handler: impl HandleResults,
+    ) -> Result<SearchStats, SearchError> {
+        self.prepare(&cache, &r)?;
+        let start = Instant::now();
+        let stop = StopCondition::new(&r, start);
+        let files_total = cache.files_count();
+        let files_processed = Arc::new(AtomicUsize::new(0));
+        let budget = MemoryBudget::new(r.max_memory_bytes, r.memory.clone());
+        let _cache_held = budget.hold_cache(&cache);
+        let capnum = r.thread_local_results_cap;
+        let max_line_len = r.max_line_len;
+
+        // Broken cache should be reported before anything is spawned.
+        let mut paths: Vec<Box<str>> = Vec::with_capacity(files_total);
+        let mut files = cache.stream_iter()?;
+        while let Some(filepath) = files.read_next()? {
+            paths.push(filepath.into());
+        }
+
+        let (sx, rx) = flume::bounded(
+            r.channel_capacity
+                .unwrap_or(rayon::current_num_threads() * 2),
+        );
+        let (stats_sx, stats_rx) = flume::bounded(1);
+        {
+            let files_processed = Arc::clone(&files_processed);
+            let budget = budget.clone();
+            rayon::spawn(move || {
+                let stats = paths
+                    .par_iter()
+                    .fold(
+                        || {
+                            (
+                                sx.clone(),
+                                Vec::with_capacity(capnum),
+                                SearchStats::default(),
+                            )
+                        },
+                        |(sx, mut batch, mut stats), filepath| {
+                            if stop.should_stop() {
+                                stats.truncated = true;
+                                return (sx, batch, stats);
+                            }
+                            files_processed.fetch_add(1, Relaxed);
+
+                            let fullpath = longpath::file_path(&self.root_folder, filepath);
+                            let filesize = match fs::metadata(&fullpath) {
+                                Ok(meta) => meta.len() as usize,
+                                Err(_) => {
+                                    stats.io_errors += 1;
+                                    return (sx, batch, stats);
+                                }
+                            };
+                            if filesize > MEGABYTE || !budget.fits(filesize) {
+                                stats.files_skipped += 1;
+                                return (sx, batch, stats);
+                            }
+                            let _reservation = match budget.reserve(filesize, &stop) {
+                                Some(reservation) => reservation,
+                                None => {
+                                    stats.truncated = true;
+                                    return (sx, batch, stats);
+                                }
+                            };
+                            let filebuf = match fs::read(&fullpath) {
+                                Ok(filebuf) => filebuf,
+                                Err(_) => {
+                                    stats.io_errors += 1;
+                                    return (sx, batch, stats);
+                                }
+                            };
+                            stats.bytes_read += filebuf.len();
+
+                            let scored = panic::catch_unwind(AssertUnwindSafe(|| {
+                                let mut file_stats = SearchStats::default();
+                                let mut results = Vec::new();
+                                // The stop is checked before the next file.
+                                let _go_on = Scratch::with(|scratch| {
+                                    self.search_file(
+                                        &filebuf,
+                                        filepath,
+                                        max_line_len,
+                                        &stop,
+                                        &mut file_stats,
+                                        scratch,
+                                        &mut results,
+                                    )
+                                });
+                                (results, file_stats)
+                            }));
+                            let results = match scored {
+                                Ok((results, file_stats)) => {
+                                    stats += file_stats;
+                                    results
+                                }
+                                // Only this file is left unsearched.
+                                Err(payload) => {
+                                    stats += SearchStats::from_panic(payload);
+                                    return (sx, batch, stats);
+                                }
+                            };
+                            for result in results {
+                                batch.push(result);
+                                if batch.len() >= capnum {
+                                    let msg = mem::replace(&mut batch, Vec::with_capacity(capnum));
+                                    let _any_result = budget.send(&sx, msg);
+                                }
+                            }
+                            (sx, batch, stats)
+                        },
+                    )
+                    .map(|(sx, batch, stats)| {
+                        // The last vector could be empty or partially filled.
+                        if !batch.is_empty() {
+                            let _any_result = budget.send(&sx, batch);
+                        }
+                        stats
+                    })
+                    .reduce(SearchStats::default, |mut stats, other| {
+                        stats += other;
+                        stats
+                    });
+
+                // Disconnects the results channel, before the stats are sent.
+                drop(sx);
+                let _any_result = stats_sx.send(stats);
+            });
+        }
+
+        receive_results(rx, &mut handler, &files_processed, files_total, &budget);
+
+        // If the pool panicked, there are no stats, but the results were handled.
+        let mut stats = stats_rx.recv().unwrap_or_default();
+        stats.elapsed = start.elapsed();
+        Ok(stats)
+    }
+}
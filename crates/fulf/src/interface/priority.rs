@@ -0,0 +1,75 @@
+//! The scheduling priority of the worker threads.
+
+/// Lowers the priority of the current thread, so the background search
+/// doesn't compete with the compiler or the editor UI.
+///
+/// It's the best effort: if the platform refuses, the thread just keeps its priority.
+#[inline]
+pub(super) fn lower_current_thread() {
+    imp::lower_current_thread()
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    pub(super) fn lower_current_thread() {
+        let param = libc::sched_param { sched_priority: 0 };
+        // On Linux, the 0 is the calling thread, not the whole process.
+        //x SAFETY: the param is a valid pointer for the duration of the call.
+        let idle = unsafe { libc::sched_setscheduler(0, libc::SCHED_IDLE, &param) };
+        if idle != 0 {
+            // The idle class could be forbidden by the seccomp or the like,
+            // the highest niceness is the next best thing.
+            //x SAFETY: no pointers, and the error is just ignored.
+            let _any_result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) };
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod imp {
+    pub(super) fn lower_current_thread() {
+        //x SAFETY: no pointers, and the error is just ignored.
+        let _any_result = unsafe {
+            libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_BACKGROUND, 0)
+        };
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use winapi::um::{processthreadsapi, winbase::THREAD_MODE_BACKGROUND_BEGIN};
+
+    pub(super) fn lower_current_thread() {
+        // The background mode lowers the IO priority too.
+        //x SAFETY: the pseudo handle of the current thread is always valid.
+        let _any_result = unsafe {
+            processthreadsapi::SetThreadPriority(
+                processthreadsapi::GetCurrentThread(),
+                THREAD_MODE_BACKGROUND_BEGIN as i32,
+            )
+        };
+    }
+}
+
+// The `setpriority` of other unixes is for the whole process, not the thread.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", windows)))]
+mod imp {
+    #[inline]
+    pub(super) fn lower_current_thread() {}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_is_idle() {
+        let policy = std::thread::spawn(|| {
+            lower_current_thread();
+            unsafe { libc::sched_getscheduler(0) }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(policy, libc::SCHED_IDLE);
+    }
+}
@@ -0,0 +1,163 @@
+use {
+    super::{
+        Algo, CancelToken, HandleResults, Rules, SearchError, SearchStats, SpecializedAscii,
+        StopCondition,
+    },
+    crate::{
+        filepath_cache::{serialize_relative, NotUtf8, SerializeError},
+        ItemFormatter,
+    },
+    std::{error::Error, fmt, panic, path::Path, sync::Arc, thread, time::Instant},
+};
+
+/// One of the roots of the [`multi_root_spawner`].
+///
+/// [`multi_root_spawner`]: struct.SpecializedAscii.html#method.multi_root_spawner
+#[derive(Debug, Clone)]
+pub struct SearchRoot {
+    /// The folder to walk and search.
+    pub path: Arc<Path>,
+    /// The first component of the result paths, to tell the roots apart;
+    /// the folder name of the `path` by default.
+    ///
+    /// Not added to the absolute paths, see the [`Rules::absolute_paths`].
+    ///
+    /// [`Rules::absolute_paths`]: struct.Rules.html#structfield.absolute_paths
+    pub label: Arc<str>,
+}
+
+impl SearchRoot {
+    #[inline]
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        let path: Arc<Path> = path.as_ref().into();
+        let label = path
+            .file_name()
+            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+            .into();
+        Self { path, label }
+    }
+
+    /// Replaces the default label.
+    #[inline]
+    pub fn label(self, label: &str) -> Self {
+        Self {
+            label: label.into(),
+            ..self
+        }
+    }
+}
+
+/// The error of the [`multi_root_spawner`].
+///
+/// [`multi_root_spawner`]: struct.SpecializedAscii.html#method.multi_root_spawner
+#[derive(Debug)]
+pub enum MultiRootError {
+    /// One of the [`include_globs`] or of the [`exclude_globs`] is invalid.
+    ///
+    /// [`include_globs`]: struct.Rules.html#structfield.include_globs
+    /// [`exclude_globs`]: struct.Rules.html#structfield.exclude_globs
+    Glob(ignore::Error),
+    /// The root could not be walked.
+    Walk(SerializeError),
+    /// The root could not be searched.
+    Search(SearchError),
+}
+
+impl fmt::Display for MultiRootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultiRootError::Glob(e) => write!(f, "invalid glob: {}", e),
+            MultiRootError::Walk(e) => write!(f, "can't walk the root: {:?}", e),
+            MultiRootError::Search(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for MultiRootError {}
+
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo,
+    U: Algo,
+    F: ItemFormatter + Clone + Send + 'static,
+{
+    /// Like the [`spawner`], but searches several roots, e.g. the split
+    /// frontend and backend repositories; the `root_folder` of this searcher
+    /// is not used.
+    ///
+    /// All the roots are walked at once, with the [`walk_builder`],
+    /// then searched one after another. Every result path starts
+    /// with the [`label`] of its root. The progress is the one of the root,
+    /// that is searched now.
+    ///
+    /// The [`deadline`] and the [`stop_on_best_matches`] are of the whole search,
+    /// not of every root.
+    ///
+    /// Returns the statistics of all the roots, summed up.
+    ///
+    /// [`deadline`]: struct.Rules.html#structfield.deadline
+    /// [`stop_on_best_matches`]: struct.Rules.html#structfield.stop_on_best_matches
+    ///
+    /// [`spawner`]: #method.spawner
+    /// [`walk_builder`]: struct.Rules.html#method.walk_builder
+    /// [`label`]: struct.SearchRoot.html#structfield.label
+    pub fn multi_root_spawner(
+        self,
+        roots: &[SearchRoot],
+        r: Rules,
+        mut handler: impl HandleResults,
+    ) -> Result<SearchStats, MultiRootError> {
+        let stop = StopCondition::new(&r, Instant::now());
+        // Every glob is checked, before any walker is spawned.
+        let builders = roots
+            .iter()
+            .map(|root| r.walk_builder(&root.path).map_err(MultiRootError::Glob))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The walks left are cut short, once one of the roots fails.
+        let abort = CancelToken::new();
+        let walkers: Vec<_> = roots
+            .iter()
+            .zip(builders)
+            .map(|(root, mut builder)| {
+                let abort = abort.clone();
+                builder.filter_entry(move |_| !abort.is_cancelled());
+                let path = Arc::clone(&root.path);
+                let options = r.cache_options();
+                thread::spawn(move || {
+                    // The unreadable folders are just not searched, like in the `spawner`.
+                    serialize_relative(path, builder, options, NotUtf8::IgnorePath, |_| ())
+                })
+            })
+            .collect();
+
+        let mut stats = SearchStats::default();
+        let mut walkers = walkers.into_iter();
+        for (root, walker) in roots.iter().zip(&mut walkers) {
+            let searched = walker
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload))
+                .map_err(MultiRootError::Walk)
+                .and_then(|cache| {
+                    let mut searcher = self.clone();
+                    searcher.root_folder = Arc::clone(&root.path);
+                    searcher.path_display.label = Some(Arc::clone(&root.label));
+                    searcher
+                        .receiving_spawner(Arc::new(cache), r.clone(), &mut handler, stop.clone())
+                        .map_err(MultiRootError::Search)
+                });
+            match searched {
+                Ok(root_stats) => stats += root_stats,
+                Err(e) => {
+                    abort.cancel();
+                    walkers.for_each(|walker| {
+                        let _any_result = walker.join();
+                    });
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
@@ -0,0 +1,83 @@
+//! The temporary buffers of one worker, reused from file to file.
+
+use {
+    super::Spaceless,
+    crate::fzy_algo::scoring_utils::Score,
+    std::{cell::RefCell, mem},
+};
+
+/// The items kept in each of the matrices and the spaceless buffers after the file,
+/// the bigger ones are freed: 256 KiB of scores for the line of 4K chars and the needle of 16.
+const KEPT_SCORES: usize = 1 << 16;
+
+/// The decompressed bytes kept after the file, the bigger buffer is freed.
+#[cfg(feature = "flate2")]
+const KEPT_UNPACKED: usize = 1 << 16;
+
+thread_local! {
+    /// The scratch of the pool's worker, see the [`Scratch::with`].
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// The buffers, that live only while one file is searched:
+/// the matrices of the algorithm, the line without the whitespaces
+/// and the decompressed `.gz` file.
+///
+/// Every worker has one, so the lines of the file are scored
+/// without the allocations once the buffers are big enough.
+#[derive(Debug, Default)]
+pub(super) struct Scratch {
+    pub(super) matricies: (Vec<Score>, Vec<Score>),
+    pub(super) spaceless: Spaceless,
+    #[cfg(feature = "flate2")]
+    pub(super) unpacked: Vec<u8>,
+}
+
+impl Scratch {
+    /// Runs the `f` with the scratch of the current thread,
+    /// for the workers of the pools, which don't own the files loop.
+    #[cfg(any(feature = "tokio", feature = "rayon"))]
+    #[inline]
+    pub(super) fn with<R>(f: impl FnOnce(&mut Self) -> R) -> R {
+        SCRATCH.with(|scratch| f(&mut scratch.borrow_mut()))
+    }
+
+    /// Prepares the buffers for the next file: their capacity is kept,
+    /// unless the last file made them too big, like one huge line would.
+    pub(super) fn reset(&mut self) {
+        free_if_bigger(&mut self.matricies.0, KEPT_SCORES);
+        free_if_bigger(&mut self.matricies.1, KEPT_SCORES);
+        free_if_bigger(&mut self.spaceless.chars, KEPT_SCORES);
+        if self.spaceless.line.capacity() > KEPT_SCORES {
+            mem::take(&mut self.spaceless.line);
+        }
+        #[cfg(feature = "flate2")]
+        {
+            free_if_bigger(&mut self.unpacked, KEPT_UNPACKED);
+            self.unpacked.clear();
+        }
+    }
+}
+
+#[inline]
+fn free_if_bigger<T>(buf: &mut Vec<T>, kept: usize) {
+    if buf.capacity() > kept {
+        mem::take(buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_buffers_are_freed() {
+        let mut scratch = Scratch::default();
+        scratch.matricies.0.reserve(16);
+        scratch.matricies.1.reserve(KEPT_SCORES + 1);
+
+        scratch.reset();
+        assert!(scratch.matricies.0.capacity() >= 16);
+        assert_eq!(scratch.matricies.1.capacity(), 0);
+    }
+}
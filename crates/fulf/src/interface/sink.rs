@@ -0,0 +1,144 @@
+use {
+    crate::fzy_algo::scoring_utils::MWP,
+    std::{fmt, sync::mpsc, time::Duration},
+};
+
+/// Where the workers of the [`sink_spawner`] put their batches of the results,
+/// e.g. the queue of the embedder, so there's no thread to pass them along.
+///
+/// The items are the formatted results, unless the workers
+/// send the [`LineMatch`]es as they are.
+///
+/// Implemented for the `flume::Sender`, and for the `Sender`
+/// and the `SyncSender` of the `std::sync::mpsc`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use {
+///     fulf::{filepath_cache::serialize_paths, fzy_algo::scoring_utils::MWP, matcher, Rules},
+///     std::{
+///         path::Path,
+///         sync::{mpsc, Arc},
+///         thread,
+///     },
+/// };
+///
+/// let (sender, receiver) = mpsc::sync_channel::<Vec<MWP>>(4);
+/// let ui = thread::spawn(move || receiver.iter().flatten().count());
+///
+/// let cache = Arc::new(serialize_paths(vec!["src/main.rs", "src/lib.rs"]));
+/// let spec = matcher::searcher(Path::new(".").into(), "fn main".into(), Arc::new(matcher::Fzy));
+/// let stats = spec.sink_spawner(cache, Rules::new(), sender).unwrap();
+/// println!("{} of {} results are shown", ui.join().unwrap(), stats.lines_matched);
+/// ```
+///
+/// [`sink_spawner`]: struct.SpecializedAscii.html#method.sink_spawner
+/// [`LineMatch`]: struct.LineMatch.html
+pub trait ResultSink<T = MWP>: Send {
+    /// Puts the batch, waits for the room, if the sink is bounded.
+    ///
+    /// Returns the batch back, if the sink is closed:
+    /// no one takes the results anymore.
+    fn send(&self, batch: Vec<T>) -> Result<(), Vec<T>>;
+
+    /// Like the [`send`], but waits for the room only for the `timeout`,
+    /// see the [`Rules::send_timeout`].
+    ///
+    /// By default it waits as long as the `send` does.
+    ///
+    /// [`send`]: #tymethod.send
+    /// [`Rules::send_timeout`]: struct.Rules.html#structfield.send_timeout
+    fn send_timeout(&self, batch: Vec<T>, _timeout: Duration) -> Result<(), SinkError<T>> {
+        self.send(batch).map_err(SinkError::Closed)
+    }
+}
+
+/// The batch, that the [`ResultSink`] didn't take.
+///
+/// [`ResultSink`]: trait.ResultSink.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkError<T = MWP> {
+    /// The sink stayed full for the whole timeout.
+    Full(Vec<T>),
+    /// No one takes the results anymore.
+    Closed(Vec<T>),
+}
+
+impl<T> fmt::Display for SinkError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SinkError::Full(_) => f.write_str("the sink is full"),
+            SinkError::Closed(_) => f.write_str("the sink is closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SinkError<T> {}
+
+impl<T> From<flume::SendTimeoutError<Vec<T>>> for SinkError<T> {
+    #[inline]
+    fn from(e: flume::SendTimeoutError<Vec<T>>) -> Self {
+        match e {
+            flume::SendTimeoutError::Timeout(batch) => SinkError::Full(batch),
+            flume::SendTimeoutError::Disconnected(batch) => SinkError::Closed(batch),
+        }
+    }
+}
+
+impl<T: Send> ResultSink<T> for flume::Sender<Vec<T>> {
+    #[inline]
+    fn send(&self, batch: Vec<T>) -> Result<(), Vec<T>> {
+        flume::Sender::send(self, batch).map_err(|e| e.0)
+    }
+
+    #[inline]
+    fn send_timeout(&self, batch: Vec<T>, timeout: Duration) -> Result<(), SinkError<T>> {
+        flume::Sender::send_timeout(self, batch, timeout).map_err(SinkError::from)
+    }
+}
+
+impl<T: Send> ResultSink<T> for mpsc::Sender<Vec<T>> {
+    #[inline]
+    fn send(&self, batch: Vec<T>) -> Result<(), Vec<T>> {
+        mpsc::Sender::send(self, batch).map_err(|e| e.0)
+    }
+}
+
+impl<T: Send> ResultSink<T> for mpsc::SyncSender<Vec<T>> {
+    #[inline]
+    fn send(&self, batch: Vec<T>) -> Result<(), Vec<T>> {
+        mpsc::SyncSender::send(self, batch).map_err(|e| e.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{filepath_cache::serialize_paths, matcher, temp_tree::TempTree, Rules},
+        std::sync::{mpsc, Arc},
+    };
+
+    #[test]
+    fn workers_send_into_the_sink() {
+        let root = TempTree::new("sink");
+        root.write("a.rs", "fn main() {}\nfn mainly() {}\n")
+            .write("b.rs", "struct Main;\n");
+        let (sender, receiver) = mpsc::channel();
+        let cache = Arc::new(serialize_paths(vec!["a.rs", "b.rs"]));
+        let spec = matcher::searcher(root.path().into(), "main".into(), Arc::new(matcher::Fzy));
+        let stats = spec.sink_spawner(cache, Rules::new(), sender).unwrap();
+
+        let mut results: Vec<String> = receiver.iter().flatten().map(|r| r.0).collect();
+        results.sort();
+        assert_eq!(stats.lines_matched, 3);
+        assert_eq!(
+            results,
+            [
+                "a.rs:1:1:fn main() {}",
+                "a.rs:2:1:fn mainly() {}",
+                "b.rs:1:1:struct Main;"
+            ]
+        );
+    }
+}
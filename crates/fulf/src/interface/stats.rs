@@ -0,0 +1,162 @@
+use std::{
+    ops::AddAssign,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Statistics of a finished search.
+///
+/// Every worker thread collects its own statistics,
+/// and those are summed up when the workers are joined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchStats {
+    /// Number of files, that were read and fully scored.
+    pub files_scanned: usize,
+    /// Number of files, that were skipped, or left partially searched, because they are:
+    ///
+    /// - bigger than a megabyte;
+    /// - bigger than the memory left by the [`Rules::max_memory_bytes`];
+    /// - not UTF-8 encoded, once the first such line is read;
+    /// - minified or generated, see the [`Rules::skip_minified`];
+    /// - `.gz` files, those are broken, or bigger than a megabyte unpacked,
+    ///   with the `flate2` feature.
+    ///
+    /// [`Rules::max_memory_bytes`]: struct.Rules.html#structfield.max_memory_bytes
+    /// [`Rules::skip_minified`]: struct.Rules.html#structfield.skip_minified
+    pub files_skipped: usize,
+    /// Number of bytes read from all files, skipped ones included.
+    pub bytes_read: usize,
+    /// Number of lines, that were passed to the algorithm.
+    pub lines_scored: usize,
+    /// Number of lines, that matched the needle, in the fully scored files.
+    ///
+    /// The [`SearchMode::Files`] counts only the first matched line of every file.
+    ///
+    /// [`SearchMode::Files`]: enum.SearchMode.html#variant.Files
+    pub lines_matched: usize,
+    /// Number of files, that could not be opened or read.
+    pub io_errors: usize,
+    /// The search was stopped before all the files were read,
+    /// thus the results are partial.
+    pub truncated: bool,
+    /// Number of the workers, that panicked; their files are left unsearched,
+    /// so the search is truncated then.
+    ///
+    /// The [`rayon_spawner`] and the [`search_stream`] skip only the file,
+    /// that the worker panicked on.
+    ///
+    /// [`rayon_spawner`]: struct.SpecializedAscii.html#method.rayon_spawner
+    /// [`search_stream`]: struct.SpecializedAscii.html#method.search_stream
+    pub worker_panics: usize,
+    /// Number of the results, dropped by the [`Rules::drop_lowest_scores`],
+    /// or not sent in the [`Rules::send_timeout`] at the end of the search.
+    ///
+    /// [`Rules::drop_lowest_scores`]: struct.Rules.html#structfield.drop_lowest_scores
+    /// [`Rules::send_timeout`]: struct.Rules.html#structfield.send_timeout
+    pub results_dropped: usize,
+    /// The message of the first panic of the workers.
+    pub panic_message: Option<String>,
+    /// Wall time of the whole search.
+    ///
+    /// It's only set by the spawner, so it is zero in per-thread statistics.
+    pub elapsed: Duration,
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.files_scanned += other.files_scanned;
+        self.files_skipped += other.files_skipped;
+        self.bytes_read += other.bytes_read;
+        self.lines_scored += other.lines_scored;
+        self.lines_matched += other.lines_matched;
+        self.io_errors += other.io_errors;
+        self.truncated |= other.truncated;
+        self.worker_panics += other.worker_panics;
+        self.results_dropped += other.results_dropped;
+        if self.panic_message.is_none() {
+            self.panic_message = other.panic_message;
+        }
+        // Searches run concurrently, so the wall time is not summed.
+        self.elapsed = self.elapsed.max(other.elapsed);
+    }
+}
+
+impl SearchStats {
+    /// The statistics of the worker, that panicked with the `payload`.
+    #[cfg(feature = "search")]
+    pub(super) fn from_panic(payload: Box<dyn std::any::Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&str>() {
+                Ok(message) => (*message).to_owned(),
+                Err(_payload) => String::from("the panic without a message"),
+            },
+        };
+        Self {
+            truncated: true,
+            worker_panics: 1,
+            panic_message: Some(message),
+            ..Self::default()
+        }
+    }
+}
+
+/// The bytes, that the running searches hold right now.
+///
+/// All clones of the counter share the same numbers, just like the clones
+/// of the [`CancelToken`]: keep a clone of the [`Rules::memory`] to watch
+/// the searches, started with those rules. The numbers are the sizes of the data,
+/// not of the allocations, so the real footprint is a bit bigger.
+///
+/// [`CancelToken`]: struct.CancelToken.html
+/// [`Rules::memory`]: struct.Rules.html#structfield.memory
+#[derive(Debug, Clone, Default)]
+pub struct MemoryUsage(Arc<MemoryCounters>);
+
+#[derive(Debug, Default)]
+pub(super) struct MemoryCounters {
+    pub(super) file_buffers: AtomicUsize,
+    pub(super) result_batches: AtomicUsize,
+    pub(super) caches: AtomicUsize,
+}
+
+impl MemoryUsage {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The loaded files, that are being scored.
+    #[inline]
+    pub fn file_buffers(&self) -> usize {
+        self.0.file_buffers.load(Relaxed)
+    }
+
+    /// The result batches, that the workers sent, but the handler didn't take yet.
+    #[inline]
+    pub fn result_batches(&self) -> usize {
+        self.0.result_batches.load(Relaxed)
+    }
+
+    /// The file caches of the running searches.
+    #[inline]
+    pub fn caches(&self) -> usize {
+        self.0.caches.load(Relaxed)
+    }
+
+    /// All of the above.
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.file_buffers() + self.result_batches() + self.caches()
+    }
+
+    #[cfg(feature = "search")]
+    #[inline]
+    pub(super) fn counters(&self) -> &MemoryCounters {
+        &self.0
+    }
+}
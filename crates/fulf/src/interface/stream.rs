@@ -0,0 +1,140 @@
+use {
+    super::{
+        longpath, scratch::Scratch, Algo, ItemFormatter, Rules, SearchError, SearchStats,
+        SpecializedAscii, StopCondition, MEGABYTE,
+    },
+    crate::{filepath_cache::IndexedCache, fzy_algo::scoring_utils::MWP},
+    std::{
+        mem,
+        panic::{self, AssertUnwindSafe},
+        sync::Arc,
+        time::Instant,
+    },
+    tokio::{stream::Stream, sync::mpsc, task::JoinHandle},
+};
+
+impl<A, U, F> SpecializedAscii<A, U, F>
+where
+    A: Algo,
+    U: Algo,
+    F: ItemFormatter + Clone + Send + 'static,
+{
+    /// Async version of the [`spawner`], should be called within the tokio runtime.
+    ///
+    /// Files are read with the async IO, and scored with the `spawn_blocking`,
+    /// so there are no threads dedicated to the search.
+    /// Batches are sent as soon as `thread_local_results_cap` results are found,
+    /// the flushing rules and `threads` are ignored.
+    ///
+    /// The stream ends with the search, and the statistics
+    /// are returned by the spawned task.
+    ///
+    /// [`spawner`]: #method.spawner
+    pub fn search_stream(
+        mut self,
+        cache: Arc<IndexedCache>,
+        r: Rules,
+    ) -> (
+        impl Stream<Item = Vec<MWP>>,
+        JoinHandle<Result<SearchStats, SearchError>>,
+    ) {
+        let (mut sender, receiver) = mpsc::channel(r.channel_capacity.unwrap_or(2));
+
+        let task = tokio::spawn(async move {
+            self.prepare(&cache, &r)?;
+            let start = Instant::now();
+            let stop = StopCondition::new(&r, start);
+            let capnum = r.thread_local_results_cap;
+            let max_line_len = r.max_line_len;
+
+            let mut stats = SearchStats::default();
+            let mut batch = Vec::with_capacity(capnum);
+
+            let mut files = cache.stream_iter()?;
+            'files: while let Some(filepath) = files.read_next()? {
+                if stop.should_stop() {
+                    stats.truncated = true;
+                    break;
+                }
+
+                let fullpath = longpath::file_path(&self.root_folder, filepath);
+                match tokio::fs::metadata(&fullpath).await {
+                    Ok(meta) if meta.len() > MEGABYTE as u64 => {
+                        stats.files_skipped += 1;
+                        continue;
+                    }
+                    Ok(_meta) => {}
+                    Err(_) => {
+                        stats.io_errors += 1;
+                        continue;
+                    }
+                }
+                let filebuf = match tokio::fs::read(&fullpath).await {
+                    Ok(filebuf) => filebuf,
+                    Err(_) => {
+                        stats.io_errors += 1;
+                        continue;
+                    }
+                };
+                stats.bytes_read += filebuf.len();
+
+                let spec = self.clone();
+                let filepath: Box<str> = filepath.into();
+                let file_stop = stop.clone();
+                let scored = tokio::task::spawn_blocking(move || {
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        let mut file_stats = SearchStats::default();
+                        let mut results = Vec::new();
+                        // The stop is checked before the next file.
+                        let _go_on = Scratch::with(|scratch| {
+                            spec.search_file(
+                                &filebuf,
+                                &filepath,
+                                max_line_len,
+                                &file_stop,
+                                &mut file_stats,
+                                scratch,
+                                &mut results,
+                            )
+                        });
+                        (results, file_stats)
+                    }))
+                })
+                .await;
+
+                let (results, file_stats) = match scored {
+                    Ok(Ok(scored)) => scored,
+                    // The scoring panicked, only this file is left unsearched.
+                    Ok(Err(payload)) => {
+                        stats += SearchStats::from_panic(payload);
+                        continue;
+                    }
+                    // The runtime is shutting down.
+                    Err(_) => continue,
+                };
+                stats += file_stats;
+
+                for result in results {
+                    batch.push(result);
+                    if batch.len() >= capnum {
+                        let msg = mem::replace(&mut batch, Vec::with_capacity(capnum));
+                        // The stream was dropped, no one needs the results.
+                        if sender.send(msg).await.is_err() {
+                            stats.truncated = true;
+                            break 'files;
+                        }
+                    }
+                }
+            }
+
+            if !batch.is_empty() {
+                let _any_result = sender.send(batch).await;
+            }
+            stats.elapsed = start.elapsed();
+
+            Ok(stats)
+        });
+
+        (receiver, task)
+    }
+}
@@ -0,0 +1,217 @@
+use {
+    super::Rules,
+    crate::filepath_cache::CacheOptions,
+    ignore::{overrides::OverrideBuilder, WalkBuilder},
+    std::{cmp::Ordering, path::Path},
+};
+
+impl Rules {
+    /// The [`file_order`] and the [`dedupe_links`],
+    /// to pass to the [`serialize_ordered`] or the [`serialize_relative`].
+    ///
+    /// [`file_order`]: #structfield.file_order
+    /// [`dedupe_links`]: #structfield.dedupe_links
+    /// [`serialize_ordered`]: filepath_cache/fn.serialize_ordered.html
+    /// [`serialize_relative`]: filepath_cache/fn.serialize_relative.html
+    #[inline]
+    pub fn cache_options(&self) -> CacheOptions {
+        CacheOptions {
+            order: self.file_order,
+            dedupe_links: self.dedupe_links,
+        }
+    }
+
+    /// The walker of the `root`, that follows the walk rules:
+    /// [`include_globs`], [`exclude_globs`], [`max_depth`], [`follow_links`],
+    /// [`sorted_walk`], [`same_file_system`], [`search_hidden`]
+    /// and the ignore files toggles.
+    ///
+    /// Pass it to the [`serialize`] to make the cache of the files.
+    ///
+    /// Returns error, if any of the globs is invalid.
+    ///
+    /// [`include_globs`]: #structfield.include_globs
+    /// [`exclude_globs`]: #structfield.exclude_globs
+    /// [`max_depth`]: #structfield.max_depth
+    /// [`follow_links`]: #structfield.follow_links
+    /// [`sorted_walk`]: #structfield.sorted_walk
+    /// [`same_file_system`]: #structfield.same_file_system
+    /// [`search_hidden`]: #structfield.search_hidden
+    /// [`serialize`]: filepath_cache/fn.serialize.html
+    pub fn walk_builder(&self, root: impl AsRef<Path>) -> Result<WalkBuilder, ignore::Error> {
+        let root = root.as_ref();
+        let mut builder = WalkBuilder::new(root);
+        builder
+            .max_depth(self.max_depth)
+            .follow_links(self.follow_links)
+            .same_file_system(self.same_file_system)
+            .hidden(!self.search_hidden)
+            .git_ignore(self.git_ignore)
+            .git_exclude(self.git_ignore)
+            .git_global(self.git_ignore)
+            .ignore(self.dot_ignore)
+            .parents(self.parent_ignores);
+        if self.sorted_walk {
+            builder.sort_by_file_path(|a_path, b_path| {
+                match (a_path.is_file(), b_path.is_file()) {
+                    (true, true) | (false, false) => a_path.cmp(b_path),
+                    // Files in the current directory should always go before
+                    // anything else, so they are cached in one chunk.
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                }
+            });
+        }
+        if self.dot_ignore {
+            for name in &self.custom_ignore_filenames {
+                builder.add_custom_ignore_filename(name);
+            }
+        }
+
+        if !self.include_globs.is_empty() || !self.exclude_globs.is_empty() {
+            let mut overrides = OverrideBuilder::new(root);
+            for glob in &self.include_globs {
+                overrides.add(glob)?;
+            }
+            // The ignore globs of the overrides start with the `!`.
+            for glob in &self.exclude_globs {
+                overrides.add(&format!("!{}", glob))?;
+            }
+            builder.overrides(overrides.build()?);
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::RulesBuilder};
+
+    #[test]
+    fn globs_filter_the_walk() {
+        let r = Rules::builder()
+            .include_glob("**/*.rs")
+            .exclude_glob("**/bin/**")
+            .build()
+            .unwrap();
+        let files: Vec<_> = r
+            .walk_builder(".")
+            .unwrap()
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().map_or(false, |ft| ft.is_file()))
+            .map(|entry| entry.into_path())
+            .collect();
+
+        assert!(!files.is_empty());
+        assert!(files.iter().all(|path| path.extension().unwrap() == "rs"));
+        assert!(files
+            .iter()
+            .all(|path| !path.to_str().unwrap().contains("bin")));
+    }
+
+    #[test]
+    fn hidden_files_are_walked_on_demand() {
+        use crate::temp_tree::TempTree;
+
+        let root = TempTree::new("hidden");
+        root.write(".hidden", "").write("visible", "");
+
+        let files_count = |r: Rules| r.walk_builder(root.path()).unwrap().build().count() - 1;
+        let default_count = files_count(Rules::new());
+        let hidden_count = files_count(Rules::builder().search_hidden(true).build().unwrap());
+
+        assert_eq!((default_count, hidden_count), (1, 2));
+    }
+
+    #[test]
+    fn ignore_files_could_be_disabled() {
+        use crate::temp_tree::TempTree;
+
+        let root = TempTree::new("no-ignore");
+        root.write(".ignore", "ignored\n")
+            .write(".clapignore", "clap_ignored\n")
+            .write("ignored", "")
+            .write("clap_ignored", "");
+
+        let files_count = |r: RulesBuilder| {
+            let r = r.custom_ignore_filename(".clapignore").build().unwrap();
+            r.walk_builder(root.path()).unwrap().build().count() - 1
+        };
+        let default_count = files_count(Rules::builder());
+        let no_ignore_count = files_count(Rules::builder().no_ignore());
+
+        assert_eq!((default_count, no_ignore_count), (0, 2));
+    }
+
+    #[test]
+    fn shallow_walk() {
+        let r = Rules::builder().max_depth(1).build().unwrap();
+        let mut walk = r.walk_builder(".").unwrap().build().filter_map(Result::ok);
+        assert!(walk.all(|entry| entry.depth() <= 1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_walk_errors() {
+        use {
+            crate::{
+                filepath_cache::{serialize, NotUtf8},
+                temp_tree::TempTree,
+            },
+            std::os::unix::fs::symlink,
+        };
+
+        let tree = TempTree::new("follow-links");
+        let root = tree.path();
+        tree.write("real/file", "fn main() {}");
+        symlink(root.join("real"), root.join("link")).unwrap();
+        symlink(root, root.join("real").join("loop")).unwrap();
+
+        let r = Rules::builder().follow_links(true).build().unwrap();
+        let mut walk_errors = Vec::new();
+        let cache = serialize(
+            root.to_str().unwrap(),
+            r.walk_builder(root).unwrap(),
+            NotUtf8::ReturnError,
+            |e| walk_errors.push(e),
+        );
+
+        // The loop comes with the depth, where it was found.
+        fn is_loop(e: &ignore::Error) -> bool {
+            match e {
+                ignore::Error::Loop { .. } => true,
+                ignore::Error::WithDepth { err, .. } => is_loop(err),
+                _ => false,
+            }
+        }
+
+        // The file itself and the one behind the `link`.
+        assert_eq!(cache.unwrap().files_count(), 2);
+        assert!(walk_errors.iter().all(is_loop));
+        assert!(!walk_errors.is_empty());
+    }
+
+    #[test]
+    fn unsorted_walk_caches_all_files() {
+        use crate::filepath_cache::{serialize, NotUtf8};
+
+        let cache = |r: Rules| {
+            let builder = r.walk_builder(".").unwrap();
+            serialize(".", builder, NotUtf8::ReturnError, |e| panic!("{}", e))
+                .unwrap()
+                .files_count()
+        };
+        let sorted = cache(Rules::new());
+        let unsorted = cache(Rules::builder().sorted_walk(false).build().unwrap());
+        assert!(sorted > 0);
+        assert_eq!(sorted, unsorted);
+    }
+
+    #[test]
+    fn invalid_glob_is_error() {
+        let r = Rules::builder().exclude_glob("a{b").build().unwrap();
+        assert!(r.walk_builder(".").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use {
+    super::handler::HandleResults,
+    crate::fzy_algo::scoring_utils::MWP,
+    std::io::{self, Write},
+};
+
+/// Writes every received result into the writer, one result per line.
+///
+/// Only the formatted string is written, so the output format
+/// is defined by the formatter of the searcher.
+///
+/// Paths could contain newlines, so there's the [`nul_delimited`] mode
+/// for `xargs -0` and similar tools.
+///
+/// The first write error stops any further writes,
+/// and is returned by the [`finish`] method.
+///
+/// [`finish`]: #method.finish
+/// [`nul_delimited`]: #method.nul_delimited
+pub struct LineWriter<W: Write> {
+    writer: W,
+    delimiter: u8,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> LineWriter<W> {
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            delimiter: b'\n',
+            error: None,
+        }
+    }
+
+    /// Separates the results with `\0` instead of `\n`.
+    #[inline]
+    pub fn nul_delimited(self) -> Self {
+        Self {
+            delimiter: b'\0',
+            ..self
+        }
+    }
+
+    /// Flushes the writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => self.writer.flush().map(|()| self.writer),
+        }
+    }
+
+    fn write_all(&mut self, results: &[MWP]) -> io::Result<()> {
+        for result in results {
+            self.writer.write_all(result.0.as_bytes())?;
+            self.writer.write_all(&[self.delimiter])?;
+        }
+        // Results are streamed, so they should be seen as soon as possible.
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> HandleResults for LineWriter<W> {
+    fn handle_results(&mut self, results: Vec<MWP>) {
+        if self.error.is_none() {
+            self.error = self.write_all(&results).err();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_result_per_line() {
+        let mut writer = LineWriter::new(Vec::new());
+        writer.handle_results(vec![("a:1:1:x".into(), 1, Box::new([]))]);
+        writer.handle_results(vec![("b:2:1:y".into(), 2, Box::new([]))]);
+
+        assert_eq!(writer.finish().unwrap(), b"a:1:1:x\nb:2:1:y\n");
+    }
+
+    #[test]
+    fn nul_delimited_keeps_newlines() {
+        let mut writer = LineWriter::new(Vec::new()).nul_delimited();
+        writer.handle_results(vec![
+            ("new\nline:1:1:x".into(), 1, Box::new([])),
+            ("b:2:1:y".into(), 2, Box::new([])),
+        ]);
+
+        assert_eq!(writer.finish().unwrap(), b"new\nline:1:1:x\0b:2:1:y\0");
+    }
+}
@@ -1,13 +1,49 @@
 //! **Fu**zzy **l**inesearcher and **f**ilterer.
 //!
 //! Like regex searcher, but not regex searcher.
+//!
+//! The walking and the searching of the files need the default `search` feature.
+//! Without it, only the scoring and the filtering of the in-memory lines
+//! are left, and those compile to the `wasm32-unknown-unknown`.
 
 pub mod bytelines;
+#[cfg(feature = "config")]
+pub mod config;
+#[cfg(feature = "search")]
+pub mod debounce;
+pub mod filter;
 pub mod fzy_algo;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "search")]
+pub mod index;
+#[cfg(all(feature = "mlua", feature = "search"))]
+pub mod lua;
+pub mod merger;
+#[cfg(feature = "rmp-serde")]
+pub mod msgpack;
+#[cfg(all(feature = "libloading", feature = "search"))]
+pub mod plugin;
+#[cfg(all(feature = "json", feature = "search"))]
+pub mod provider;
+#[cfg(feature = "json")]
+pub mod saved;
+#[cfg(all(feature = "json", feature = "search"))]
+pub mod server;
+#[cfg(all(feature = "json", feature = "search"))]
+pub mod session;
+pub mod tags;
+#[cfg(all(feature = "notify", feature = "search"))]
+pub mod watch;
 
 mod interface;
 pub use interface::*;
 
+#[cfg(test)]
+mod temp_tree;
+
+#[cfg(feature = "search")]
 pub use ignore::{Walk, WalkBuilder};
 
+#[cfg(feature = "search")]
 pub mod filepath_cache;
@@ -0,0 +1,103 @@
+//! The Lua function as the line matcher, to prototype the custom ranking
+//! without recompiling the crate.
+//!
+//! The chunk should evaluate to the function, that takes the line and the needle,
+//! and returns the score and the table of the matched char positions
+//! (starting from 0), or `nil` if the line doesn't match:
+//!
+//! ```lua
+//! return function(line, needle)
+//!     local start = string.find(line:lower(), needle:lower(), 1, true)
+//!     if not start then
+//!         return nil
+//!     end
+//!     local positions = {}
+//!     for i = 0, #needle - 1 do
+//!         positions[#positions + 1] = start - 1 + i
+//!     end
+//!     return 1000 - start, positions
+//! end
+//! ```
+//!
+//! The positions are the char positions, just like the ones of the fzy algorithm.
+//!
+//! There's only one Lua state, so the threads take turns to call the function;
+//! it's fine for the prototype, not so much for the huge projects.
+
+use {
+    crate::{
+        fzy_algo::scoring_utils::{MatchWithPositions, Score},
+        matcher::LineMatcher,
+    },
+    mlua::{Function, Lua, RegistryKey},
+    std::sync::Mutex,
+};
+
+/// The matcher, that calls the Lua function for every line.
+///
+/// Errors of the function are the lines, that don't match.
+pub struct LuaMatcher {
+    lua: Mutex<Lua>,
+    scorer: RegistryKey,
+}
+
+impl LuaMatcher {
+    /// Evaluates the chunk, which should return the scoring function.
+    pub fn new(source: &str) -> mlua::Result<Self> {
+        let lua = Lua::new();
+        let scorer: Function = lua.load(source).eval()?;
+        let scorer = lua.create_registry_value(scorer)?;
+        Ok(Self {
+            lua: Mutex::new(lua),
+            scorer,
+        })
+    }
+}
+
+impl LineMatcher for LuaMatcher {
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        _prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        // The poisoned state is the panicked call, there's nothing wrong with the state itself.
+        let lua = self
+            .lua
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let scorer: Function = lua.registry_value(&self.scorer).ok()?;
+        let (score, positions): (Option<Score>, Option<Vec<usize>>) =
+            scorer.call((line, needle)).ok()?;
+        Some((score?, positions.unwrap_or_default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lua_function_scores_the_line() {
+        let matcher = LuaMatcher::new(
+            r#"
+            return function(line, needle)
+                local start = string.find(line, needle, 1, true)
+                if not start then
+                    return nil
+                end
+                return -start, { start - 1 }
+            end
+            "#,
+        )
+        .unwrap();
+
+        let prealloc = &mut Default::default();
+        assert_eq!(
+            matcher.match_line("fn main()", "main", prealloc),
+            Some((-4, vec![3]))
+        );
+        assert_eq!(matcher.match_line("fn main()", "xyz", prealloc), None);
+        assert!(LuaMatcher::new("return 42").is_err());
+    }
+}
@@ -0,0 +1,716 @@
+//! Merging of the results from the worker threads.
+//!
+//! Every worker sends its own batches of results, those batches
+//! should be merged into one sorted and capped list, that is shown to the user.
+
+use {
+    crate::fzy_algo::scoring_utils::{Score, MWP},
+    std::{cmp::Ordering, collections::HashMap, mem, path, sync::Arc},
+};
+
+/// Number of the batches, that every search of the [`SearchMerger`]
+/// could send ahead, before it waits for the merge.
+///
+/// [`SearchMerger`]: struct.SearchMerger.html
+#[cfg(feature = "search")]
+const MERGED_BATCHES: usize = 16;
+
+/// Defines the order of the results.
+///
+/// Implemented for any `FnMut(&MWP, &MWP) -> Ordering` closure,
+/// but the built-in strategies should be enough for most cases:
+///
+/// * [`ByScore`] — best scores first;
+///
+/// * [`ByScoreThenPath`] — best scores first,
+///   equal scores are sorted by the path and line number;
+///
+/// * [`ByPath`] — sorted by the path and line number, scores are ignored.
+///
+/// [`ByScore`]: struct.ByScore.html
+/// [`ByScoreThenPath`]: struct.ByScoreThenPath.html
+/// [`ByPath`]: struct.ByPath.html
+pub trait SortStrategy {
+    /// `Ordering::Less` means that `a` is shown before `b`.
+    fn cmp(&mut self, a: &MWP, b: &MWP) -> Ordering;
+
+    /// The better score is always shown first, whatever the rest of the order is.
+    ///
+    /// Then the results, those score below the last one of the full list,
+    /// are not even formatted, see the [`Merger::floor`].
+    ///
+    /// [`Merger::floor`]: struct.Merger.html#method.floor
+    #[inline]
+    fn by_score(&self) -> bool {
+        false
+    }
+}
+
+impl<F> SortStrategy for F
+where
+    F: FnMut(&MWP, &MWP) -> Ordering,
+{
+    #[inline]
+    fn cmp(&mut self, a: &MWP, b: &MWP) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Best scores first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByScore;
+
+impl SortStrategy for ByScore {
+    #[inline]
+    fn cmp(&mut self, a: &MWP, b: &MWP) -> Ordering {
+        b.1.cmp(&a.1)
+    }
+
+    #[inline]
+    fn by_score(&self) -> bool {
+        true
+    }
+}
+
+/// Best scores first, equal scores are sorted by the path and line number.
+///
+/// Unlike [`ByScore`], gives the same order on every run.
+///
+/// [`ByScore`]: struct.ByScore.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByScoreThenPath;
+
+impl SortStrategy for ByScoreThenPath {
+    #[inline]
+    fn cmp(&mut self, a: &MWP, b: &MWP) -> Ordering {
+        ByScore.cmp(a, b).then_with(|| ByPath.cmp(a, b))
+    }
+
+    #[inline]
+    fn by_score(&self) -> bool {
+        true
+    }
+}
+
+/// Sorted by the path and line number, scores are ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByPath;
+
+impl SortStrategy for ByPath {
+    #[inline]
+    fn cmp(&mut self, a: &MWP, b: &MWP) -> Ordering {
+        path_and_row(&a.0).cmp(&path_and_row(&b.0))
+    }
+}
+
+/// Splits the `path:row:col:line` string into the path and the row.
+///
+/// The path itself could contain `:` chars, so the first `:row:col:`
+/// sequence is searched. If there's no such sequence,
+/// the whole string is the path.
+fn path_and_row(s: &str) -> (&str, usize) {
+    fn digits(s: &str) -> Option<(usize, &str)> {
+        let end = s.find(|c: char| !c.is_ascii_digit())?;
+        let num = s[..end].parse().ok()?;
+        Some((num, &s[end..]))
+    }
+
+    s.match_indices(':')
+        .find_map(|(idx, _)| {
+            let (row, rest) = digits(&s[idx + 1..])?;
+            let (_col, rest) = digits(rest.strip_prefix(':')?)?;
+            rest.strip_prefix(':').map(|_| (&s[..idx], row))
+        })
+        .unwrap_or((s, 0))
+}
+
+/// The folder of the file, cut to the first `depth` components.
+///
+/// The files right in the root are in the `""` folder.
+fn dir_of(path: &str, depth: usize) -> &str {
+    let is_separator = |c: char| c == '/' || c == path::MAIN_SEPARATOR;
+    let dir = path.rfind(is_separator).map_or("", |idx| &path[..idx]);
+    match dir.match_indices(is_separator).nth(depth.max(1) - 1) {
+        Some((idx, _separator)) => &dir[..idx],
+        None => dir,
+    }
+}
+
+/// The limit of the results of one folder, see the [`Merger::dir_cap`].
+///
+/// [`Merger::dir_cap`]: struct.Merger.html#method.dir_cap
+#[derive(Debug, Clone, Copy)]
+struct DirCap {
+    max: usize,
+    depth: usize,
+}
+
+/// Keeps the best `cap` results, sorted with the [`SortStrategy`].
+///
+/// # Examples
+///
+/// ```
+/// use fulf::merger::{ByScore, Merger};
+///
+/// let mut merger = Merger::new(2, ByScore);
+///
+/// merger.merge_delta(vec![("a".into(), 1, Box::new([])), ("b".into(), 3, Box::new([]))]);
+/// let delta = merger.merge_delta(vec![("c".into(), 2, Box::new([]))]);
+///
+/// // "c" was inserted between "b" and "a", and "a" was pushed out.
+/// assert!(delta.iter().map(|(pos, item)| (pos, &*item.0)).eq(vec![(1, "c")]));
+/// assert_eq!(delta.len(), 2);
+/// ```
+///
+/// [`SortStrategy`]: trait.SortStrategy.html
+pub struct Merger<S> {
+    cap: usize,
+    strategy: S,
+    items: Vec<MWP>,
+    // The paths of the files of the `items`, if those were merged with the paths.
+    paths: Vec<Option<Arc<str>>>,
+    // The allocations of the previous `items` and `paths`, reused on every merge.
+    spare: Vec<MWP>,
+    spare_paths: Vec<Option<Arc<str>>>,
+    // Positions of the items inserted by the last merge.
+    positions: Vec<usize>,
+    dir_cap: Option<DirCap>,
+    // Results of every folder in the merged list.
+    dir_counts: HashMap<Box<str>, usize>,
+    // Old items, pushed out by the last merge because of the `dir_cap`.
+    removed: usize,
+}
+
+impl<S> Merger<S>
+where
+    S: SortStrategy,
+{
+    pub fn new(cap: usize, strategy: S) -> Self {
+        Self {
+            cap,
+            strategy,
+            items: Vec::new(),
+            paths: Vec::new(),
+            spare: Vec::new(),
+            spare_paths: Vec::new(),
+            positions: Vec::new(),
+            dir_cap: None,
+            dir_counts: HashMap::new(),
+            removed: 0,
+        }
+    }
+
+    /// Keeps at most `max` results of every folder, so the folders like
+    /// the `locales/` or the `snapshots/` don't take the whole list.
+    ///
+    /// The folder is the first `depth` components of the result's path,
+    /// 1 means the folders right in the root; the files right in the root
+    /// are one folder too. The better result of the full folder pushes out
+    /// the worst one of that folder, see the [`Delta::removed`].
+    ///
+    /// Only the results, merged with their paths by the [`merge_paths_delta`],
+    /// are capped: the formatters put the path anywhere in the result,
+    /// so it's not parsed out of the result.
+    ///
+    /// [`Delta::removed`]: struct.Delta.html#method.removed
+    /// [`merge_paths_delta`]: #method.merge_paths_delta
+    pub fn dir_cap(mut self, max: usize, depth: usize) -> Self {
+        self.dir_cap = Some(DirCap { max, depth });
+        self
+    }
+
+    /// The score of the last result of the full list, if the strategy
+    /// puts the better scores first: the results with the lower scores
+    /// never get into the list, so there's no need to format them.
+    ///
+    /// The floor only rises, once the list is full; there's no floor
+    /// with the [`dir_cap`], which could shorten the list.
+    ///
+    /// [`dir_cap`]: #method.dir_cap
+    pub fn floor(&self) -> Option<Score> {
+        if !self.strategy.by_score() || self.dir_cap.is_some() || self.items.len() < self.cap {
+            return None;
+        }
+        self.items.last().map(|item| item.1)
+    }
+
+    /// All the results, that survived so far.
+    #[inline]
+    pub fn items(&self) -> &[MWP] {
+        &self.items
+    }
+
+    #[inline]
+    pub fn into_items(self) -> Vec<MWP> {
+        self.items
+    }
+
+    /// Merges the batch into the sorted results.
+    #[inline]
+    pub fn merge(&mut self, batch: Vec<MWP>) {
+        self.merge_delta(batch);
+    }
+
+    /// Merges the batch into the sorted results,
+    /// returns only the items from the batch, that survived the cap.
+    ///
+    /// The UI could insert those items at their positions
+    /// and truncate its list to the [`Delta::len`],
+    /// instead of redrawing the whole list.
+    ///
+    /// [`Delta::len`]: struct.Delta.html#method.len
+    pub fn merge_delta(&mut self, batch: Vec<MWP>) -> Delta<'_> {
+        self.merge_items(batch.into_iter().map(|item| (item, None)).collect())
+    }
+
+    /// Like the [`merge_delta`], but every result comes with the path of its file,
+    /// e.g. the [`LineMatch::path`], so it's capped by its folder,
+    /// see the [`dir_cap`].
+    ///
+    /// [`merge_delta`]: #method.merge_delta
+    /// [`dir_cap`]: #method.dir_cap
+    /// [`LineMatch::path`]: ../struct.LineMatch.html#structfield.path
+    pub fn merge_paths_delta(&mut self, batch: Vec<(MWP, Arc<str>)>) -> Delta<'_> {
+        let batch = batch.into_iter().map(|(item, path)| (item, Some(path)));
+        self.merge_items(batch.collect())
+    }
+
+    fn merge_items(&mut self, mut batch: Vec<(MWP, Option<Arc<str>>)>) -> Delta<'_> {
+        let cap = self.cap;
+        let mut old = mem::take(&mut self.items);
+        let mut old_paths = mem::take(&mut self.paths);
+        let mut merged = mem::take(&mut self.spare);
+        let mut merged_paths = mem::take(&mut self.spare_paths);
+        merged.clear();
+        merged_paths.clear();
+        merged.reserve(cap.min(old.len() + batch.len()));
+        self.positions.clear();
+        self.dir_counts.clear();
+        self.removed = 0;
+
+        {
+            let strategy = &mut self.strategy;
+
+            // When the list is full, only items better than the last one
+            // could get into it.
+            // With the folder cap, the pushed out items free their places.
+            let full = old.len() >= cap && self.dir_cap.is_none();
+            if let Some(last) = old.last().filter(|_| full) {
+                batch.retain(|(item, _path)| strategy.cmp(item, last) == Ordering::Less);
+            }
+            // Only the first `cap` items of the batch could survive the merge,
+            // so there's no need to sort the rest of them.
+            // With the folder cap, the rest could take the places of the capped ones.
+            if batch.len() > cap && self.dir_cap.is_none() {
+                batch.select_nth_unstable_by(cap, |a, b| strategy.cmp(&a.0, &b.0));
+                batch.truncate(cap);
+            }
+            batch.sort_by(|a, b| strategy.cmp(&a.0, &b.0));
+
+            // The old paths are there only with the folder cap.
+            old_paths.resize(old.len(), None);
+            let mut old_items = old.drain(..).zip(old_paths.drain(..)).peekable();
+            let mut new_items = batch.into_iter().peekable();
+            while merged.len() < cap {
+                // On equal items the old one goes first,
+                // so the shown items don't jump around.
+                let take_new = match (old_items.peek(), new_items.peek()) {
+                    (Some(o), Some(n)) => strategy.cmp(&n.0, &o.0) == Ordering::Less,
+                    (Some(_), None) => false,
+                    (None, Some(_)) => true,
+                    (None, None) => break,
+                };
+
+                let (item, path) = match if take_new {
+                    new_items.next()
+                } else {
+                    old_items.next()
+                } {
+                    Some(item) => item,
+                    None => break,
+                };
+
+                if let (Some(DirCap { max, depth }), Some(path)) = (self.dir_cap, &path) {
+                    let dir = dir_of(path, depth);
+                    if self
+                        .dir_counts
+                        .get(dir)
+                        .map_or(false, |&count| count >= max)
+                    {
+                        if !take_new {
+                            self.removed += 1;
+                        }
+                        continue;
+                    }
+                    // Only the new folders are allocated.
+                    match self.dir_counts.get_mut(dir) {
+                        Some(count) => *count += 1,
+                        None => {
+                            self.dir_counts.insert(dir.into(), 1);
+                        }
+                    }
+                }
+
+                if take_new {
+                    self.positions.push(merged.len());
+                }
+                merged.push(item);
+                if self.dir_cap.is_some() {
+                    merged_paths.push(path);
+                }
+            }
+        }
+
+        self.spare = old;
+        self.spare_paths = old_paths;
+        self.items = merged;
+        self.paths = merged_paths;
+
+        Delta {
+            items: &self.items,
+            positions: &self.positions,
+            removed: self.removed,
+        }
+    }
+}
+
+/// Merges the results of several searches, running at once,
+/// e.g. the search of the file contents and the one of the paths,
+/// into one list with the same cap, for the "search everything" pickers.
+///
+/// The searches are merged fairly: one batch of every search in turn,
+/// and every search could send only a few batches ahead,
+/// so the fast one doesn't push the results of the slow ones out.
+///
+/// # Examples
+///
+/// ```
+/// use fulf::merger::{ByScore, SearchMerger};
+/// use std::thread;
+///
+/// let mut merger = SearchMerger::new(2, ByScore);
+/// let contents = merger.sender();
+/// let paths = merger.sender();
+/// thread::spawn(move || contents.send(vec![("a.rs:1:1:x".into(), 5, Box::new([]) as _)]));
+/// thread::spawn(move || paths.send(vec![("b.rs".into(), 9, Box::new([]) as _)]));
+///
+/// let results = merger.run(|_delta| ());
+/// assert_eq!(&*results[0].0, "b.rs");
+/// assert_eq!(results.len(), 2);
+/// ```
+#[cfg(feature = "search")]
+pub struct SearchMerger<S> {
+    merger: Merger<S>,
+    receivers: Vec<flume::Receiver<Vec<MWP>>>,
+}
+
+#[cfg(feature = "search")]
+impl<S: SortStrategy> SearchMerger<S> {
+    /// Keeps at most `cap` results of all the searches, in the `strategy` order.
+    #[inline]
+    pub fn new(cap: usize, strategy: S) -> Self {
+        Self::with_merger(Merger::new(cap, strategy))
+    }
+
+    /// Like the [`new`], but with the configured merger.
+    ///
+    /// The batches of the searches come without the paths, so the [`Merger::dir_cap`]
+    /// doesn't cap them.
+    ///
+    /// [`new`]: #method.new
+    /// [`Merger::dir_cap`]: struct.Merger.html#method.dir_cap
+    #[inline]
+    pub fn with_merger(merger: Merger<S>) -> Self {
+        Self {
+            merger,
+            receivers: Vec::new(),
+        }
+    }
+
+    /// The sender for one more search: pass its batches there,
+    /// e.g. from the handler of the [`spawner`], and drop it, once the search is done.
+    ///
+    /// [`spawner`]: ../struct.SpecializedAscii.html#method.spawner
+    pub fn sender(&mut self) -> flume::Sender<Vec<MWP>> {
+        let (sender, receiver) = flume::bounded(MERGED_BATCHES);
+        self.receivers.push(receiver);
+        sender
+    }
+
+    /// Merges the batches until all the senders are dropped.
+    ///
+    /// `display` is called only when some new results got into the list,
+    /// just like in the [`sorted_spawner`]. Returns the final list.
+    ///
+    /// [`sorted_spawner`]: ../struct.SpecializedAscii.html#method.sorted_spawner
+    pub fn run(mut self, mut display: impl FnMut(Delta<'_>)) -> Vec<MWP> {
+        let mut next = 0;
+        // The searches without new batches since the last merge.
+        let mut idle = 0;
+
+        while !self.receivers.is_empty() {
+            next %= self.receivers.len();
+            let received = match self.receivers[next].try_recv() {
+                Ok(batch) => Ok(batch),
+                Err(flume::TryRecvError::Disconnected) => Err(flume::RecvError::Disconnected),
+                Err(flume::TryRecvError::Empty) => {
+                    idle += 1;
+                    if idle < self.receivers.len() {
+                        next += 1;
+                        continue;
+                    }
+                    // No search has new results, so block until any of them does.
+                    let (ready, received) = self.wait_any();
+                    next = ready;
+                    received
+                }
+            };
+            idle = 0;
+
+            match received {
+                Ok(batch) => {
+                    let delta = self.merger.merge_delta(batch);
+                    if delta.inserted() != 0 {
+                        display(delta);
+                    }
+                    next += 1;
+                }
+                // The search is done, the rest keep their turns.
+                Err(flume::RecvError::Disconnected) => {
+                    self.receivers.remove(next);
+                }
+            }
+        }
+
+        self.merger.into_items()
+    }
+
+    /// Waits for the first search with the new batch, or the finished one;
+    /// returns its index with what it sent.
+    fn wait_any(&self) -> (usize, Result<Vec<MWP>, flume::RecvError>) {
+        self.receivers
+            .iter()
+            .enumerate()
+            .fold(flume::Selector::new(), |selector, (idx, receiver)| {
+                selector.recv(receiver, move |received| (idx, received))
+            })
+            .wait()
+    }
+}
+
+/// The items, inserted by the last merge.
+#[derive(Debug, Clone, Copy)]
+pub struct Delta<'a> {
+    items: &'a [MWP],
+    positions: &'a [usize],
+    removed: usize,
+}
+
+impl<'a> Delta<'a> {
+    /// Inserted items with their final positions, in the ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &'a MWP)> + 'a {
+        let items = self.items;
+        self.positions.iter().map(move |&pos| (pos, &items[pos]))
+    }
+
+    /// Number of inserted items.
+    #[inline]
+    pub fn inserted(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Number of the old items, pushed out of the middle of the list
+    /// by the [`Merger::dir_cap`].
+    ///
+    /// If it's not zero, the inserted positions are not enough
+    /// to update the shown list, the whole [`items`] should be redrawn.
+    ///
+    /// [`Merger::dir_cap`]: struct.Merger.html#method.dir_cap
+    /// [`items`]: #method.items
+    #[inline]
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Length of the whole list after the merge.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The whole list after the merge.
+    #[inline]
+    pub fn items(&self) -> &'a [MWP] {
+        self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(s: &str, score: i32) -> MWP {
+        (s.into(), score, Box::new([]))
+    }
+
+    /// The result, that is just the path of its file.
+    fn pathed(path: &str, score: i32) -> (MWP, Arc<str>) {
+        (item(path, score), path.into())
+    }
+
+    #[test]
+    fn delta_positions_are_final() {
+        let mut merger = Merger::new(4, ByScore);
+
+        let delta = merger.merge_delta(vec![item("a", 10), item("b", 30)]);
+        assert_eq!(delta.iter().map(|(pos, _)| pos).collect::<Vec<_>>(), [0, 1]);
+
+        let delta = merger.merge_delta(vec![item("c", 5), item("d", 40), item("e", 20)]);
+        let inserted = delta
+            .iter()
+            .map(|(pos, item)| (pos, &*item.0))
+            .collect::<Vec<_>>();
+        // "c" didn't make it past the cap.
+        assert_eq!(inserted, [(0, "d"), (2, "e")]);
+        assert_eq!(delta.len(), 4);
+
+        let names = merger.items().iter().map(|i| &*i.0).collect::<Vec<_>>();
+        assert_eq!(names, ["d", "b", "e", "a"]);
+    }
+
+    #[test]
+    fn floor_rises_with_the_full_list() {
+        let mut merger = Merger::new(2, ByScore);
+        merger.merge(vec![item("a", 10)]);
+        assert_eq!(merger.floor(), None);
+
+        merger.merge(vec![item("b", 30)]);
+        assert_eq!(merger.floor(), Some(10));
+        merger.merge(vec![item("c", 20), item("d", 5)]);
+        assert_eq!(merger.floor(), Some(20));
+
+        let mut by_path = Merger::new(1, ByPath);
+        by_path.merge(vec![item("a", 10)]);
+        assert_eq!(by_path.floor(), None);
+    }
+
+    #[test]
+    fn by_path_reads_row_numbers() {
+        assert_eq!(path_and_row("src/a:b.rs:10:3:x: y"), ("src/a:b.rs", 10));
+        assert_eq!(path_and_row("no numbers"), ("no numbers", 0));
+
+        let mut merger = Merger::new(8, ByScoreThenPath);
+        merger.merge(vec![
+            item("b:2:1:x", 1),
+            item("a:10:1:x", 1),
+            item("a:9:1:x", 1),
+        ]);
+        merger.merge(vec![item("c:1:1:x", 2)]);
+
+        let names = merger.items().iter().map(|i| &*i.0).collect::<Vec<_>>();
+        assert_eq!(names, ["c:1:1:x", "a:9:1:x", "a:10:1:x", "b:2:1:x"]);
+    }
+
+    #[test]
+    fn batch_bigger_than_cap() {
+        let mut merger = Merger::new(3, ByScore);
+        merger.merge((0..100).map(|i| item("x", i)).collect());
+        let delta = merger.merge_delta((0..100).map(|i| item("y", i + 1)).collect());
+
+        assert_eq!(delta.inserted(), 2);
+        let items = merger
+            .items()
+            .iter()
+            .map(|i| (&*i.0, i.1))
+            .collect::<Vec<_>>();
+        assert_eq!(items, [("y", 100), ("x", 99), ("y", 99)]);
+
+        // Nothing could beat the last item, so nothing is inserted.
+        assert_eq!(merger.merge_delta(vec![item("z", 99)]).inserted(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "search")]
+    fn searches_are_merged_fairly() {
+        let mut merger = SearchMerger::new(4, ByScore);
+        let fast = merger.sender();
+        let slow = merger.sender();
+        let fast = std::thread::spawn(move || {
+            for score in 0..100 {
+                let _any_result = fast.send(vec![item("fast", score % 10)]);
+            }
+        });
+        let slow = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            let _any_result = slow.send(vec![item("slow", 50)]);
+        });
+
+        let results = merger.run(|_delta| ());
+        fast.join().unwrap();
+        slow.join().unwrap();
+        let scores = results.iter().map(|i| i.1).collect::<Vec<_>>();
+        assert_eq!(scores, [50, 9, 9, 9]);
+        assert_eq!(&*results[0].0, "slow");
+    }
+
+    #[test]
+    fn folders_are_capped() {
+        assert_eq!(dir_of("locales/de/a.po", 1), "locales");
+        assert_eq!(dir_of("locales/de/a.po", 2), "locales/de");
+        assert_eq!(dir_of("main.rs", 1), "");
+
+        let mut merger = Merger::new(4, ByScore).dir_cap(2, 1);
+        merger.merge_paths_delta(vec![
+            pathed("locales/a", 10),
+            pathed("locales/de/b", 9),
+            pathed("locales/c", 8),
+            pathed("src/d", 1),
+        ]);
+        let names = merger.items().iter().map(|i| &*i.0).collect::<Vec<_>>();
+        assert_eq!(names, ["locales/a", "locales/de/b", "src/d"]);
+
+        let delta = merger.merge_paths_delta(vec![pathed("locales/e", 20)]);
+        assert_eq!(delta.inserted(), 1);
+        assert_eq!(delta.removed(), 1);
+        let names = merger.items().iter().map(|i| &*i.0).collect::<Vec<_>>();
+        assert_eq!(names, ["locales/e", "locales/a", "src/d"]);
+    }
+
+    #[test]
+    fn folders_are_taken_from_the_paths() {
+        // The JSON result has no `path:row:col:` in it.
+        let json = |path: &str, score| {
+            let line = format!("{{\"path\":\"{}\",\"row\":1}}", path);
+            ((line, score, Box::new([]) as Box<[usize]>), path.into())
+        };
+        let mut merger = Merger::new(4, ByScore).dir_cap(1, 1);
+        merger.merge_paths_delta(vec![json("a/x", 3), json("a/y", 2), json("b/z", 1)]);
+        assert_eq!(
+            merger.items().iter().map(|i| i.1).collect::<Vec<_>>(),
+            [3, 1]
+        );
+
+        // The results without the paths are not capped.
+        merger.merge_delta(vec![item("a/w:1:1:x", 4)]);
+        assert_eq!(merger.items().len(), 3);
+    }
+
+    #[test]
+    fn full_capped_list_stays_full() {
+        let mut merger = Merger::new(3, ByScore).dir_cap(1, 1);
+        merger.merge_paths_delta(vec![pathed("a/x", 5), pathed("b/x", 4), pathed("c/x", 3)]);
+
+        // The better item of the `a` pushes out the old one,
+        // the list is still full.
+        let delta = merger.merge_paths_delta(vec![pathed("a/y", 10), pathed("d/x", 2)]);
+        assert_eq!(delta.removed(), 1);
+        assert_eq!(delta.len(), 3);
+        let names = merger.items().iter().map(|i| &*i.0).collect::<Vec<_>>();
+        assert_eq!(names, ["a/y", "b/x", "c/x"]);
+    }
+}
@@ -0,0 +1,44 @@
+//! MessagePack encoding of the result batches.
+//!
+//! Neovim talks msgpack-RPC, so the batch could be sent to it as is,
+//! without a JSON round-trip.
+//!
+//! Every result is encoded as the `[string, score, positions]` array,
+//! and the batch is the array of such arrays.
+
+use {crate::fzy_algo::scoring_utils::MWP, std::io::Write};
+
+pub use rmp_serde::encode::Error;
+
+/// Encodes the batch into the new buffer.
+#[inline]
+pub fn encode_batch(batch: &[MWP]) -> Result<Vec<u8>, Error> {
+    rmp_serde::to_vec(batch)
+}
+
+/// Encodes the batch right into the writer.
+#[inline]
+pub fn write_batch<W: Write + ?Sized>(writer: &mut W, batch: &[MWP]) -> Result<(), Error> {
+    rmp_serde::encode::write(writer, batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_is_array_of_arrays() {
+        let batch: Vec<MWP> = vec![("a:1:1:x".into(), 7, Box::new([6]))];
+        let bytes = encode_batch(&batch).unwrap();
+
+        // fixarray(1), fixarray(3), fixstr(7), 7, fixarray(1), 6.
+        let mut expected = vec![0x91, 0x93, 0xa7];
+        expected.extend_from_slice(b"a:1:1:x");
+        expected.extend_from_slice(&[0x07, 0x91, 0x06]);
+        assert_eq!(bytes, expected);
+
+        let mut written = Vec::new();
+        write_batch(&mut written, &batch).unwrap();
+        assert_eq!(written, bytes);
+    }
+}
@@ -0,0 +1,186 @@
+//! The line matchers from the shared libraries, loaded at runtime.
+//!
+//! The library exports two C functions:
+//!
+//! ```c
+//! // Should return the `fulf::plugin::ABI_VERSION`.
+//! uint32_t fulf_matcher_abi_version(void);
+//!
+//! // Scores the UTF-8 line for the UTF-8 needle, neither is nul-terminated.
+//! // Returns 1 if the line matches, 0 if it doesn't.
+//! //
+//! // On the match, writes the score, and the char positions of the matched chars
+//! // (starting from 0) into the `positions`, at most `positions_cap` of them,
+//! // and their number into the `positions_len`.
+//! int32_t fulf_match_line(
+//!     const uint8_t *line, size_t line_len,
+//!     const uint8_t *needle, size_t needle_len,
+//!     int32_t *score,
+//!     size_t *positions, size_t positions_cap, size_t *positions_len);
+//! ```
+//!
+//! The `positions_cap` is at least the number of the line's chars.
+//! The positions should be strictly increasing, or the line doesn't match.
+//! The `fulf_match_line` is called from all the searching threads at once,
+//! so it should be thread-safe.
+
+use {
+    crate::{
+        fzy_algo::scoring_utils::{MatchWithPositions, Score},
+        matcher::LineMatcher,
+    },
+    libloading::{Library, Symbol},
+    std::{cell::RefCell, error::Error, ffi::OsStr, fmt},
+};
+
+/// The version of the plugin ABI, described in the [module docs](index.html).
+pub const ABI_VERSION: u32 = 1;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+
+type MatchLineFn = unsafe extern "C" fn(
+    line: *const u8,
+    line_len: usize,
+    needle: *const u8,
+    needle_len: usize,
+    score: *mut i32,
+    positions: *mut usize,
+    positions_cap: usize,
+    positions_len: *mut usize,
+) -> i32;
+
+thread_local! {
+    /// The positions, written by the plugin, reused for every line of the thread.
+    static POSITIONS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The matcher of the shared library.
+pub struct PluginMatcher {
+    match_line: MatchLineFn,
+    // The function is valid only while the library is loaded.
+    _library: Library,
+}
+
+impl PluginMatcher {
+    /// Loads the library and checks its ABI version.
+    ///
+    /// # Safety
+    ///
+    /// The library runs its initialization code on load,
+    /// and its functions should follow the ABI of the [module docs](index.html).
+    pub unsafe fn load(path: impl AsRef<OsStr>) -> Result<Self, PluginError> {
+        let library = Library::new(path).map_err(PluginError::Load)?;
+
+        let abi_version: Symbol<AbiVersionFn> = library
+            .get(b"fulf_matcher_abi_version\0")
+            .map_err(PluginError::Load)?;
+        let version = abi_version();
+        if version != ABI_VERSION {
+            return Err(PluginError::AbiVersion(version));
+        }
+
+        let match_line: Symbol<MatchLineFn> = library
+            .get(b"fulf_match_line\0")
+            .map_err(PluginError::Load)?;
+        Ok(Self {
+            match_line: *match_line,
+            _library: library,
+        })
+    }
+}
+
+impl LineMatcher for PluginMatcher {
+    fn match_line(
+        &self,
+        line: &str,
+        needle: &str,
+        _prealloc: &mut (Vec<Score>, Vec<Score>),
+    ) -> Option<MatchWithPositions> {
+        POSITIONS.with(|positions| {
+            let positions = &mut *positions.borrow_mut();
+            // There are no more chars than bytes.
+            if positions.len() < line.len() {
+                positions.resize(line.len(), 0);
+            }
+            let mut score: i32 = 0;
+            let mut positions_len: usize = 0;
+
+            //x SAFETY: the pointers are valid for their lengths,
+            //x the function follows the ABI, see the `PluginMatcher::load`.
+            let matched = unsafe {
+                (self.match_line)(
+                    line.as_ptr(),
+                    line.len(),
+                    needle.as_ptr(),
+                    needle.len(),
+                    &mut score,
+                    positions.as_mut_ptr(),
+                    line.len(),
+                    &mut positions_len,
+                )
+            };
+            if matched == 0 || positions_len > line.len() {
+                return None;
+            }
+            let positions = &positions[..positions_len];
+            if !are_valid(positions, line) {
+                return None;
+            }
+            Some((score, positions.to_vec()))
+        })
+    }
+}
+
+/// The positions of the plugin are strictly increasing
+/// and point to the chars of the line, otherwise it's no match.
+fn are_valid(positions: &[usize], line: &str) -> bool {
+    positions.windows(2).all(|pair| pair[0] < pair[1])
+        && positions
+            .last()
+            .map_or(true, |&last| last < line.chars().count())
+}
+
+/// The error of the plugin loading.
+#[derive(Debug)]
+pub enum PluginError {
+    /// The library or its functions can't be loaded.
+    Load(libloading::Error),
+    /// The library has another version of the ABI.
+    AbiVersion(u32),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginError::Load(e) => write!(f, "can't load the plugin: {}", e),
+            PluginError::AbiVersion(version) => write!(
+                f,
+                "the plugin ABI version is {}, not {}",
+                version, ABI_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for PluginError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_library_is_load_error() {
+        let loaded = unsafe { PluginMatcher::load("fulf-no-such-plugin.so") };
+        assert!(matches!(loaded, Err(PluginError::Load(_))));
+    }
+
+    #[test]
+    fn bad_positions_are_no_match() {
+        assert!(are_valid(&[0, 2, 3], "añbc"));
+        assert!(are_valid(&[], "abc"));
+        // Out of the chars, but not of the bytes.
+        assert!(!are_valid(&[0, 4], "añbc"));
+        assert!(!are_valid(&[1, 1], "abc"));
+        assert!(!are_valid(&[2, 0], "abc"));
+    }
+}
@@ -0,0 +1,174 @@
+//! vim-clap's dynamic provider protocol.
+//!
+//! Every message is a JSON object, prefixed with the `Content-length` header,
+//! just like maple prints it for the dynamic filter:
+//!
+//! ```text
+//! Content-length: 55
+//!
+//! {"indices":[[0,1]],"lines":["a.rs:1:1:fn"],"total":1}
+//! ```
+//!
+//! `total` is the number of matches found so far, `lines` and `indices`
+//! are the best results and their highlighted chars. If the best results
+//! haven't changed since the last message, only the `total` is sent.
+//!
+//! If there's the `id` of the request, it's sent in every message too.
+
+use {
+    crate::{
+        fzy_algo::scoring_utils::MWP,
+        merger::{ByScore, Merger, SortStrategy},
+        HandleResults,
+    },
+    serde_json::{json, Value},
+    std::{
+        io::{self, Write},
+        time::{Duration, Instant},
+    },
+};
+
+/// How often the messages are sent during the search.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Paints the line with its icon.
+type IconPainter = Box<dyn Fn(&str) -> String>;
+
+/// Sends the results to vim-clap as a dynamic provider does.
+///
+/// Keeps only the best `number` results, sends the updates during the search
+/// not more often than every 200 milliseconds, and the final message
+/// in the [`finish`] method.
+///
+/// Just like with the [`LineWriter`], the first write error stops
+/// any further writes, and is returned by the [`finish`].
+///
+/// [`finish`]: #method.finish
+/// [`LineWriter`]: ../struct.LineWriter.html
+pub struct DynProvider<W: Write, S = ByScore> {
+    writer: W,
+    merger: Merger<S>,
+    total: usize,
+    // Top results were changed after the last message.
+    changed: bool,
+    last_update: Instant,
+    // The painter and the number of chars of its icon.
+    icons: Option<(IconPainter, usize)>,
+    id: Option<u64>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> DynProvider<W> {
+    /// Best scores are sent first.
+    #[inline]
+    pub fn new(writer: W, number: usize) -> Self {
+        Self::with_strategy(writer, number, ByScore)
+    }
+}
+
+impl<W: Write, S: SortStrategy> DynProvider<W, S> {
+    pub fn with_strategy(writer: W, number: usize, strategy: S) -> Self {
+        Self {
+            writer,
+            merger: Merger::new(number, strategy),
+            total: 0,
+            changed: false,
+            last_update: Instant::now(),
+            icons: None,
+            id: None,
+            error: None,
+        }
+    }
+
+    /// Paints every sent line with the icon, e.g. with the vim-clap's `IconPainter`.
+    ///
+    /// `icon_len` is the number of chars, that the painter adds before the line,
+    /// the indices are shifted by it.
+    pub fn with_icons(self, painter: impl Fn(&str) -> String + 'static, icon_len: usize) -> Self {
+        Self {
+            icons: Some((Box::new(painter), icon_len)),
+            ..self
+        }
+    }
+
+    /// Tags every message with the `id` of the request.
+    #[inline]
+    pub fn with_id(self, id: u64) -> Self {
+        Self {
+            id: Some(id),
+            ..self
+        }
+    }
+
+    /// Sends the final message and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.error.is_none() {
+            self.changed = true;
+            self.error = self.send().err();
+        }
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.writer),
+        }
+    }
+
+    fn send(&mut self) -> io::Result<()> {
+        let mut msg = if self.changed {
+            let (lines, indices): (Vec<String>, Vec<Vec<usize>>) = match &self.icons {
+                Some((paint, icon_len)) => self
+                    .merger
+                    .items()
+                    .iter()
+                    .map(|(s, _, pos)| (paint(s), pos.iter().map(|p| p + icon_len).collect()))
+                    .unzip(),
+                None => self
+                    .merger
+                    .items()
+                    .iter()
+                    .map(|(s, _, pos)| (s.clone(), pos.to_vec()))
+                    .unzip(),
+            };
+            json!({ "total": self.total, "lines": lines, "indices": indices })
+        } else {
+            json!({ "total": self.total })
+        };
+        if let (Some(id), Value::Object(map)) = (self.id, &mut msg) {
+            map.insert("id".into(), id.into());
+        }
+        let msg = msg.to_string();
+
+        self.changed = false;
+        self.last_update = Instant::now();
+        writeln!(self.writer, "Content-length: {}\n\n{}", msg.len(), msg)?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write, S: SortStrategy> HandleResults for DynProvider<W, S> {
+    fn handle_results(&mut self, results: Vec<MWP>) {
+        self.total += results.len();
+        self.changed |= self.merger.merge_delta(results).inserted() != 0;
+
+        if self.error.is_none() && self.last_update.elapsed() >= UPDATE_INTERVAL {
+            self.error = self.send().err();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn final_message_has_top_lines() {
+        let mut provider = DynProvider::new(Vec::new(), 1).with_icons(|s| format!("* {}", s), 2);
+        provider.handle_results(vec![
+            ("a.rs:1:1:fn".into(), 1, Box::new([9])),
+            ("b.rs:1:1:fn".into(), 2, Box::new([9, 10])),
+        ]);
+
+        let out = String::from_utf8(provider.finish().unwrap()).unwrap();
+        let msg = r#"{"indices":[[11,12]],"lines":["* b.rs:1:1:fn"],"total":2}"#;
+        assert_eq!(out, format!("Content-length: {}\n\n{}\n", msg.len(), msg));
+    }
+}
@@ -0,0 +1,102 @@
+//! Saving of the finished search to a file, so the results of the expensive
+//! search could be seen again later, without searching again.
+//!
+//! The file is JSON: the needle, the rules, the results and the statistics.
+
+use {
+    crate::{fzy_algo::scoring_utils::MWP, Rules, SearchStats},
+    serde::{Deserialize, Serialize},
+    std::{
+        fs::File,
+        io::{self, BufReader, BufWriter, Write},
+        path::Path,
+    },
+};
+
+/// The version of the file format, the files of other versions are not loaded.
+const FORMAT_VERSION: u32 = 1;
+
+/// The finished search, along with everything needed to show it again.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fulf::{saved::SavedSearch, Rules, SearchStats};
+///
+/// let results = vec![("src/main.rs:1:1:fn main()".into(), 7, vec![0, 3].into_boxed_slice())];
+/// let saved = SavedSearch::new("fn main", Rules::new(), results, SearchStats::default());
+/// saved.save("monorepo.fulf.json").unwrap();
+///
+/// let loaded = SavedSearch::load("monorepo.fulf.json").unwrap();
+/// assert_eq!(loaded.results, saved.results);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    version: u32,
+    pub needle: String,
+    pub rules: Rules,
+    pub results: Vec<MWP>,
+    pub stats: SearchStats,
+}
+
+impl SavedSearch {
+    #[inline]
+    pub fn new(needle: &str, rules: Rules, results: Vec<MWP>, stats: SearchStats) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            needle: needle.into(),
+            rules,
+            results,
+            stats,
+        }
+    }
+
+    /// Writes the search to the file, replacing the old one.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()
+    }
+
+    /// Reads the search, saved by the [`save`].
+    ///
+    /// Returns the `InvalidData` error for the file of another format version.
+    ///
+    /// [`save`]: #method.save
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let saved: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if saved.version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown version of the saved search: {}", saved.version),
+            ));
+        }
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::temp_tree::TempTree};
+
+    #[test]
+    fn search_is_reloaded() {
+        let tree = TempTree::new("saved");
+        let path = tree.join("search.json");
+        let results: Vec<MWP> = vec![("a.rs:1:1:fn x()".into(), 7, Box::new([0, 1]))];
+        let stats = SearchStats {
+            files_scanned: 3,
+            ..SearchStats::default()
+        };
+        let rules = Rules::builder().results_cap(16).build().unwrap();
+        SavedSearch::new("fx", rules, results.clone(), stats.clone())
+            .save(&path)
+            .unwrap();
+
+        let loaded = SavedSearch::load(&path).unwrap();
+        assert_eq!(loaded.needle, "fx");
+        assert_eq!(loaded.results, results);
+        assert_eq!(loaded.stats, stats);
+        assert_eq!(loaded.rules.results_cap, 16);
+    }
+}
@@ -0,0 +1,376 @@
+//! A long-lived search process, driven by JSON messages.
+//!
+//! Every request is one line of JSON:
+//!
+//! ```text
+//! {"id": 1, "method": "search", "params": {"root": ".", "needle": "fn", "number": 100}}
+//! ```
+//!
+//! Methods:
+//!
+//! * `search` — cancels the running search and starts the new one;
+//!   the walk of every root is done only once, the next searches
+//!   in the same root use the cached walk;
+//!
+//! * `update_query` — `{"needle": "fn main"}`, searches the new needle
+//!   in the root of the last search;
+//!
+//! * `cancel` — cancels the running search;
+//!
+//! * `stats` — the statistics of the last finished search;
+//!
+//! * `index` — `{"root": "."}`, builds the [`Index`] of the root,
+//!   the next searches in it read only the files with every byte of the needle;
+//!   with the `"path": "/tmp/fulf.idx"` the index is loaded from that file,
+//!   or is built and saved there;
+//!
+//! * `update_index` — `{"root": ".", "paths": ["src/main.rs"]}`,
+//!   reads the changed files of the indexed root again, e.g. on their save;
+//!
+//! * `save_session` — `{"path": "/tmp/fulf"}`, saves the walked roots,
+//!   the last search and the frecency of the needles into the folder,
+//!   see the [`SearchSession`];
+//!
+//! * `restore_session` — `{"path": "/tmp/fulf"}`, restores the saved roots,
+//!   so the next searches in them don't walk them again;
+//!   replied with the last search and the needles, the most frecent first:
+//!   `{"last": {"root": ".", "needle": "fn", "number": 100}, "recent": ["fn"]}`.
+//!
+//! The results are sent as the [`DynProvider`] messages,
+//! tagged with the `id` of the request, that started the search.
+//! Other methods are replied with `{"id": 1, "result": ...}`,
+//! any failed request is replied with `{"id": 1, "error": "..."}`.
+//!
+//! [`DynProvider`]: ../provider/struct.DynProvider.html
+//! [`SearchSession`]: ../session/struct.SearchSession.html
+//! [`Index`]: ../index/struct.Index.html
+
+use {
+    crate::{
+        debounce::stop,
+        filepath_cache::{serialize_ordered, IndexedCache, NotUtf8},
+        fzy_search,
+        index::Index,
+        provider::DynProvider,
+        session::SearchSession,
+        CancelToken, HandleResults, Rules, SearchStats,
+    },
+    serde::Deserialize,
+    serde_json::{json, Value},
+    std::{
+        collections::HashMap,
+        io::{self, BufRead, Write},
+        path::Path,
+        sync::{Arc, Mutex},
+        thread::{self, JoinHandle},
+    },
+};
+
+/// Number of the best results, sent if the request doesn't say otherwise.
+const DEFAULT_NUMBER: usize = 100;
+
+/// Reads the requests until the end of the input, the replies go to the stdout.
+///
+/// Returns only on the end of the input or on the IO error.
+#[inline]
+pub fn run_forever(reader: impl BufRead) -> io::Result<()> {
+    run_forever_with(reader, Rules::new())
+}
+
+/// Just like the [`run_forever`], but every search uses the given rules,
+/// e.g. the ones from the user config.
+///
+/// The `cancel` token of the rules is replaced for every search.
+///
+/// [`run_forever`]: fn.run_forever.html
+pub fn run_forever_with(reader: impl BufRead, rules: Rules) -> io::Result<()> {
+    let mut server = Server {
+        rules,
+        ..Server::default()
+    };
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let id = request.id;
+                match server.handle(request) {
+                    Ok(None) => continue,
+                    Ok(Some(result)) => json!({ "id": id, "result": result }),
+                    Err(e) => json!({ "id": id, "error": e }),
+                }
+            }
+            Err(e) => json!({ "error": format!("invalid message: {}", e) }),
+        };
+        write_message(&reply)?;
+    }
+
+    stop(server.in_flight.take());
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: u64,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    root: Option<String>,
+    needle: String,
+    number: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct IndexParams {
+    root: String,
+    #[serde(default)]
+    paths: Vec<String>,
+    path: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SessionParams {
+    path: String,
+}
+
+#[derive(Default)]
+struct Server {
+    session: SearchSession,
+    indexes: HashMap<String, Index>,
+    in_flight: Option<(CancelToken, JoinHandle<()>)>,
+    stats: Arc<Mutex<Option<SearchStats>>>,
+    rules: Rules,
+}
+
+impl Server {
+    /// Returns the reply, if the request should be replied right away.
+    fn handle(&mut self, request: Request) -> Result<Option<Value>, String> {
+        match &*request.method {
+            "search" => {
+                let params = search_params(request.params)?;
+                let root = params.root.ok_or("no root to search in")?;
+                let number = params.number.unwrap_or(DEFAULT_NUMBER);
+                self.search(request.id, root, params.needle, number)?;
+                Ok(None)
+            }
+            "update_query" => {
+                let params = search_params(request.params)?;
+                let last = self.session.last.clone().ok_or("no search to update")?;
+                let number = params.number.unwrap_or(last.number);
+                self.search(request.id, last.root, params.needle, number)?;
+                Ok(None)
+            }
+            "cancel" => {
+                if let Some((cancel, _thread)) = &self.in_flight {
+                    cancel.cancel();
+                }
+                Ok(Some(Value::Bool(true)))
+            }
+            "stats" => serde_json::to_value(&*self.stats.lock().unwrap())
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            "index" => {
+                let params = index_params(request.params)?;
+                let root = Path::new(&params.root);
+                let index = match &params.path {
+                    Some(path) => Index::load_or_build(root, &self.rules, path),
+                    None => Index::build(root, &self.rules),
+                }
+                .map_err(|e| e.to_string())?;
+                let files = index.files_count();
+                self.indexes.insert(params.root, index);
+                Ok(Some(json!({ "files": files })))
+            }
+            "update_index" => {
+                let params = index_params(request.params)?;
+                let index = self
+                    .indexes
+                    .get_mut(&params.root)
+                    .ok_or("no index of the root")?;
+                index.update(&params.paths);
+                Ok(Some(json!({ "files": index.files_count() })))
+            }
+            "save_session" => {
+                let params = session_params(request.params)?;
+                self.session
+                    .save(params.path)
+                    .map(|()| Some(Value::Bool(true)))
+                    .map_err(|e| format!("can't save the session: {}", e))
+            }
+            "restore_session" => {
+                let params = session_params(request.params)?;
+                self.session = SearchSession::restore(params.path)
+                    .map_err(|e| format!("can't restore the session: {}", e))?;
+                Ok(Some(json!({
+                    "last": self.session.last,
+                    "recent": self.session.frecency.ranked(),
+                })))
+            }
+            method => Err(format!("unknown method: {}", method)),
+        }
+    }
+
+    fn search(
+        &mut self,
+        id: u64,
+        root: String,
+        needle: String,
+        number: usize,
+    ) -> Result<(), String> {
+        self.rules
+            .check_needle(&needle)
+            .map_err(|e| e.to_string())?;
+
+        stop(self.in_flight.take());
+
+        let cache = match (self.indexes.get(&root), self.session.cache(&root)) {
+            (Some(index), _) => Arc::new(index.candidates(&needle)),
+            (None, Some(cache)) => cache,
+            (None, None) => {
+                let cache = self.walk(&root)?;
+                // The cache is read only once, so the session keeps its clone.
+                self.session.insert(root.clone(), cache.clone());
+                Arc::new(cache)
+            }
+        };
+        self.session.searched(root.clone(), needle.clone(), number);
+
+        let cancel = CancelToken::new();
+        let mut r = self.rules.clone();
+        r.cancel = cancel.clone();
+        let stats = Arc::clone(&self.stats);
+
+        let t = thread::spawn(move || {
+            let mut provider = DynProvider::new(Output::default(), number).with_id(id);
+
+            let result = fzy_search(Path::new(&root).into(), needle.into(), cache, r, |batch| {
+                provider.handle_results(batch)
+            });
+            match result {
+                Ok(search_stats) => {
+                    let _any_result = provider.finish();
+                    *stats.lock().unwrap() = Some(search_stats);
+                }
+                Err(e) => {
+                    let _any_result = write_message(&json!({ "id": id, "error": e.to_string() }));
+                }
+            }
+        });
+        self.in_flight = Some((cancel, t));
+
+        Ok(())
+    }
+
+    fn walk(&self, root: &str) -> Result<IndexedCache, String> {
+        // The walk errors are not interesting to the editor:
+        // there's nothing it could do with them.
+        let builder = self.rules.walk_builder(root).map_err(|e| e.to_string())?;
+        let options = self.rules.cache_options();
+        serialize_ordered(root, builder, options, NotUtf8::IgnorePath, |_| ())
+            .map_err(|e| format!("can't walk the root: {:?}", e))
+    }
+}
+
+fn search_params(params: Value) -> Result<SearchParams, String> {
+    serde_json::from_value(params).map_err(|e| format!("invalid params: {}", e))
+}
+
+fn index_params(params: Value) -> Result<IndexParams, String> {
+    serde_json::from_value(params).map_err(|e| format!("invalid params: {}", e))
+}
+
+fn session_params(params: Value) -> Result<SessionParams, String> {
+    serde_json::from_value(params).map_err(|e| format!("invalid params: {}", e))
+}
+
+/// Collects one message and writes it to the stdout on flush,
+/// so messages from the search thread and the main thread are never mixed.
+#[derive(Default)]
+struct Output(Vec<u8>);
+
+impl Write for Output {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let result = stdout.write_all(&self.0).and_then(|()| stdout.flush());
+        self.0.clear();
+        result
+    }
+}
+
+fn write_message(msg: &Value) -> io::Result<()> {
+    let msg = msg.to_string();
+    let mut output = Output::default();
+    writeln!(output, "Content-length: {}\n\n{}", msg.len(), msg)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::temp_tree::TempTree};
+
+    fn request(method: &str, params: Value) -> Request {
+        Request {
+            id: 1,
+            method: method.into(),
+            params,
+        }
+    }
+
+    #[test]
+    fn walked_root_is_searched_again() {
+        let tree = TempTree::new("server");
+        tree.write("a.rs", "fn main() {}\n")
+            .write("b.rs", "fn foo() {}\nlet x = 1;\n");
+        let root = tree.path().to_str().unwrap();
+
+        let mut server = Server::default();
+        let mut search = |method: &str, params: Value| {
+            assert_eq!(server.handle(request(method, params)), Ok(None));
+            let (_cancel, t) = server.in_flight.take().unwrap();
+            t.join().unwrap();
+            server.stats.lock().unwrap().take().unwrap()
+        };
+        let first = search("search", json!({ "root": root, "needle": "fn" }));
+        let second = search("search", json!({ "root": root, "needle": "fn" }));
+        let updated = search("update_query", json!({ "needle": "let" }));
+
+        assert_eq!((first.files_scanned, first.lines_matched), (2, 2));
+        assert_eq!((second.files_scanned, second.lines_matched), (2, 2));
+        assert_eq!((updated.files_scanned, updated.lines_matched), (2, 1));
+    }
+
+    #[test]
+    fn bad_requests_are_errors() {
+        let mut server = Server::default();
+
+        assert!(server.handle(request("nope", Value::Null)).is_err());
+        assert!(server
+            .handle(request("update_query", json!({ "needle": "fn" })))
+            .is_err());
+        assert!(server
+            .handle(request("search", json!({ "root": "." })))
+            .is_err());
+        assert_eq!(
+            server.handle(request("stats", Value::Null)),
+            Ok(Some(Value::Null))
+        );
+        assert!(server
+            .handle(request("restore_session", json!({ "path": "/nope/fulf" })))
+            .is_err());
+    }
+}
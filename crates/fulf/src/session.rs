@@ -0,0 +1,357 @@
+//! The warm state of the long-lived search: the walked roots, the last search
+//! and the frecency of the searched needles.
+//!
+//! The session could be saved into the folder on the editor's exit
+//! and restored on its next start, so the first search in the known root
+//! doesn't wait for the walk.
+//!
+//! The folder holds `session.json` with the roots, the last search and the frecency,
+//! and one `<n>.cache` file with the bytes of the files cache of every root.
+
+use {
+    crate::filepath_cache::{deserialize, IndexedCache},
+    serde::{Deserialize, Serialize},
+    std::{
+        cmp::Reverse,
+        collections::HashMap,
+        fs::{self, File},
+        io::{self, BufReader, BufWriter, Write},
+        path::Path,
+        sync::Arc,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// The version of the snapshot format, the snapshots of other versions are not restored.
+const FORMAT_VERSION: u32 = 2;
+
+/// The name of the snapshot's index file.
+const INDEX_FILE: &str = "session.json";
+
+/// Number of the needles, that the [`Frecency`] remembers.
+///
+/// [`Frecency`]: struct.Frecency.html
+const FRECENCY_CAP: usize = 256;
+
+/// The files caches of the walked roots, the last search
+/// and the frecency of the needles.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fulf::session::SearchSession;
+///
+/// let mut session = SearchSession::restore("/tmp/fulf-session").unwrap_or_default();
+/// if let Some(cache) = session.cache(".") {
+///     println!("{} files are known", cache.files_count());
+/// }
+/// if let Some(last) = &session.last {
+///     println!("the last search: {} in {}", last.needle, last.root);
+/// }
+/// session.save("/tmp/fulf-session").unwrap();
+/// ```
+#[derive(Default)]
+pub struct SearchSession {
+    caches: HashMap<String, IndexedCache>,
+    pub last: Option<LastSearch>,
+    pub frecency: Frecency,
+}
+
+/// The search to run again, e.g. on the editor's start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastSearch {
+    pub root: String,
+    pub needle: String,
+    /// Number of the results.
+    pub number: usize,
+}
+
+/// How often and how recently the needles were searched,
+/// e.g. for the history of the prompt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frecency {
+    // The number of the searches and the last one, in seconds since the epoch.
+    needles: HashMap<String, (u32, u64)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    roots: Vec<String>,
+    last: Option<LastSearch>,
+    frecency: Frecency,
+}
+
+impl SearchSession {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The files cache of the root, if it was walked.
+    ///
+    /// The cache is read only once, so it's the new clone for every search.
+    #[inline]
+    pub fn cache(&self, root: &str) -> Option<Arc<IndexedCache>> {
+        self.caches.get(root).map(|cache| Arc::new(cache.clone()))
+    }
+
+    /// Remembers the files cache of the root, replacing the old one.
+    #[inline]
+    pub fn insert(&mut self, root: String, cache: IndexedCache) {
+        self.caches.insert(root, cache);
+    }
+
+    /// Forgets the files cache of the root, e.g. if its files were changed.
+    #[inline]
+    pub fn forget(&mut self, root: &str) -> Option<IndexedCache> {
+        self.caches.remove(root)
+    }
+
+    /// Remembers the search as the last one and counts its needle in the frecency.
+    pub fn searched(&mut self, root: String, needle: String, number: usize) {
+        self.frecency.visit(&needle);
+        self.last = Some(LastSearch {
+            root,
+            needle,
+            number,
+        });
+    }
+
+    /// Writes the session into the folder, creating it if needed.
+    ///
+    /// The index is written last, so the interrupted save leaves
+    /// either the old snapshot or no snapshot at all.
+    pub fn save(&self, folder: impl AsRef<Path>) -> io::Result<()> {
+        let folder = folder.as_ref();
+        fs::create_dir_all(folder)?;
+
+        // Old index points to the cache files, that are going to be replaced.
+        match fs::remove_file(folder.join(INDEX_FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+
+        let mut roots = Vec::with_capacity(self.caches.len());
+        for (n, (root, cache)) in self.caches.iter().enumerate() {
+            fs::write(folder.join(format!("{}.cache", n)), cache.show_cache())?;
+            roots.push(root.clone());
+        }
+        // The caches of the roots, that were forgotten since the old save.
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            let stale = path.extension().map_or(false, |ext| ext == "cache")
+                && path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str()?.parse::<usize>().ok())
+                    .map_or(false, |n| n >= roots.len());
+            if stale {
+                fs::remove_file(path)?;
+            }
+        }
+
+        let snapshot = Snapshot {
+            version: FORMAT_VERSION,
+            roots,
+            last: self.last.clone(),
+            frecency: self.frecency.clone(),
+        };
+        let mut writer = BufWriter::new(File::create(folder.join(INDEX_FILE))?);
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()
+    }
+
+    /// Reads the session, saved by the [`save`].
+    ///
+    /// Returns the `InvalidData` error for the snapshot of another format version
+    /// or with the broken cache.
+    ///
+    /// [`save`]: #method.save
+    pub fn restore(folder: impl AsRef<Path>) -> io::Result<Self> {
+        let folder = folder.as_ref();
+        let file = File::open(folder.join(INDEX_FILE))?;
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+        if snapshot.version != FORMAT_VERSION {
+            return Err(invalid(format!(
+                "unknown version of the session snapshot: {}",
+                snapshot.version
+            )));
+        }
+
+        let mut caches = HashMap::with_capacity(snapshot.roots.len());
+        for (n, root) in snapshot.roots.into_iter().enumerate() {
+            let bytes = fs::read(folder.join(format!("{}.cache", n)))?;
+            let cache = deserialize(bytes)
+                .map_err(|_| invalid(format!("invalid files cache of the root {}", root)))?;
+            caches.insert(root, cache);
+        }
+
+        Ok(Self {
+            caches,
+            last: snapshot.last,
+            frecency: snapshot.frecency,
+        })
+    }
+}
+
+impl Frecency {
+    /// Counts the search of the needle now.
+    #[inline]
+    pub fn visit(&mut self, needle: &str) {
+        self.visit_at(needle, now());
+    }
+
+    /// The needles, the most frecent first.
+    #[inline]
+    pub fn ranked(&self) -> Vec<&str> {
+        self.ranked_at(now())
+    }
+
+    fn visit_at(&mut self, needle: &str, now: u64) {
+        let entry = self.needles.entry(needle.into()).or_insert((0, now));
+        entry.0 = entry.0.saturating_add(1);
+        entry.1 = now;
+
+        if self.needles.len() > FRECENCY_CAP {
+            let worst = self
+                .needles
+                .iter()
+                .min_by_key(|(_, &visits)| frecency(visits, now))
+                .map(|(needle, _)| needle.clone());
+            if let Some(worst) = worst {
+                self.needles.remove(&worst);
+            }
+        }
+    }
+
+    fn ranked_at(&self, now: u64) -> Vec<&str> {
+        let mut ranked: Vec<(&str, u64, u64)> = self
+            .needles
+            .iter()
+            .map(|(needle, &visits)| (&**needle, frecency(visits, now), visits.1))
+            .collect();
+        // The more recent one goes first on equal frecencies.
+        ranked.sort_by_key(|&(_, frecency, last)| (Reverse(frecency), Reverse(last)));
+        ranked.into_iter().map(|(needle, ..)| needle).collect()
+    }
+}
+
+/// The number of the searches, weighted by the age of the last one.
+fn frecency((count, last): (u32, u64), now: u64) -> u64 {
+    const HOUR: u64 = 60 * 60;
+    const DAY: u64 = 24 * HOUR;
+
+    let weight = match now.saturating_sub(last) {
+        age if age < HOUR => 8,
+        age if age < DAY => 4,
+        age if age < 7 * DAY => 2,
+        _ => 1,
+    };
+    u64::from(count) * weight
+}
+
+/// Seconds since the epoch, zero if the clock is before it.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{filepath_cache::serialize_paths, temp_tree::TempTree},
+    };
+
+    #[test]
+    fn session_is_restored() {
+        let tree = TempTree::new("session");
+        let folder = tree.path();
+
+        let mut session = SearchSession::new();
+        session.insert("/a".into(), serialize_paths(vec!["/a/x.rs", "/a/y.rs"]));
+        session.insert("/b".into(), serialize_paths(vec!["/b/z.rs"]));
+        session.searched("/a".into(), "fn".into(), 50);
+        session.searched("/a".into(), "fn main".into(), 50);
+        session.searched("/a".into(), "fn main".into(), 50);
+
+        session.save(folder).unwrap();
+        let restored = SearchSession::restore(folder).unwrap();
+        tree.write("0.cache", b"nope");
+        let broken = SearchSession::restore(folder);
+
+        let last = LastSearch {
+            root: "/a".into(),
+            needle: "fn main".into(),
+            number: 50,
+        };
+        assert_eq!(restored.last, Some(last));
+        assert_eq!(restored.frecency.ranked(), ["fn main", "fn"]);
+        assert_eq!(restored.cache("/a").unwrap().files_count(), 2);
+        assert_eq!(
+            restored.cache("/b").unwrap().show_cache(),
+            session.cache("/b").unwrap().show_cache()
+        );
+        assert!(restored.cache("/c").is_none());
+        assert_eq!(broken.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn every_cache_is_read_from_the_start() {
+        let mut session = SearchSession::new();
+        session.insert("/a".into(), serialize_paths(vec!["/a/x.rs", "/a/y.rs"]));
+
+        let count = || {
+            let cache = session.cache("/a").unwrap();
+            let mut files = cache.stream_iter().unwrap();
+            let mut count = 0;
+            while let Some(_file) = files.read_next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+        assert_eq!(count(), 2);
+        assert_eq!(count(), 2);
+    }
+
+    #[test]
+    fn stale_caches_are_removed() {
+        let tree = TempTree::new("stale-session");
+        let folder = tree.path();
+
+        let mut session = SearchSession::new();
+        session.insert("/a".into(), serialize_paths(vec!["/a/x.rs"]));
+        session.insert("/b".into(), serialize_paths(vec!["/b/y.rs"]));
+        session.save(folder).unwrap();
+        assert!(tree.join("1.cache").is_file());
+
+        session.forget("/b");
+        session.save(folder).unwrap();
+        assert!(tree.join("0.cache").is_file());
+        assert!(!tree.join("1.cache").exists());
+    }
+
+    #[test]
+    fn recent_needles_outrank_old_ones() {
+        let day = 24 * 60 * 60;
+        let mut frecency = Frecency::default();
+        frecency.visit_at("old", 0);
+        frecency.visit_at("old", 1);
+        frecency.visit_at("new", 10 * day);
+
+        assert_eq!(frecency.ranked_at(10 * day), ["new", "old"]);
+        // Both are old enough to weigh the same.
+        assert_eq!(frecency.ranked_at(30 * day), ["old", "new"]);
+
+        for n in 0..FRECENCY_CAP {
+            frecency.visit_at(&n.to_string(), 30 * day);
+        }
+        assert_eq!(frecency.ranked_at(30 * day).len(), FRECENCY_CAP);
+        assert!(!frecency.needles.contains_key("new"));
+    }
+}
@@ -0,0 +1,121 @@
+//! The `tags` files of the ctags, for the fuzzy search of the tag names.
+//!
+//! Each line of the file is `name<Tab>file<Tab>address;"<Tab>fields`,
+//! where the address is the line number or the search pattern,
+//! and the fields are the kind of the tag and the `key:value` pairs.
+//! The `!_TAG_` lines are the info about the file itself, and are skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! use fulf::tags::{filter_tags, parse};
+//!
+//! let text = concat!(
+//!     "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted/\n",
+//!     "main\tsrc/main.rs\t/^fn main() {$/;\"\tf\tline:3\n",
+//!     "Rules\tsrc/interface/mod.rs\t72;\"\ts\n",
+//! );
+//! let tags: Vec<_> = parse(text).collect();
+//! assert_eq!(tags.len(), 2);
+//!
+//! let matched = filter_tags("rl", &tags);
+//! assert_eq!(matched[0].0.file, "src/interface/mod.rs");
+//! assert_eq!(matched[0].0.line, Some(72));
+//! ```
+
+use crate::filter::filter_by;
+
+/// The tag of the `tags` file, borrowed from its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag<'a> {
+    /// The name of the tag, the one that is searched.
+    pub name: &'a str,
+    /// The file of the tag, relative to the `tags` file usually.
+    pub file: &'a str,
+    /// The ex command to find the tag: the line number,
+    /// or the search pattern with its delimiters, like `/^fn main() {$/`.
+    pub address: &'a str,
+    /// The line of the tag, if the address is the line number,
+    /// or the `line:` field is given.
+    pub line: Option<usize>,
+    /// The kind of the tag, like `f` or `function`.
+    pub kind: Option<&'a str>,
+}
+
+/// Parses the line of the `tags` file.
+///
+/// `None` for the `!_TAG_` lines and the lines, that are not tags.
+pub fn parse_line(line: &str) -> Option<Tag<'_>> {
+    if line.starts_with("!_TAG_") {
+        return None;
+    }
+    let line = line.strip_suffix('\r').unwrap_or(line);
+
+    let mut parts = line.splitn(3, '\t');
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let file = parts.next().filter(|file| !file.is_empty())?;
+    let rest = parts.next()?;
+
+    // The pattern could have the tabs, but not the `;"` followed by the tab.
+    let (address, fields) = match rest.find(";\"\t") {
+        Some(idx) => (&rest[..idx], &rest[idx + 3..]),
+        None => (rest.strip_suffix(";\"").unwrap_or(rest), ""),
+    };
+
+    let mut tag = Tag {
+        name,
+        file,
+        address,
+        line: address.parse().ok(),
+        kind: None,
+    };
+    for field in fields.split('\t').filter(|field| !field.is_empty()) {
+        match field.find(':') {
+            Some(idx) => match (&field[..idx], &field[idx + 1..]) {
+                ("kind", kind) => tag.kind = Some(kind),
+                ("line", line) => tag.line = tag.line.or_else(|| line.parse().ok()),
+                _other_field => (),
+            },
+            // The kind without the `kind:`.
+            None => tag.kind = Some(field),
+        }
+    }
+    Some(tag)
+}
+
+/// Parses all the tags of the `tags` file, skipping the lines, that are not tags.
+pub fn parse(text: &str) -> impl Iterator<Item = Tag<'_>> {
+    text.lines().filter_map(parse_line)
+}
+
+/// Scores the names of the tags with the fzy algorithm,
+/// returns the matched tags, best scores first.
+///
+/// Positions point to the chars of the tag name.
+pub fn filter_tags<'t, 'a>(
+    needle: &str,
+    tags: &'t [Tag<'a>],
+) -> Vec<(&'t Tag<'a>, i32, Box<[usize]>)> {
+    filter_by(needle, tags, |tag| tag.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_and_fields() {
+        let tag =
+            parse_line("new\tsrc/lib.rs\t/^\tpub fn new() {$/;\"\tkind:function\tline:42").unwrap();
+        assert_eq!(tag.address, "/^\tpub fn new() {$/");
+        assert_eq!(tag.kind, Some("function"));
+        assert_eq!(tag.line, Some(42));
+
+        let tag = parse_line("MAX\tsrc/lib.rs\t7").unwrap();
+        assert_eq!(tag.line, Some(7));
+        assert_eq!(tag.kind, None);
+
+        assert_eq!(parse_line("!_TAG_PROGRAM_NAME\tctags\t//"), None);
+        assert_eq!(parse_line("no tabs here"), None);
+    }
+}
@@ -0,0 +1,73 @@
+//! The temporary folders of the tests.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+/// Makes the names unique, when the tests of one run share the name.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The folder in the temporary dir, removed with all its files on drop,
+/// even if the test panics.
+#[derive(Debug)]
+pub(crate) struct TempTree {
+    root: PathBuf,
+}
+
+impl TempTree {
+    /// Creates the empty folder, unique for the `name`, the process and the call.
+    pub(crate) fn new(name: &str) -> Self {
+        let id = NEXT_ID.fetch_add(1, Relaxed);
+        let root = env::temp_dir().join(format!("fulf-{}-{}-{}", name, process::id(), id));
+        // Left by the killed run with the same pid.
+        let _any_result = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    #[inline]
+    pub(crate) fn path(&self) -> &Path {
+        &self.root
+    }
+
+    #[inline]
+    pub(crate) fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Writes the file, its folders are created too.
+    pub(crate) fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> &Self {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+        self
+    }
+}
+
+impl Drop for TempTree {
+    fn drop(&mut self) {
+        let _any_result = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_is_removed_on_drop() {
+        let (first, second) = (TempTree::new("tree"), TempTree::new("tree"));
+        assert_ne!(first.path(), second.path());
+
+        first.write("src/a.rs", "fn a() {}\n");
+        assert!(first.join("src/a.rs").is_file());
+        let root = first.path().to_owned();
+        drop(first);
+        assert!(!root.exists());
+    }
+}
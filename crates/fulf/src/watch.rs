@@ -0,0 +1,357 @@
+//! Watch mode: the search stays live, while the files of the root are edited.
+//!
+//! The file system notifies the watcher about the changed files,
+//! and only those are searched again; the folders of the new files
+//! are listed by the rules, so the ignored files are not searched.
+
+use {
+    crate::{
+        filepath_cache::{serialize_paths, serialize_relative, IndexedCache, NotUtf8},
+        fzy_algo::scoring_utils::MWP,
+        fzy_search, group_by_path,
+        matcher::{self, Fzy},
+        DefaultFormatter, Rules,
+    },
+    notify::{RecommendedWatcher, RecursiveMode, Watcher as _},
+    std::{
+        collections::{HashMap, HashSet},
+        error::Error,
+        fmt,
+        path::{self, Path, PathBuf},
+        sync::Arc,
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    },
+};
+
+/// The changes of the results, pushed by the [`Watcher`].
+///
+/// The paths are relative to the root, like the ones of the cache.
+///
+/// [`Watcher`]: struct.Watcher.html
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    /// The results of the first search, in the batches.
+    Found(Vec<MWP>),
+    /// The file was changed or created:
+    /// its results replace the old ones, if there are no results,
+    /// the file doesn't match anymore.
+    Changed { path: String, results: Vec<MWP> },
+    /// The file was removed, and so were its results.
+    Removed(String),
+}
+
+/// The error of the [`Watcher::new`].
+///
+/// [`Watcher::new`]: struct.Watcher.html#method.new
+#[derive(Debug)]
+pub enum WatchError {
+    /// One of the [`include_globs`] or of the [`exclude_globs`] is invalid.
+    ///
+    /// [`include_globs`]: ../struct.Rules.html#structfield.include_globs
+    /// [`exclude_globs`]: ../struct.Rules.html#structfield.exclude_globs
+    Glob(ignore::Error),
+    /// The root could not be watched.
+    Notify(notify::Error),
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchError::Glob(e) => write!(f, "invalid glob: {}", e),
+            WatchError::Notify(e) => write!(f, "can't watch the root: {}", e),
+        }
+    }
+}
+
+impl Error for WatchError {}
+
+/// Searches the root, and then searches again the files, changed since.
+///
+/// Dropping the watcher stops it and waits for its thread.
+///
+/// # Examples
+///
+/// ```no_run
+/// use {
+///     fulf::{watch::Watcher, Rules},
+///     std::{path::Path, time::Duration},
+/// };
+///
+/// let watcher = Watcher::new(
+///     Path::new(".").into(),
+///     "fn main".into(),
+///     Rules::new(),
+///     Duration::from_millis(100),
+///     |event| println!("{:?}", event),
+/// )
+/// .unwrap();
+/// ```
+pub struct Watcher {
+    // Both are `Option` only to be taken in the `Drop`.
+    notifier: Option<RecommendedWatcher>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Starts watching the root and spawns the thread of the first search
+    /// and of the next ones.
+    ///
+    /// The changes, that come within the `interval` after the first one,
+    /// are searched together, e.g. all the files of the `git checkout`.
+    /// The watcher stops on its own, if the `cancel` token of the rules is cancelled.
+    pub fn new<E>(
+        root: Arc<Path>,
+        needle: Arc<str>,
+        rules: Rules,
+        interval: Duration,
+        mut on_event: E,
+    ) -> Result<Self, WatchError>
+    where
+        E: FnMut(WatchEvent) + Send + 'static,
+    {
+        rules.walk_builder(&root).map_err(WatchError::Glob)?;
+
+        // Watched before the first walk, so no change is missed.
+        let (sender, changes) = flume::unbounded::<PathBuf>();
+        let mut notifier = notify::recommended_watcher(move |event: notify::Result<_>| {
+            if let Ok(notify::Event { paths, .. }) = event {
+                for path in paths {
+                    let _any_result = sender.send(path);
+                }
+            }
+        })
+        .map_err(WatchError::Notify)?;
+        notifier
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(WatchError::Notify)?;
+
+        let thread = thread::spawn(move || {
+            let mut known: HashSet<String> = HashSet::new();
+            if let Some(cache) = walk(&root, &rules, None) {
+                known = paths(cache.clone());
+                let _any_result = fzy_search(
+                    Arc::clone(&root),
+                    Arc::clone(&needle),
+                    Arc::new(cache),
+                    rules.clone(),
+                    |batch| on_event(WatchEvent::Found(batch)),
+                );
+            }
+            // The notifications could have the canonical paths.
+            let canonical_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+            // Disconnected, once the watcher is dropped.
+            while let Ok(first) = changes.recv() {
+                let deadline = Instant::now() + interval;
+                let mut changed = HashSet::new();
+                changed.insert(first);
+                while let Ok(path) = changes.recv_deadline(deadline) {
+                    changed.insert(path);
+                }
+                if rules.cancel.is_cancelled() {
+                    break;
+                }
+
+                let mut searched = Vec::new();
+                let mut created = Vec::new();
+                for path in changed {
+                    let relative = match path
+                        .strip_prefix(&root)
+                        .or_else(|_| path.strip_prefix(&canonical_root))
+                    {
+                        Ok(relative) => relative,
+                        Err(_) => continue,
+                    };
+                    let (path, relative) = match relative.to_str() {
+                        Some(name) if !name.is_empty() => (root.join(relative), name.to_owned()),
+                        _ => continue,
+                    };
+
+                    if !path.exists() {
+                        // The removed folder removes all its files.
+                        let folder = format!("{}{}", relative, path::MAIN_SEPARATOR);
+                        let removed: Vec<String> = known
+                            .iter()
+                            .filter(|file| **file == relative || file.starts_with(&folder))
+                            .cloned()
+                            .collect();
+                        for file in removed {
+                            known.remove(&file);
+                            on_event(WatchEvent::Removed(file));
+                        }
+                    } else if known.contains(&relative) {
+                        searched.push(relative);
+                    } else if path.is_file() {
+                        created.push((path, relative));
+                    }
+                }
+
+                // Only the new files, that the walk would find, are searched.
+                if !created.is_empty() {
+                    let folders = created
+                        .iter()
+                        .filter_map(|(path, _)| Some(path.parent()?.to_path_buf()))
+                        .collect();
+                    let listed = walk(&root, &rules, Some(folders)).map(paths);
+                    for (_path, relative) in created {
+                        if listed.as_ref().map_or(false, |l| l.contains(&relative)) {
+                            known.insert(relative.clone());
+                            searched.push(relative);
+                        }
+                    }
+                }
+
+                if !searched.is_empty() {
+                    searched.sort_unstable();
+                    search_again(&root, &needle, &rules, searched, &mut on_event);
+                }
+            }
+        });
+
+        Ok(Self {
+            notifier: Some(notifier),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // Disconnects the channel of the changes, which stops the thread.
+        drop(self.notifier.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _any_result = thread.join();
+        }
+    }
+}
+
+/// Walks the root by the rules; with the `folders`, only the files right in those
+/// are listed, and only the folders on the way to them are read.
+fn walk(root: &Path, rules: &Rules, folders: Option<HashSet<PathBuf>>) -> Option<IndexedCache> {
+    let mut builder = rules.walk_builder(root).ok()?;
+    if let Some(folders) = folders {
+        builder.filter_entry(move |entry| {
+            let path = entry.path();
+            if entry.file_type().map_or(false, |ft| ft.is_dir()) {
+                folders.iter().any(|folder| folder.starts_with(path))
+            } else {
+                path.parent()
+                    .map_or(false, |parent| folders.contains(parent))
+            }
+        });
+    }
+
+    serialize_relative(
+        root,
+        builder,
+        rules.cache_options(),
+        NotUtf8::IgnorePath,
+        |_| (),
+    )
+    .ok()
+}
+
+/// The relative paths of the cache.
+fn paths(cache: IndexedCache) -> HashSet<String> {
+    let mut paths = HashSet::with_capacity(cache.files_count());
+    if let Ok(mut files) = cache.stream_iter() {
+        while let Ok(Some(path)) = files.read_next() {
+            paths.insert(path.to_owned());
+        }
+    }
+    paths
+}
+
+/// Searches the changed files in one search, every file gets its own event.
+fn search_again(
+    root: &Arc<Path>,
+    needle: &Arc<str>,
+    rules: &Rules,
+    files: Vec<String>,
+    on_event: &mut impl FnMut(WatchEvent),
+) {
+    let cache = serialize_paths(files.iter().map(String::as_str));
+    let mut matches = Vec::new();
+    let _any_result = matcher::searcher(Arc::clone(root), Arc::clone(needle), Arc::new(Fzy))
+        .match_spawner(Arc::new(cache), rules.clone(), |batch| {
+            matches.extend(batch)
+        });
+
+    let mut matches: HashMap<_, _> = group_by_path(matches).into_iter().collect();
+    for path in files {
+        let results = matches
+            .remove(path.as_str())
+            .unwrap_or_default()
+            .iter()
+            .map(|matched| matched.format(&DefaultFormatter))
+            .collect();
+        on_event(WatchEvent::Changed { path, results });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::temp_tree::TempTree, std::fs};
+
+    #[test]
+    fn changes_are_searched_again() {
+        let root = TempTree::new("watch");
+        let mut rules = Rules::builder().exclude_glob("*.log").build().unwrap();
+        rules.git_ignore = false;
+        let (sender, events) = flume::unbounded();
+        // One write could be notified twice, so the events are awaited, not counted.
+        let wait_for = |expected: &dyn Fn(&WatchEvent) -> bool| loop {
+            let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert!(
+                !matches!(&event, WatchEvent::Changed { path, .. } if path == "d.log"),
+                "the excluded file is searched"
+            );
+            if expected(&event) {
+                break event;
+            }
+        };
+
+        root.write("a.rs", "fn alpha() {}\n")
+            .write("c.rs", "fn gamma() {}\n");
+        let watcher = Watcher::new(
+            root.path().into(),
+            "alpha".into(),
+            rules,
+            Duration::from_millis(20),
+            move |event| sender.send(event).unwrap(),
+        )
+        .unwrap();
+
+        match wait_for(&|event| matches!(event, WatchEvent::Found(_))) {
+            WatchEvent::Found(batch) => assert_eq!(batch[0].0, "a.rs:1:1:fn alpha() {}"),
+            _ => unreachable!(),
+        }
+
+        fs::remove_file(root.join("a.rs")).unwrap();
+        wait_for(&|event| *event == WatchEvent::Removed("a.rs".into()));
+
+        root.write("d.log", "alpha\n")
+            .write("b.rs", "let alpha = 1;\n");
+        let found = |event: &WatchEvent| {
+            matches!(event, WatchEvent::Changed { path, results }
+                if path == "b.rs" && !results.is_empty())
+        };
+        match wait_for(&found) {
+            WatchEvent::Changed { results, .. } => {
+                assert_eq!(results[0].0, "b.rs:1:1:let alpha = 1;")
+            }
+            _ => unreachable!(),
+        }
+
+        root.write("b.rs", "let beta = 1;\n");
+        let changed = WatchEvent::Changed {
+            path: "b.rs".into(),
+            results: Vec::new(),
+        };
+        wait_for(&|event| *event == changed);
+
+        drop(watcher);
+    }
+}
@@ -0,0 +1,296 @@
+use fulf::filepath_cache::{deserialize, serialize, IndexedCache, NotUtf8};
+use fulf::fzy_algo::scoring_utils::MWP;
+use fulf::merger::{ByScore, Merger};
+use fulf::{fzy_search, Rules};
+use printer::truncate_long_matched_lines;
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::wrap_pyfunction;
+use std::collections::HashMap;
+use std::path::{is_separator, Path};
+use std::sync::Arc;
+
+/// Matched chars of every line.
+type MatchedIndicesInBatch = Vec<Vec<usize>>;
+/// Best lines first.
+type LinesInBatch = Vec<String>;
+/// The full lines of the truncated ones, by their 1-based numbers.
+///
+/// The keys are strings, because the Vim dict made from the call result
+/// can't have the number keys.
+type TruncatedMapInfo = HashMap<String, String>;
+
+/// The icon and the space after it are skipped, when the icons are enabled.
+const ICON_CHARS: usize = 2;
+/// The positions are shifted by the bytes of the icon and the space,
+/// that's where Vim highlights them.
+const ICON_OFFSET: usize = 4;
+
+fn split(results: Vec<MWP>) -> (MatchedIndicesInBatch, LinesInBatch) {
+    results
+        .into_iter()
+        .map(|(line, _score, indices)| (indices.into_vec(), line))
+        .unzip()
+}
+
+/// The part of the line, that is matched by the `fuzzy_match`.
+#[derive(Debug, Clone, Copy)]
+enum LineSplitter {
+    Full,
+    /// The `tag:row` of the tag line.
+    TagNameOnly,
+    /// The file name of the path.
+    FileNameOnly,
+    /// The text of the `path:row:col:text` grep line.
+    GrepExcludeFilePath,
+}
+
+impl LineSplitter {
+    /// Unknown splitters match the full line.
+    fn parse(splitter: &str) -> Self {
+        match splitter {
+            "TagNameOnly" => Self::TagNameOnly,
+            "FileNameOnly" => Self::FileNameOnly,
+            "GrepExcludeFilePath" => Self::GrepExcludeFilePath,
+            _ => Self::Full,
+        }
+    }
+
+    /// The byte range of the matched part, `None` if the line has no such part.
+    fn split(self, line: &str) -> Option<(usize, usize)> {
+        match self {
+            Self::Full => Some((0, line.len())),
+            Self::TagNameOnly => rfind_numbers(line, 1).map(|end| (0, end)),
+            Self::FileNameOnly => {
+                let start = line.rfind(is_separator).map_or(0, |idx| idx + 1);
+                Some((start, line.len())).filter(|&(start, end)| start < end)
+            }
+            Self::GrepExcludeFilePath => rfind_numbers(line, 2)
+                .filter(|&end| line[end..].starts_with(':'))
+                .map(|end| (end + 1, line.len())),
+        }
+    }
+}
+
+/// The end of the last `:number` chain, like the `:12:4` of `count` numbers,
+/// i.e. the byte index after the last digit.
+fn rfind_numbers(line: &str, count: usize) -> Option<usize> {
+    let chain_end = |mut idx: usize| {
+        for _ in 0..count {
+            if !line[idx..].starts_with(':') {
+                return None;
+            }
+            let rest = &line[idx + 1..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or_else(|| rest.len());
+            if digits == 0 {
+                return None;
+            }
+            idx += 1 + digits;
+        }
+        Some(idx)
+    };
+    line.rmatch_indices(':').find_map(|(idx, _)| chain_end(idx))
+}
+
+fn value_error(msg: String) -> PyErr {
+    PyErr::new::<ValueError, _>(msg)
+}
+
+fn walk(root: &str) -> PyResult<IndexedCache> {
+    let builder = Rules::new()
+        .walk_builder(root)
+        .map_err(|e| value_error(e.to_string()))?;
+    serialize(root, builder, NotUtf8::IgnorePath, |_| ())
+        .map_err(|e| value_error(format!("can't walk {}: {:?}", root, e)))
+}
+
+fn search(
+    cache: IndexedCache,
+    root: &str,
+    query: &str,
+    number: usize,
+) -> PyResult<(MatchedIndicesInBatch, LinesInBatch)> {
+    let mut merger = Merger::new(number, ByScore);
+    let r = Rules::builder()
+        .results_cap(number)
+        .build()
+        .and_then(|r| r.check_needle(query).map(|()| r))
+        .map_err(|e| value_error(e.to_string()))?;
+
+    fzy_search(
+        Path::new(root).into(),
+        query.into(),
+        Arc::new(cache),
+        r,
+        |batch| merger.merge(batch),
+    )
+    .map_err(|e| value_error(e.to_string()))?;
+
+    Ok(split(merger.into_items()))
+}
+
+/// Filter the lines of the buffer using the fzy algorithm, best `number` lines first.
+#[pyfunction]
+fn filter_lines(
+    query: &str,
+    lines: Vec<String>,
+    number: usize,
+) -> (MatchedIndicesInBatch, LinesInBatch) {
+    let mut matched = fulf::filter::filter_lines(query, lines);
+    matched.truncate(number);
+    split(matched)
+}
+
+/// Filter the candidates using the fzy algorithm, best lines first,
+/// just like the `fuzzy_match` of the python version in the `fzy.py`.
+///
+/// The spaces of the query are ignored, like in the file search.
+/// The long lines are truncated to the `winwidth`, so the matched chars are visible.
+#[pyfunction]
+fn fuzzy_match(
+    query: &str,
+    candidates: Vec<String>,
+    winwidth: usize,
+    enable_icon: bool,
+    line_splitter: &str,
+) -> (MatchedIndicesInBatch, LinesInBatch, TruncatedMapInfo) {
+    let query: String = query.split_whitespace().collect();
+    let splitter = LineSplitter::parse(line_splitter);
+    let skipped = if enable_icon { ICON_CHARS } else { 0 };
+
+    // The line, the chars before its matched part and that part.
+    let parts = candidates.into_iter().filter_map(|line| {
+        let rest = line.char_indices().nth(skipped).map_or(line.len(), |c| c.0);
+        let (start, end) = splitter.split(&line[rest..])?;
+        let (start, end) = (rest + start, rest + end);
+        let chars_before = line[rest..start].chars().count();
+        Some((line, chars_before, (start, end)))
+    });
+    let offset = if enable_icon { ICON_OFFSET } else { 0 };
+    let ranked =
+        fulf::filter::filter_by(&query, parts, |(line, _, (start, end))| &line[*start..*end])
+            .into_iter()
+            .map(|((line, chars_before, _), score, positions)| {
+                let shift = offset + chars_before;
+                let positions = positions.iter().map(|p| p + shift).collect();
+                (line, score, positions)
+            });
+
+    let skipped = if enable_icon { Some(ICON_CHARS) } else { None };
+    let (lines, truncated_map) = truncate_long_matched_lines(ranked, winwidth, skipped);
+    let (indices, lines) = lines
+        .into_iter()
+        .map(|(line, _score, indices)| (indices, line))
+        .unzip();
+    let truncated_map = truncated_map
+        .into_iter()
+        .map(|(lnum, line)| (lnum.to_string(), line))
+        .collect();
+    (indices, lines, truncated_map)
+}
+
+/// Search the files in the `root` folder, best `number` lines first.
+#[pyfunction]
+fn default_searcher(
+    py: Python,
+    root: &str,
+    query: &str,
+    number: usize,
+) -> PyResult<(MatchedIndicesInBatch, LinesInBatch)> {
+    py.allow_threads(|| search(walk(root)?, root, query, number))
+}
+
+/// Walk the `root` folder, returns the cache as bytes, that could be saved
+/// and passed to the `search_cache` later.
+#[pyfunction]
+fn serialize_cache(py: Python, root: &str) -> PyResult<PyObject> {
+    let cache = py.allow_threads(|| walk(root))?;
+    Ok(PyBytes::new(py, cache.show_cache()).into())
+}
+
+/// Search the files from the cache, made by the `serialize_cache` for the same `root`.
+#[pyfunction]
+fn search_cache(
+    py: Python,
+    cache: &[u8],
+    root: &str,
+    query: &str,
+    number: usize,
+) -> PyResult<(MatchedIndicesInBatch, LinesInBatch)> {
+    let cache = cache.to_vec();
+    py.allow_threads(|| {
+        let cache = deserialize(cache).map_err(|_| value_error("invalid cache".into()))?;
+        search(cache, root, query, number)
+    })
+}
+
+/// The paths of the cache, made by the `serialize_cache`, in the order of the search.
+#[pyfunction]
+fn cache_files(py: Python, cache: &[u8]) -> PyResult<Vec<String>> {
+    let cache = cache.to_vec();
+    py.allow_threads(|| {
+        let invalid = |_| value_error("invalid cache".into());
+        let cache = deserialize(cache).map_err(invalid)?;
+        let mut files = Vec::with_capacity(cache.files_count());
+        let mut iter = cache.stream_iter().map_err(invalid)?;
+        while let Some(path) = iter.read_next().map_err(invalid)? {
+            files.push(path.to_owned());
+        }
+        Ok(files)
+    })
+}
+
+/// This module is a python module implemented in Rust.
+#[pymodule]
+fn fulf_py(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(fuzzy_match))?;
+    m.add_wrapped(wrap_pyfunction!(filter_lines))?;
+    m.add_wrapped(wrap_pyfunction!(default_searcher))?;
+    m.add_wrapped(wrap_pyfunction!(serialize_cache))?;
+    m.add_wrapped(wrap_pyfunction!(search_cache))?;
+    m.add_wrapped(wrap_pyfunction!(cache_files))?;
+
+    Ok(())
+}
+
+#[test]
+fn filter_lines_should_work() {
+    let lines = vec!["fn main()".into(), "nothing".into(), "foo bar".into()];
+    let (indices, lines) = filter_lines("fb", lines, 1);
+
+    assert_eq!(lines.len(), 1);
+    assert_eq!(indices[0].len(), 2);
+}
+
+#[test]
+fn fuzzy_match_should_skip_icon() {
+    let lines = vec![" .dependabot/config.yml".into(), " .editorconfig".into()];
+    let (indices, lines, truncated_map) = fuzzy_match("con", lines, 62, true, "Full");
+
+    assert_eq!(lines.len(), 2);
+    assert!(indices.iter().flatten().all(|&idx| idx >= ICON_OFFSET));
+    assert!(truncated_map.is_empty());
+}
+
+#[test]
+fn line_splitters_should_choose_the_part() {
+    let grep = "src/lib.rs:12:4:fn main() {}";
+    assert_eq!(
+        LineSplitter::GrepExcludeFilePath.split(grep),
+        Some((16, grep.len()))
+    );
+    assert_eq!(LineSplitter::FileNameOnly.split("a/b/c.rs"), Some((4, 8)));
+    assert_eq!(
+        LineSplitter::TagNameOnly.split("main:60 [fn]"),
+        Some((0, 7))
+    );
+    assert_eq!(LineSplitter::GrepExcludeFilePath.split("no numbers"), None);
+
+    let (indices, lines, _) =
+        fuzzy_match("fn", vec![grep.into()], 62, false, "GrepExcludeFilePath");
+    assert_eq!(lines, [grep]);
+    assert_eq!(indices, [vec![16, 17]]);
+}
@@ -56,7 +56,7 @@ def clap_fzy_py():
 
 
 try:
-    from clap.fuzzymatch_rs import fuzzy_match as fuzzy_match_rs
+    from clap.fulf_py import fuzzy_match as fuzzy_match_rs
 
     def clap_fzy_rs():
         return fuzzy_match_rs(vim.eval("a:query"), vim.eval("a:candidates"),
@@ -6,7 +6,7 @@ import random
 import re
 import string
 
-import fuzzymatch_rs
+import fulf_py
 from fzy_impl import fzy_scorer
 
 
@@ -29,11 +29,15 @@ def fuzzy_match_py(query, candidates):
     return (indices, filtered)
 
 
+def fuzzy_match_rs(query, candidates):
+    return fulf_py.fuzzy_match(query, candidates, 62, False, 'Full')
+
+
 query = 'sr'
 candidates = open('../../test/testdata.txt', 'r').read().split('\n')
 
 print(fuzzy_match_py(query, candidates))
-print(fuzzymatch_rs.fuzzy_match(query, candidates))
+print(fuzzy_match_rs(query, candidates))
 
 
 def test_pure_python_10000(benchmark):
@@ -41,7 +45,7 @@ def test_pure_python_10000(benchmark):
 
 
 def test_rust_10000(benchmark):
-    print(benchmark(fuzzymatch_rs.fuzzy_match, query, candidates[:10000]))
+    print(benchmark(fuzzy_match_rs, query, candidates[:10000]))
 
 
 def test_pure_python_100000(benchmark):
@@ -49,7 +53,7 @@ def test_pure_python_100000(benchmark):
 
 
 def test_rust_100000(benchmark):
-    print(benchmark(fuzzymatch_rs.fuzzy_match, query, candidates[:100000]))
+    print(benchmark(fuzzy_match_rs, query, candidates[:100000]))
 
 
 def test_pure_python_200000(benchmark):
@@ -57,7 +61,7 @@ def test_pure_python_200000(benchmark):
 
 
 def test_rust_200000(benchmark):
-    print(benchmark(fuzzymatch_rs.fuzzy_match, query, candidates[:200000]))
+    print(benchmark(fuzzy_match_rs, query, candidates[:200000]))
 
 
 #  This would cost more than 30 seconds for Python.
@@ -65,10 +69,10 @@ def test_rust_200000(benchmark):
 #  print(benchmark(fuzzy_match_py, query, candidates[:500000]))
 
 #  def test_rust_500000(benchmark):
-#  print(benchmark(fuzzymatch_rs.fuzzy_match, query, candidates[:500000]))
+#  print(benchmark(fuzzy_match_rs, query, candidates[:500000]))
 
 #  def test_pure_python_800000(benchmark):
 #  print(benchmark(fuzzy_match_py, query, candidates[:800000]))
 
 #  def test_rust_800000(benchmark):
-#  print(benchmark(fuzzymatch_rs.fuzzy_match, query, candidates[:800000]))
+#  print(benchmark(fuzzy_match_rs, query, candidates[:800000]))